@@ -0,0 +1,75 @@
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use nycu_gdc_game_jam_0th::game::{Ally, AllyElement, Board, Enemy, Game, PATH_LENGTH};
+use std::hint::black_box;
+
+const ELEMENTS: [AllyElement; 8] = [
+    AllyElement::Basic,
+    AllyElement::Slow,
+    AllyElement::Aoe,
+    AllyElement::Dot,
+    AllyElement::Critical,
+    AllyElement::Chain,
+    AllyElement::Coin,
+    AllyElement::Aura,
+];
+
+/// A full 3x7 board of allies cycling through every element, with `enemy_count` enemies spread
+/// evenly along the path. Enemies have enough HP to survive the measured frames.
+fn full_board(enemy_count: usize) -> Board {
+    let ally_grid = (0..3)
+        .map(|i| {
+            (0..7)
+                .map(|j| {
+                    Some(Ally {
+                        element: ELEMENTS[(i * 7 + j) % ELEMENTS.len()],
+                        atk: 10,
+                        range: 2,
+                        aoe_range: 1,
+                        level: 1,
+                        attacks_per_second: 1.0,
+                        levelup_ratio: 1.5,
+                        special_value: 2.0,
+                        ..Default::default()
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    let enemies = (0..enemy_count)
+        .map(|k| Enemy {
+            hp: usize::MAX / 2,
+            move_speed: 1.0,
+            position: PATH_LENGTH * k as f32 / enemy_count as f32,
+            ..Default::default()
+        })
+        .collect();
+    Board {
+        ally_grid,
+        enemies,
+        enemy_ready2spawn: Vec::new(),
+    }
+}
+
+fn bench_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Game::update");
+    for enemy_count in [50, 200, 500] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(enemy_count),
+            &enemy_count,
+            |b, &enemy_count| {
+                b.iter_batched(
+                    || Game::with_board(full_board(enemy_count)),
+                    |mut game| {
+                        game.update();
+                        black_box(game)
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_update);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nycu_gdc_game_jam_0th::game::ConfigFile;
+
+// Malformed config must come back as `Err`; libFuzzer flags any panic or hang.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(content) = std::str::from_utf8(data) {
+        let _ = ConfigFile::from_toml(content);
+    }
+});
@@ -1,21 +1,36 @@
 use crate::{
+    assets, clipboard,
     event::{AppEvent, Event, EventHandler},
-    game::{Ally, AllyElement, Game},
+    export,
+    game::{
+        Ally, AllyElement, AllySelectionMode, Direction, EnemyKind, Game, GameEvent, GameMode,
+        GameState, PATH_LENGTH,
+    },
+    highscore::{HighScoreEntry, HighScores},
+    replay::{Replay, ReplayAction},
+    settings::Settings,
+    strings::Strings,
+    tutorial::TutorialStep,
 };
 use color_eyre::Result;
 use rand::seq::IndexedRandom;
 use ratatui::{
     DefaultTerminal,
-    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
 };
 use ratatui_image::{
     picker::Picker,
     protocol::{ImageSource, Protocol, StatefulProtocol},
 };
-use std::{collections::HashMap, fmt::Debug, time::Instant};
-use tachyonfx::{Duration, EffectManager};
-use tracing::info;
-use tui_logger::TuiWidgetState;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    sync::{Arc, atomic::AtomicU32},
+    time::Instant,
+};
+use tachyonfx::{Duration, EffectManager, fx};
+use tracing::{info, warn};
+use tui_logger::{TuiWidgetEvent, TuiWidgetState};
 
 /// Workaround to make TuiWidgetState `Debug`
 pub struct TuiWidgetStateWrapper(pub TuiWidgetState);
@@ -40,7 +55,7 @@ impl Debug for ProtocolWrapper {
 pub struct App {
     /// Is the application running?
     pub running: bool,
-    /// Counter.
+    /// How many allies a press of Space tries to buy, from 1 to [`MAX_BUY_QUANTITY`].
     pub counter: u8,
     /// Event handler.
     pub events: EventHandler,
@@ -51,14 +66,130 @@ pub struct App {
     pub picker: Picker,
     /// Store all images used in game
     pub image_repository: HashMap<String, ProtocolWrapper>,
+    /// When the last frame was drawn
     pub last_tick: Instant,
+    /// Whether something changed that should be drawn without waiting for the frame cap
+    pub needs_redraw: bool,
     pub effects: Effects,
+    /// Readiness of the ally in each glowing cell, keyed by `ally_grid` position and shared with
+    /// the cell's cooldown glow effect
+    pub cooldown_glows: HashMap<(usize, usize), Arc<AtomicU32>>,
+    /// Cells of allies of two elements whose colors are cycling, as `ally_grid` positions
+    pub element_cycles: HashSet<(usize, usize)>,
+    /// Whether the selected ally has its animated border, rather than the static one drawn while
+    /// the game stands still
+    pub is_selection_animated: bool,
     pub is_selection_updated: bool,
     pub is_ally_updated: bool,
+    /// Highlighted entry of the menu, an index into [`GameMode::ALL`], or one past it for the
+    /// settings entry
+    pub menu_index: usize,
+    pub settings: Settings,
+    /// Display text of the interface, translated by `lang.toml`
+    pub strings: Strings,
+    /// Highlighted row of the settings screen
+    pub settings_index: usize,
+    /// Screen the settings screen returns to, the menu or a paused game
+    pub settings_return: AppMode,
+    /// Highlighted entry of the pause menu, an index into [`PauseMenuEntry::ALL`]
+    pub pause_menu_index: usize,
+    pub high_scores: HighScores,
+    /// Whether the finished game has been written to the high scores
+    pub is_score_recorded: bool,
+    /// Recording of the current run, saved once it ends
+    pub replay: Option<Replay>,
+    /// Whether lifesteal restored a life since the last frame
+    pub is_life_restored: bool,
+    /// Whether kills paid out a bounty since the last frame
+    pub is_coin_gained: bool,
+    /// Whether the frame time overlay is shown, for performance debugging
+    pub show_perf: bool,
+    /// Time between the last two frames drawn
+    pub frame_time: std::time::Duration,
+    /// Whether a buy failed since the last frame, for lack of coins or room
+    pub is_buy_rejected: bool,
+    /// Whether ally cells are labeled with their `ally_grid` index, for debugging placement
+    pub show_coordinates: bool,
+    /// Inspect mode, while the player browses the board with the game paused
+    pub inspect: Option<Inspect>,
+    /// Whether the spawn telegraphs should pulse this frame
+    pub is_spawn_pulse_due: bool,
+    /// Attacks since the last frame to animate, as (ally grid cell, board cell of the target)
+    pub attacks: Vec<((usize, usize), (usize, usize))>,
+    /// Chain bounces since the last frame to animate, as (board cell, board cell)
+    pub bounces: Vec<((usize, usize), (usize, usize))>,
+    /// Damage popups, keyed by board cell (row, column)
+    pub damage_popups: HashMap<(usize, usize), DamagePopup>,
+    /// Current step of the tutorial, `None` outside of it or once it is done
+    pub tutorial: Option<TutorialStep>,
+    /// Panel in-game keys go to, cycled with Tab
+    pub focus: Focus,
+    /// Sale waiting for the player to press y or n
+    pub pending_sell: Option<SellOrder>,
+    /// Path cell sandbox spawns land in, counted from the entrance
+    pub sandbox_spawn_cell: usize,
+    /// Latest lines of the combat log, oldest first, at most [`COMBAT_LOG_LEN`] of them
+    pub combat_log: VecDeque<String>,
+    /// Element picker, `None` while it's closed
+    pub element_picker: Option<ElementPicker>,
+    /// Seed copied from the game-over screen, printed on exit in case the clipboard didn't take it
+    pub copied_seed: Option<u64>,
+    /// Whether the terminal reports key releases, set by `main` before the app starts. Held
+    /// keys only repeat on the app's own schedule when it does, or they'd never stop.
+    pub reports_key_releases: bool,
+    /// Arrow key held down, repeated on every tick once the key repeat delay is over
+    pub held_key: Option<HeldKey>,
+}
+
+/// Lines the combat log keeps before dropping the oldest.
+pub const COMBAT_LOG_LEN: usize = 50;
+
+/// Spawn telegraphs pulse once every this many ticks.
+const SPAWN_PULSE_TICKS: usize = 10;
+
+/// How many ticks a damage popup stays on screen.
+const DAMAGE_POPUP_TICKS: usize = 15;
+
+/// Most frames drawn per second.
+const ACTIVE_FPS: f64 = 60.0;
+
+/// Frames drawn per second while nothing on screen moves, see [`App::is_idle`].
+const IDLE_FPS: f64 = 10.0;
+
+/// Most allies a single press of Space can buy.
+pub const MAX_BUY_QUANTITY: u8 = 9;
+
+/// Damage recently dealt in a board cell, shown over it for a moment.
+#[derive(Debug, Clone)]
+pub struct DamagePopup {
+    /// Total HP taken off enemies in the cell by the latest hits.
+    pub damage: usize,
+    /// Matchup multiplier of the latest hit.
+    pub effectiveness: f32,
+    pub ticks_left: usize,
 }
 
 pub struct Effects(pub EffectManager<UniqueEffectId>);
 
+impl Effects {
+    /// Stops the cooldown glows of the ally cells at `cells`, given as `ally_grid` positions.
+    pub fn stop_cooldown_glows(&mut self, cells: impl IntoIterator<Item = (usize, usize)>) {
+        for (row, col) in cells {
+            self.0
+                .add_unique_effect(UniqueEffectId::CooldownGlow(row, col), fx::sleep(0));
+        }
+    }
+
+    /// Stops the element color cycles of the ally cells at `cells`, given as `ally_grid`
+    /// positions.
+    pub fn stop_element_cycles(&mut self, cells: impl IntoIterator<Item = (usize, usize)>) {
+        for (row, col) in cells {
+            self.0
+                .add_unique_effect(UniqueEffectId::ElementCycle(row, col), fx::sleep(0));
+        }
+    }
+}
+
 impl Debug for Effects {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Effects")
@@ -70,100 +201,707 @@ pub enum UniqueEffectId {
     #[default]
     Selected,
     Hover,
+    /// The cooldown glow of the ally cell at an `ally_grid` position.
+    CooldownGlow(usize, usize),
+    /// The color cycle of the ally of two elements at an `ally_grid` position.
+    ElementCycle(usize, usize),
+}
+
+/// Inspect mode, held in [`App::inspect`]: the game stands still while a cursor browses every
+/// cell of the board, path included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inspect {
+    /// Board cell (row, column) under the inspect cursor.
+    pub cell: (usize, usize),
+    /// Whether the game was running when inspect mode began, so leaving it resumes the game.
+    pub resumes: bool,
+}
+
+/// Keys that repeat while held down: the arrows, which move every cursor and highlight.
+fn is_arrow_key(code: KeyCode) -> bool {
+    matches!(
+        code,
+        KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+    )
+}
+
+/// An arrow key held down, held in [`App::held_key`] until its release comes in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeldKey {
+    /// The press, handled again on every repeat.
+    pub key: KeyEvent,
+    pub pressed_at: Instant,
+    pub last_repeat: Instant,
+}
+
+/// The element picker, held in [`App::element_picker`] while the player chooses an ally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementPicker {
+    /// Highlighted entry, in [`AllyElement::ALL`].
+    pub index: usize,
+    /// Grid cell the chosen ally is built on, `None` when buying onto random cells.
+    pub build_at: Option<(usize, usize)>,
+}
+
+/// Allies to sell, held in [`App::pending_sell`] while the player confirms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SellOrder {
+    /// The ally at a grid position.
+    One((usize, usize)),
+    /// Every single-element ally of an element.
+    AllOf(AllyElement),
+}
+
+/// Panel of the game screen that in-game keys are routed to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    /// The board: cursor, selection and merging.
+    #[default]
+    Grid,
+    /// The event log.
+    Events,
+    /// The merge panel.
+    Shop,
+}
+
+impl Focus {
+    pub fn next(self) -> Focus {
+        match self {
+            Focus::Grid => Focus::Events,
+            Focus::Events => Focus::Shop,
+            Focus::Shop => Focus::Grid,
+        }
+    }
+}
+
+/// Entries of the menu shown over a paused game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMenuEntry {
+    Resume,
+    Restart,
+    Settings,
+    QuitToMenu,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl PauseMenuEntry {
+    pub const ALL: [PauseMenuEntry; 4] = [
+        PauseMenuEntry::Resume,
+        PauseMenuEntry::Restart,
+        PauseMenuEntry::Settings,
+        PauseMenuEntry::QuitToMenu,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PauseMenuEntry::Resume => "Resume",
+            PauseMenuEntry::Restart => "Restart",
+            PauseMenuEntry::Settings => "Settings",
+            PauseMenuEntry::QuitToMenu => "Quit to menu",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppMode {
     Menu,
     InGame,
+    Settings,
 }
 
 impl Default for App {
     fn default() -> Self {
+        App::with_picker(Picker::from_query_stdio().expect("failed to init app.picker"))
+    }
+}
+
+impl App {
+    /// Constructs a new instance of [`App`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An [`App`] drawing images with `picker`, instead of one queried from the terminal.
+    pub fn with_picker(picker: Picker) -> Self {
+        let settings = Settings::load();
+        let menu_index = GameMode::ALL
+            .iter()
+            .position(|m| *m == settings.default_mode)
+            .unwrap_or_default();
         Self {
             running: true,
-            counter: 0,
+            counter: 1,
             events: EventHandler::new(),
             game: None,
             mode: AppMode::Menu,
             log_state: TuiWidgetStateWrapper(TuiWidgetState::default()),
-            picker: Picker::from_query_stdio().expect("failed to init app.picker"),
+            picker,
             image_repository: HashMap::new(),
             effects: Effects(EffectManager::default()),
+            cooldown_glows: HashMap::new(),
+            element_cycles: HashSet::new(),
+            is_selection_animated: false,
             last_tick: Instant::now(),
+            needs_redraw: true,
             is_selection_updated: false,
             is_ally_updated: false,
+            menu_index,
+            settings,
+            strings: Strings::load(),
+            settings_index: 0,
+            settings_return: AppMode::Menu,
+            pause_menu_index: 0,
+            high_scores: HighScores::load(),
+            is_score_recorded: false,
+            replay: None,
+            is_life_restored: false,
+            is_coin_gained: false,
+            is_buy_rejected: false,
+            show_perf: false,
+            frame_time: std::time::Duration::ZERO,
+            show_coordinates: false,
+            inspect: None,
+            is_spawn_pulse_due: false,
+            attacks: Vec::new(),
+            bounces: Vec::new(),
+            damage_popups: HashMap::new(),
+            tutorial: None,
+            focus: Focus::default(),
+            pending_sell: None,
+            sandbox_spawn_cell: 0,
+            combat_log: VecDeque::with_capacity(COMBAT_LOG_LEN),
+            element_picker: None,
+            copied_seed: None,
+            reports_key_releases: false,
+            held_key: None,
         }
     }
-}
-
-impl App {
-    /// Constructs a new instance of [`App`].
-    pub fn new() -> Self {
-        Self::default()
-    }
 
     /// Run the application's main loop.
-    pub fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
+    pub fn run(&mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
         while self.running {
-            let duration = self.last_tick.elapsed().into();
-            self.last_tick = Instant::now();
-            terminal.draw(|frame| {
-                frame.render_widget(&mut self, frame.area());
-                let area = frame.area();
-                self.effects
-                    .0
-                    .process_effects(duration, frame.buffer_mut(), area);
-            })?;
+            // Input is drawn as soon as the cap allows, everything else at the frame rate
+            let elapsed = self.last_tick.elapsed();
+            let fps = if self.is_idle() { IDLE_FPS } else { ACTIVE_FPS };
+            let is_frame_due = elapsed.as_secs_f64() >= 1.0 / fps;
+            let can_draw = elapsed.as_secs_f64() >= 1.0 / ACTIVE_FPS;
+            if is_frame_due || (self.needs_redraw && can_draw) {
+                self.needs_redraw = false;
+                self.last_tick = Instant::now();
+                self.frame_time = elapsed;
+                // Effects hold still behind the pause menu
+                let fx_elapsed = if self.is_pause_menu_open() {
+                    std::time::Duration::ZERO
+                } else {
+                    elapsed
+                };
+                terminal.draw(|frame| {
+                    frame.render_widget(&mut *self, frame.area());
+                    let area = frame.area();
+                    if !self.settings.reduce_motion {
+                        self.effects
+                            .0
+                            .process_effects(fx_elapsed.into(), frame.buffer_mut(), area);
+                    }
+                })?;
+            }
             self.handle_events()?;
         }
         Ok(())
     }
 
+    /// Sells right away, or asks first if the order includes an ally at or above the
+    /// confirmation level of the settings.
+    fn request_sell(&mut self, order: SellOrder) {
+        let Some(level) = self.sell_order_level(order) else {
+            info!("no ally under the cursor to sell");
+            return;
+        };
+        let threshold = self.settings.confirm_sell_level;
+        if threshold > 0 && level >= threshold {
+            self.pending_sell = Some(order);
+        } else {
+            self.sell(order);
+        }
+    }
+
+    /// Highest level among the allies `order` would sell, `None` if it sells nothing.
+    pub fn sell_order_level(&self, order: SellOrder) -> Option<usize> {
+        let game = self.game.as_ref()?;
+        match order {
+            SellOrder::One((y, x)) => game.board.ally_grid[y][x].as_ref().map(|a| a.level),
+            SellOrder::AllOf(element) => game.allies_of_element(element).map(|a| a.level).max(),
+        }
+    }
+
+    fn sell(&mut self, order: SellOrder) {
+        self.record(match order {
+            SellOrder::One(cell) => ReplayAction::Sell { cell },
+            SellOrder::AllOf(element) => ReplayAction::SellAllOf { element },
+        });
+        let Some(game) = self.game.as_mut() else {
+            return;
+        };
+        match order {
+            SellOrder::One(pos) => {
+                game.sell_ally(pos);
+            }
+            SellOrder::AllOf(element) => {
+                game.sell_all_of_element(element);
+            }
+        }
+        self.is_selection_updated = true;
+        self.is_ally_updated = true;
+    }
+
+    /// Whether the pause menu is shown, which is whenever the game is paused outside of the
+    /// tutorial.
+    pub fn is_pause_menu_open(&self) -> bool {
+        self.mode == AppMode::InGame
+            && self.tutorial.is_none()
+            && self.inspect.is_none()
+            && self
+                .game
+                .as_ref()
+                .is_some_and(|g| matches!(g.game_state, GameState::Pause))
+    }
+
+    /// Whether nothing on screen is moving: on the menu or a stopped game, with no effect
+    /// playing. Effects hold still behind the pause menu, so it is idle whatever they are.
+    pub fn is_idle(&self) -> bool {
+        if self.is_pause_menu_open() {
+            return true;
+        }
+        let is_stopped = match self.mode {
+            AppMode::Menu | AppMode::Settings => true,
+            AppMode::InGame => self
+                .game
+                .as_ref()
+                .is_none_or(|g| matches!(g.game_state, GameState::Pause | GameState::End)),
+        };
+        is_stopped && (self.settings.reduce_motion || !self.effects.0.is_running())
+    }
+
     pub fn handle_events(&mut self) -> color_eyre::Result<()> {
-        match self.events.next()? {
-            Event::Tick => self.tick(),
+        let event = self.events.next()?;
+        if !matches!(event, Event::Tick) {
+            self.needs_redraw = true;
+        }
+        match event {
+            Event::Tick => {
+                self.tick();
+                self.repeat_held_key()?;
+            }
             Event::Crossterm(event) => match event {
-                crossterm::event::Event::Key(key_event) => self.handle_key_event(key_event)?,
+                crossterm::event::Event::Key(key_event) => self.handle_key_input(key_event)?,
+                crossterm::event::Event::Resize(_, _) => self.handle_resize(),
+                // The release may never come while another window has the keys
+                crossterm::event::Event::FocusLost => self.held_key = None,
                 _ => {}
             },
-            Event::App(app_event) => match app_event {
-                AppEvent::Increment => self.increment_counter(),
-                AppEvent::Decrement => self.decrement_counter(),
-                AppEvent::Quit => self.quit(),
-                AppEvent::StartGame => {
-                    assert_eq!(AppMode::Menu, self.mode);
-                    self.game = Some(Game::new());
-                    self.game.as_mut().unwrap().init_game();
-                    self.init_image_repository()
-                        .expect("failed to read image assets");
-                    self.mode = AppMode::InGame;
-                }
-                AppEvent::MoveCursor(direction) => {
-                    assert!(self.game.is_some());
-                    self.game.as_mut().unwrap().cursor_move(direction);
-                }
-                AppEvent::ToggleSelection => {
-                    assert!(self.game.is_some());
-                    self.game.as_mut().unwrap().cursor_select();
-                    self.is_selection_updated = true;
-                    self.is_ally_updated = true;
+            Event::App(app_event) => {
+                let observed = app_event.clone();
+                match app_event {
+                    AppEvent::Increment => self.increment_counter(),
+                    AppEvent::Decrement => self.decrement_counter(),
+                    AppEvent::Quit => self.quit(),
+                    AppEvent::StartGame(game_mode) => {
+                        assert_eq!(AppMode::Menu, self.mode);
+                        let mut game = Game::with_mode(game_mode);
+                        game.auto_buy = self.settings.auto_buy;
+                        game.auto_buy_threshold = self.settings.auto_buy_threshold;
+                        game.auto_merge_on_buy = self.settings.auto_merge_on_buy;
+                        game.keep_selection_on_failed_merge =
+                            self.settings.keep_selection_on_failed_merge;
+                        // The daily challenge ignores config.toml, see Game::init_game
+                        let config = (game_mode != GameMode::Daily)
+                            .then(|| std::fs::read_to_string("config.toml").ok())
+                            .flatten();
+                        let mut replay = Replay::new(&game, config);
+                        game.init_game();
+                        self.narrate(&game.events);
+                        replay.record_input(&game, self.settings_action());
+                        self.replay = Some(replay);
+                        self.game = Some(game);
+                        self.combat_log.clear();
+                        self.log_combat(0);
+                        if self.image_repository.is_empty() {
+                            self.init_image_repository()
+                                .expect("failed to read image assets");
+                        }
+                        self.is_score_recorded = false;
+                        self.damage_popups.clear();
+                        self.tutorial =
+                            (game_mode == GameMode::Tutorial).then_some(TutorialStep::FIRST);
+                        self.sandbox_spawn_cell = 0;
+                        self.focus = Focus::default();
+                        self.mode = AppMode::InGame;
+                    }
+                    AppEvent::BackToMenu => {
+                        self.game = None;
+                        self.replay = None;
+                        self.pending_sell = None;
+                        self.inspect = None;
+                        self.element_picker = None;
+                        self.attacks.clear();
+                        self.bounces.clear();
+                        self.tutorial = None;
+                        // Endless board effects would keep the menu from idling
+                        self.effects = Effects(EffectManager::default());
+                        self.cooldown_glows.clear();
+                        self.element_cycles.clear();
+                        self.is_selection_animated = false;
+                        self.mode = AppMode::Menu;
+                    }
+                    AppEvent::MenuMove(direction) => {
+                        // One entry per mode, then settings
+                        let len = GameMode::ALL.len() + 1;
+                        self.menu_index = match direction {
+                            Direction::Up => (self.menu_index + len - 1) % len,
+                            Direction::Down => (self.menu_index + 1) % len,
+                            _ => self.menu_index,
+                        };
+                    }
+                    AppEvent::OpenSettings => {
+                        self.settings_index = 0;
+                        self.settings_return = self.mode;
+                        self.mode = AppMode::Settings;
+                    }
+                    AppEvent::CloseSettings => {
+                        // Settings changed mid-run apply to the run
+                        if let Some(game) = self.game.as_mut() {
+                            game.auto_buy = self.settings.auto_buy;
+                            game.auto_buy_threshold = self.settings.auto_buy_threshold;
+                            game.auto_merge_on_buy = self.settings.auto_merge_on_buy;
+                            game.keep_selection_on_failed_merge =
+                                self.settings.keep_selection_on_failed_merge;
+                        }
+                        self.record(self.settings_action());
+                        self.mode = self.settings_return;
+                    }
+                    AppEvent::PauseMenuMove(direction) => {
+                        let len = PauseMenuEntry::ALL.len();
+                        self.pause_menu_index = match direction {
+                            Direction::Up => (self.pause_menu_index + len - 1) % len,
+                            Direction::Down => (self.pause_menu_index + 1) % len,
+                            _ => self.pause_menu_index,
+                        };
+                    }
+                    AppEvent::PauseMenuSelect => {
+                        let event = match PauseMenuEntry::ALL[self.pause_menu_index] {
+                            PauseMenuEntry::Resume => AppEvent::TogglePause,
+                            PauseMenuEntry::Restart => AppEvent::RestartGame,
+                            PauseMenuEntry::Settings => AppEvent::OpenSettings,
+                            PauseMenuEntry::QuitToMenu => AppEvent::BackToMenu,
+                        };
+                        self.events.send(event);
+                    }
+                    AppEvent::RestartGame => {
+                        assert!(self.game.is_some());
+                        let game_mode = self.game.as_ref().unwrap().mode;
+                        self.events.send(AppEvent::BackToMenu);
+                        self.events.send(AppEvent::StartGame(game_mode));
+                    }
+                    AppEvent::SettingsMove(direction) => {
+                        let len = Settings::LEN;
+                        self.settings_index = match direction {
+                            Direction::Up => (self.settings_index + len - 1) % len,
+                            Direction::Down => (self.settings_index + 1) % len,
+                            _ => self.settings_index,
+                        };
+                    }
+                    AppEvent::ChangeSetting => {
+                        self.settings.cycle(self.settings_index);
+                        if let Err(err) = self.settings.save() {
+                            warn!(%err, "failed to save settings");
+                        }
+                    }
+                    AppEvent::MoveCursor(direction) => {
+                        assert!(self.game.is_some());
+                        self.game.as_mut().unwrap().cursor_move(direction);
+                    }
+                    AppEvent::ToggleSelection => {
+                        assert!(self.game.is_some());
+                        self.record(ReplayAction::ToggleSelection);
+                        let game = self.game.as_mut().unwrap();
+                        let seen = game.events.len();
+                        game.cursor_select();
+                        let game = self.game.as_ref().unwrap();
+                        self.narrate(&game.events[seen..]);
+                        self.log_combat(seen);
+                        self.is_selection_updated = true;
+                        self.is_ally_updated = true;
+                    }
+                    AppEvent::SellAlly => {
+                        assert!(self.game.is_some());
+                        let cursor = self.game.as_ref().unwrap().cursor;
+                        self.request_sell(SellOrder::One(cursor));
+                    }
+                    AppEvent::SellAllOfElement => {
+                        assert!(self.game.is_some());
+                        let game = self.game.as_ref().unwrap();
+                        let (y, x) = game.cursor;
+                        match game.board.ally_grid[y][x].as_ref().map(|a| a.element) {
+                            Some(element) => self.request_sell(SellOrder::AllOf(element)),
+                            None => info!("no ally under the cursor to sell"),
+                        }
+                    }
+                    AppEvent::ConfirmSell => {
+                        if let Some(order) = self.pending_sell.take() {
+                            self.sell(order);
+                        }
+                    }
+                    AppEvent::CancelSell => {
+                        self.pending_sell = None;
+                    }
+                    AppEvent::Undo => {
+                        assert!(self.game.is_some());
+                        self.record(ReplayAction::Undo);
+                        let game = self.game.as_mut().unwrap();
+                        if game.undo_merge() || game.undo_move() {
+                            self.is_selection_updated = true;
+                            self.is_ally_updated = true;
+                        }
+                    }
+                    AppEvent::CycleFocus => {
+                        self.focus = self.focus.next();
+                    }
+                    AppEvent::ScrollLog(direction) => {
+                        let event = match direction {
+                            Direction::Up => TuiWidgetEvent::PrevPageKey,
+                            _ => TuiWidgetEvent::NextPageKey,
+                        };
+                        self.log_state.0.transition(event);
+                    }
+                    AppEvent::CycleTargeting => {
+                        assert!(self.game.is_some());
+                        self.record(ReplayAction::CycleTargeting);
+                        self.game.as_mut().unwrap().cycle_targeting();
+                    }
+                    AppEvent::RotateAlly => {
+                        assert!(self.game.is_some());
+                        self.record(ReplayAction::RotateAlly);
+                        self.game.as_mut().unwrap().rotate_ally();
+                    }
+                    AppEvent::FocusFire => {
+                        assert!(self.game.is_some());
+                        self.record(ReplayAction::FocusFire);
+                        self.game.as_mut().unwrap().focus_fire();
+                    }
+                    AppEvent::ToggleCoordinates => {
+                        self.show_coordinates = !self.show_coordinates;
+                    }
+                    AppEvent::ToggleInspect => {
+                        assert!(self.game.is_some());
+                        if let Some(inspect) = self.inspect.take() {
+                            if inspect.resumes {
+                                self.record(ReplayAction::Resume);
+                                self.game.as_mut().unwrap().state_resume();
+                            }
+                        } else {
+                            let game = self.game.as_mut().unwrap();
+                            let resumes = matches!(game.game_state, GameState::Running);
+                            game.state_pause();
+                            // Start on the ally cell under the grid cursor
+                            let cell = (game.cursor.0 + 1, game.cursor.1 + 1);
+                            self.inspect = Some(Inspect { cell, resumes });
+                            if resumes {
+                                self.record(ReplayAction::TogglePause);
+                            }
+                        }
+                    }
+                    AppEvent::InspectMove(direction) => {
+                        if let Some(inspect) = self.inspect.as_mut() {
+                            inspect.cell = direction.step_on_board(inspect.cell);
+                        }
+                    }
+                    AppEvent::ExportState => {
+                        assert!(self.game.is_some());
+                        match export::export(self.game.as_ref().unwrap()) {
+                            Ok(path) => info!(path = %path.display(), "exported game state"),
+                            Err(err) => warn!(%err, "failed to export game state"),
+                        }
+                    }
+                    AppEvent::TogglePerfOverlay => {
+                        self.show_perf = !self.show_perf;
+                    }
+                    // The tutorial holds the game still until its last step asks to start
+                    AppEvent::TogglePause
+                        if self
+                            .tutorial
+                            .is_some_and(|step| step != TutorialStep::Start) => {}
+                    AppEvent::TogglePause => {
+                        assert!(self.game.is_some());
+                        self.record(ReplayAction::TogglePause);
+                        self.game.as_mut().unwrap().toggle_pause();
+                        self.pause_menu_index = 0;
+                    }
+                    AppEvent::BuyAlly
+                        if self.game.as_ref().unwrap().ally_selection_mode()
+                            == AllySelectionMode::Choose =>
+                    {
+                        self.element_picker = Some(ElementPicker {
+                            index: 0,
+                            build_at: None,
+                        });
+                    }
+                    AppEvent::OpenBuildMenu => {
+                        assert!(self.game.is_some());
+                        let game = self.game.as_ref().unwrap();
+                        let (i, j) = game.cursor;
+                        if game.board.ally_grid[i][j].is_some() {
+                            info!("cell taken, nothing to build on");
+                            self.is_buy_rejected = true;
+                        } else {
+                            self.element_picker = Some(ElementPicker {
+                                index: 0,
+                                build_at: Some((i, j)),
+                            });
+                        }
+                    }
+                    AppEvent::BuyAlly => {
+                        assert!(self.game.is_some());
+                        let quantity = self.counter as usize;
+                        self.record(ReplayAction::Buy { count: quantity });
+                        if self.game.as_mut().unwrap().buy_allies(quantity) > 0 {
+                            self.is_ally_updated = true;
+                        } else {
+                            self.is_buy_rejected = true;
+                        }
+                    }
+                    AppEvent::ElementPickerMove(direction) => {
+                        let len = AllyElement::ALL.len();
+                        if let Some(picker) = self.element_picker.as_mut() {
+                            picker.index = match direction {
+                                Direction::Up | Direction::Left => (picker.index + len - 1) % len,
+                                _ => (picker.index + 1) % len,
+                            };
+                        }
+                    }
+                    AppEvent::BuyAllyOf(element) => {
+                        assert!(self.game.is_some());
+                        self.element_picker = None;
+                        let count = self.counter as usize;
+                        self.record(ReplayAction::BuyOf { element, count });
+                        if self.game.as_mut().unwrap().buy_allies_of(element, count) > 0 {
+                            self.is_ally_updated = true;
+                        } else {
+                            self.is_buy_rejected = true;
+                        }
+                    }
+                    AppEvent::BuildAlly(element) => {
+                        assert!(self.game.is_some());
+                        if let Some(cell) = self.element_picker.take().and_then(|p| p.build_at) {
+                            self.record(ReplayAction::Build { cell, element });
+                            if self.game.as_mut().unwrap().build_ally_at(cell, element) {
+                                self.is_ally_updated = true;
+                            } else {
+                                self.is_buy_rejected = true;
+                            }
+                        }
+                    }
+                    AppEvent::CloseElementPicker => self.element_picker = None,
+                    AppEvent::SkipTutorial => {
+                        self.tutorial = None;
+                        self.record(ReplayAction::Resume);
+                        if let Some(game) = self.game.as_mut() {
+                            game.state_resume();
+                        }
+                    }
+                    AppEvent::SandboxSpawn(kind) => {
+                        assert!(self.game.is_some());
+                        let position = self.sandbox_spawn_cell as f32;
+                        self.record(ReplayAction::SandboxSpawn { kind, position });
+                        self.game
+                            .as_mut()
+                            .unwrap()
+                            .debug_spawn_enemy(kind, position);
+                    }
+                    AppEvent::MoveSandboxSpawn(direction) => {
+                        let cells = PATH_LENGTH as usize;
+                        self.sandbox_spawn_cell = match direction {
+                            Direction::Left => (self.sandbox_spawn_cell + cells - 1) % cells,
+                            _ => (self.sandbox_spawn_cell + 1) % cells,
+                        };
+                    }
+                    AppEvent::GrantCoins => {
+                        assert!(self.game.is_some());
+                        self.record(ReplayAction::GrantCoins);
+                        self.game.as_mut().unwrap().grant_coins();
+                    }
+                    AppEvent::CopySeed => {
+                        assert!(self.game.is_some());
+                        let seed = self.game.as_ref().unwrap().seed;
+                        self.copied_seed = Some(seed);
+                        // There is no telling whether the terminal took it, so log it either way
+                        match clipboard::copy(&seed.to_string()) {
+                            Ok(()) => info!(seed, "copied seed to the clipboard"),
+                            Err(err) => warn!(%err, seed, "failed to copy seed, note it down"),
+                        }
+                    }
                 }
-                AppEvent::BuyAlly => {
-                    assert!(self.game.is_some());
-                    self.game.as_mut().unwrap().buy_ally();
-                    self.is_ally_updated = true;
-                }
-            },
+                self.advance_tutorial(&observed);
+            }
         }
         Ok(())
     }
 
+    /// Moves the tutorial on if `event`, already applied to the game, did what the current step
+    /// asks for. The game is let run once the last step is done.
+    fn advance_tutorial(&mut self, event: &AppEvent) {
+        let (Some(step), Some(game)) = (self.tutorial, self.game.as_mut()) else {
+            return;
+        };
+        self.tutorial = step.advance(event, game);
+        if self.tutorial.is_none() {
+            if let Some(replay) = self.replay.as_mut() {
+                replay.record_input(game, ReplayAction::Resume);
+            }
+            game.state_resume();
+        }
+    }
+
+    /// Notes `action`, about to be applied to the game, in the run's replay.
+    fn record(&mut self, action: ReplayAction) {
+        if let (Some(replay), Some(game)) = (self.replay.as_mut(), self.game.as_ref()) {
+            replay.record_input(game, action);
+        }
+    }
+
+    /// The settings the game plays by, as a replay input.
+    fn settings_action(&self) -> ReplayAction {
+        ReplayAction::Settings {
+            auto_buy: self.settings.auto_buy,
+            auto_buy_threshold: self.settings.auto_buy_threshold,
+            auto_merge_on_buy: self.settings.auto_merge_on_buy,
+            keep_selection_on_failed_merge: self.settings.keep_selection_on_failed_merge,
+        }
+    }
+
+    /// The board is laid out anew on the next frame, but unique effects keep the cell rect they
+    /// were issued with. Flags them to be issued again against the new layout, so they don't
+    /// drift off their cells.
+    fn handle_resize(&mut self) {
+        if self
+            .game
+            .as_ref()
+            .is_some_and(|game| game.selected.is_some())
+        {
+            self.is_selection_updated = true;
+        }
+        self.effects
+            .stop_cooldown_glows(self.cooldown_glows.drain().map(|(cell, _)| cell));
+        self.effects
+            .stop_element_cycles(self.element_cycles.drain());
+    }
+
+    /// Loads every avatar, keyed by its path relative to the asset directory like
+    /// [`Ally::avatar_path`].
     fn init_image_repository(&mut self) -> Result<()> {
-        let image_paths = std::fs::read_dir("assets/avatars/")?
+        let asset_dir = assets::asset_dir();
+        let image_paths = std::fs::read_dir(asset_dir.join("avatars"))?
             .map(|r| r.map(|e| e.path()))
             .collect::<Result<Vec<_>, _>>()?;
-        info!(count = image_paths.len(), "load image");
+        info!(count = image_paths.len(), dir = %asset_dir.display(), "load image");
         for p in &image_paths {
             info!(path = p.to_str(), "load single image");
         }
@@ -177,43 +915,261 @@ impl App {
         self.image_repository.extend(
             image_paths
                 .into_iter()
-                .map(|e| e.to_string_lossy().to_string())
+                .map(|p| {
+                    let file_name = p.file_name().unwrap_or_default().to_string_lossy();
+                    format!("avatars/{file_name}")
+                })
                 .zip(image_sources),
         );
         Ok(())
     }
 
+    /// Sorts presses from releases and repeats, keeping track of the arrow key held down so
+    /// [`App::repeat_held_key`] can repeat it.
+    fn handle_key_input(&mut self, key_event: KeyEvent) -> color_eyre::Result<()> {
+        let is_repeating = self.reports_key_releases && self.settings.key_repeat().is_some();
+        match key_event.kind {
+            KeyEventKind::Release => {
+                if self
+                    .held_key
+                    .is_some_and(|held| held.key.code == key_event.code)
+                {
+                    self.held_key = None;
+                }
+                Ok(())
+            }
+            // The app repeats held arrow keys itself, on the rate of the settings
+            KeyEventKind::Repeat if is_repeating && is_arrow_key(key_event.code) => Ok(()),
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                self.held_key = (is_repeating && is_arrow_key(key_event.code)).then(|| {
+                    let now = Instant::now();
+                    HeldKey {
+                        key: key_event,
+                        pressed_at: now,
+                        last_repeat: now,
+                    }
+                });
+                self.handle_key_event(key_event)
+            }
+        }
+    }
+
+    /// Handles the held arrow key again if the key repeat delay is over and a repeat is due.
+    fn repeat_held_key(&mut self) -> color_eyre::Result<()> {
+        let (Some(held), Some((delay, interval))) = (self.held_key, self.settings.key_repeat())
+        else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let since_last = now.duration_since(held.last_repeat);
+        if now.duration_since(held.pressed_at) < delay
+            || (held.last_repeat != held.pressed_at && since_last < interval)
+        {
+            return Ok(());
+        }
+        self.held_key = Some(HeldKey {
+            last_repeat: now,
+            ..held
+        });
+        self.needs_redraw = true;
+        self.handle_key_event(held.key)
+    }
+
     /// Handles the key events and updates the state of [`App`].
     pub fn handle_key_event(&mut self, key_event: KeyEvent) -> color_eyre::Result<()> {
+        // The performance overlay toggles on every screen, whatever else has the keys
+        if key_event.code == KeyCode::F(3) {
+            self.events.send(AppEvent::TogglePerfOverlay);
+            return Ok(());
+        }
+        // A sale prompt takes every key until it's answered
+        if self.pending_sell.is_some() {
+            match key_event.code {
+                KeyCode::Char('y' | 'Y') => self.events.send(AppEvent::ConfirmSell),
+                KeyCode::Char('n' | 'N') | KeyCode::Esc => self.events.send(AppEvent::CancelSell),
+                KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    self.events.send(AppEvent::Quit)
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        // And the element picker, until an ally is bought, it's closed or the game ends
+        let is_running = self
+            .game
+            .as_ref()
+            .is_some_and(|g| !matches!(g.game_state, GameState::End));
+        if let Some(picker) = self.element_picker.filter(|_| is_running) {
+            let choose = |element| match picker.build_at {
+                Some(_) => AppEvent::BuildAlly(element),
+                None => AppEvent::BuyAllyOf(element),
+            };
+            match key_event.code {
+                KeyCode::Up => self.events.send(AppEvent::ElementPickerMove(Direction::Up)),
+                KeyCode::Down => self
+                    .events
+                    .send(AppEvent::ElementPickerMove(Direction::Down)),
+                KeyCode::Enter => self.events.send(choose(AllyElement::ALL[picker.index])),
+                KeyCode::Char(c @ '1'..='8') => {
+                    let element = AllyElement::ALL[c as usize - '1' as usize];
+                    self.events.send(choose(element));
+                }
+                KeyCode::Esc | KeyCode::Char(' ') => self.events.send(AppEvent::CloseElementPicker),
+                KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    self.events.send(AppEvent::Quit)
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        // And inspect mode, until it's left
+        if self.inspect.is_some() {
+            match key_event.code {
+                KeyCode::Up => self.events.send(AppEvent::InspectMove(Direction::Up)),
+                KeyCode::Down => self.events.send(AppEvent::InspectMove(Direction::Down)),
+                KeyCode::Left => self.events.send(AppEvent::InspectMove(Direction::Left)),
+                KeyCode::Right => self.events.send(AppEvent::InspectMove(Direction::Right)),
+                KeyCode::Esc | KeyCode::Char('i') => self.events.send(AppEvent::ToggleInspect),
+                KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    self.events.send(AppEvent::Quit)
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        // So does the pause menu, until the game is resumed
+        if self.is_pause_menu_open() {
+            match key_event.code {
+                KeyCode::Up => self.events.send(AppEvent::PauseMenuMove(Direction::Up)),
+                KeyCode::Down => self.events.send(AppEvent::PauseMenuMove(Direction::Down)),
+                KeyCode::Enter => self.events.send(AppEvent::PauseMenuSelect),
+                KeyCode::Esc | KeyCode::Char('p' | 'P') => self.events.send(AppEvent::TogglePause),
+                KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+                    self.events.send(AppEvent::Quit)
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
         match key_event.code {
+            KeyCode::Esc if matches!(self.mode, AppMode::Settings) => {
+                self.events.send(AppEvent::CloseSettings);
+            }
             KeyCode::Esc | KeyCode::Char('q') => self.events.send(AppEvent::Quit),
             KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
                 self.events.send(AppEvent::Quit)
             }
+            // Crashes on purpose, to check the terminal is restored when the game panics
+            KeyCode::F(12) if cfg!(debug_assertions) => panic!("debug panic key pressed"),
             KeyCode::Enter if matches!(self.mode, AppMode::Menu) => {
-                self.events.send(AppEvent::StartGame);
+                match GameMode::ALL.get(self.menu_index) {
+                    Some(&game_mode) => self.events.send(AppEvent::StartGame(game_mode)),
+                    None => self.events.send(AppEvent::OpenSettings),
+                }
+            }
+            KeyCode::Up if matches!(self.mode, AppMode::Menu) => {
+                self.events.send(AppEvent::MenuMove(Direction::Up));
+            }
+            KeyCode::Down if matches!(self.mode, AppMode::Menu) => {
+                self.events.send(AppEvent::MenuMove(Direction::Down));
+            }
+            KeyCode::Up if matches!(self.mode, AppMode::Settings) => {
+                self.events.send(AppEvent::SettingsMove(Direction::Up));
+            }
+            KeyCode::Down if matches!(self.mode, AppMode::Settings) => {
+                self.events.send(AppEvent::SettingsMove(Direction::Down));
+            }
+            KeyCode::Enter | KeyCode::Left | KeyCode::Right
+                if matches!(self.mode, AppMode::Settings) =>
+            {
+                self.events.send(AppEvent::ChangeSetting);
             }
             // Other handlers you could add here.
             _ => {}
         }
 
-        if matches!(self.mode, AppMode::InGame) {
+        let is_game_over = self
+            .game
+            .as_ref()
+            .is_some_and(|g| matches!(g.game_state, GameState::End));
+        if matches!(self.mode, AppMode::InGame) && is_game_over {
+            match key_event.code {
+                KeyCode::Enter => self.events.send(AppEvent::BackToMenu),
+                KeyCode::Char('c') if key_event.modifiers.is_empty() => {
+                    self.events.send(AppEvent::CopySeed)
+                }
+                _ => {}
+            }
+        } else if matches!(self.mode, AppMode::InGame) {
+            // Keys that work whichever panel has the focus
             match key_event.code {
-                KeyCode::Up => self
+                KeyCode::Char(' ') => {
+                    self.events.send(AppEvent::BuyAlly);
+                }
+                KeyCode::Char('+' | '=') => self.events.send(AppEvent::Increment),
+                KeyCode::Char('-') => self.events.send(AppEvent::Decrement),
+                KeyCode::Char('p' | 'P') => self.events.send(AppEvent::TogglePause),
+                KeyCode::Char('s') => self.events.send(AppEvent::SellAlly),
+                KeyCode::Char('S') => self.events.send(AppEvent::SellAllOfElement),
+                KeyCode::Char('g') => self.events.send(AppEvent::ToggleCoordinates),
+                KeyCode::Char('i') => self.events.send(AppEvent::ToggleInspect),
+                KeyCode::Char('b') => self.events.send(AppEvent::OpenBuildMenu),
+                KeyCode::Char('u') => self.events.send(AppEvent::Undo),
+                KeyCode::Char('f') => self.events.send(AppEvent::FocusFire),
+                KeyCode::Char('t') => self.events.send(AppEvent::CycleTargeting),
+                KeyCode::Char('r') => self.events.send(AppEvent::RotateAlly),
+                KeyCode::Char('e') => self.events.send(AppEvent::ExportState),
+                KeyCode::Tab if self.tutorial.is_some() => self.events.send(AppEvent::SkipTutorial),
+                KeyCode::Tab => self.events.send(AppEvent::CycleFocus),
+                _ => {}
+            }
+            let is_sandbox = self
+                .game
+                .as_ref()
+                .is_some_and(|g| g.mode == GameMode::Sandbox);
+            if is_sandbox {
+                match key_event.code {
+                    KeyCode::Char('1') => {
+                        self.events.send(AppEvent::SandboxSpawn(EnemyKind::Grunt))
+                    }
+                    KeyCode::Char('2') => self.events.send(AppEvent::SandboxSpawn(EnemyKind::Tank)),
+                    KeyCode::Char('3') => {
+                        self.events.send(AppEvent::SandboxSpawn(EnemyKind::Runner))
+                    }
+                    KeyCode::Char('4') => self
+                        .events
+                        .send(AppEvent::SandboxSpawn(EnemyKind::Reflector)),
+                    KeyCode::Char('[') => self
+                        .events
+                        .send(AppEvent::MoveSandboxSpawn(Direction::Left)),
+                    KeyCode::Char(']') => self
+                        .events
+                        .send(AppEvent::MoveSandboxSpawn(Direction::Right)),
+                    KeyCode::Char('c') if key_event.modifiers.is_empty() => {
+                        self.events.send(AppEvent::GrantCoins)
+                    }
+                    _ => {}
+                }
+            }
+            match (self.focus, key_event.code) {
+                (Focus::Grid, KeyCode::Up) => self
                     .events
                     .send(AppEvent::MoveCursor(crate::game::Direction::Up)),
-                KeyCode::Down => self
+                (Focus::Grid, KeyCode::Down) => self
                     .events
                     .send(AppEvent::MoveCursor(crate::game::Direction::Down)),
-                KeyCode::Left => self
+                (Focus::Grid, KeyCode::Left) => self
                     .events
                     .send(AppEvent::MoveCursor(crate::game::Direction::Left)),
-                KeyCode::Right => self
+                (Focus::Grid, KeyCode::Right) => self
                     .events
                     .send(AppEvent::MoveCursor(crate::game::Direction::Right)),
-                KeyCode::Enter => self.events.send(AppEvent::ToggleSelection),
-                KeyCode::Char(' ') => {
-                    self.events.send(AppEvent::BuyAlly);
+                (Focus::Grid, KeyCode::Enter) => self.events.send(AppEvent::ToggleSelection),
+                (Focus::Events, KeyCode::Up) => {
+                    self.events.send(AppEvent::ScrollLog(Direction::Up))
+                }
+                (Focus::Events, KeyCode::Down) => {
+                    self.events.send(AppEvent::ScrollLog(Direction::Down))
                 }
                 _ => {}
             }
@@ -227,8 +1183,146 @@ impl App {
     /// The tick event is where you can update the state of your application with any logic that
     /// needs to be updated at a fixed frame rate. E.g. polling a server, updating an animation.
     pub fn tick(&mut self) {
+        let ticks = self.game.as_ref().map_or(0, |g| g.ticks_elapsed);
         if let Some(game) = self.game.as_mut() {
+            let (lives, coins_earned) = (game.lives, game.coins_earned);
             game.update();
+            if let Some(replay) = self.replay.as_mut() {
+                replay.record_tick(game);
+            }
+            self.is_life_restored |= game.lives > lives;
+            self.is_coin_gained |= game.coins_earned > coins_earned;
+            self.is_ally_updated |= game.events.contains(&GameEvent::AutoBought);
+            if !matches!(game.game_state, GameState::Pause) {
+                self.is_spawn_pulse_due |= game
+                    .pending_spawns()
+                    .iter()
+                    .any(|&(_, ticks)| ticks % SPAWN_PULSE_TICKS == 0);
+                let is_fresh = game.ticks_elapsed > ticks;
+                // An ended game keeps its last events around, only draw them once
+                if is_fresh && !self.settings.reduce_motion {
+                    for event in &game.events {
+                        match *event {
+                            GameEvent::Attack { ally, target } => self.attacks.push((ally, target)),
+                            GameEvent::Bounce { from, to } => self.bounces.push((from, to)),
+                            _ => {}
+                        }
+                    }
+                }
+                self.update_damage_popups(is_fresh);
+            }
+        }
+        // Events stay around while the game stands still, only read out fresh ones
+        if let Some(game) = self.game.as_ref().filter(|g| g.ticks_elapsed > ticks) {
+            self.narrate(&game.events);
+            self.log_combat(0);
+        }
+        self.record_high_score();
+        self.save_replay();
+    }
+
+    /// Writes the replay of a finished game to the data directory, once.
+    fn save_replay(&mut self) {
+        if !self
+            .game
+            .as_ref()
+            .is_some_and(|game| matches!(game.game_state, GameState::End))
+        {
+            return;
+        }
+        let Some(replay) = self.replay.take() else {
+            return;
+        };
+        match replay.save() {
+            Ok(path) => info!(path = %path.display(), "saved replay"),
+            Err(err) => warn!(%err, "failed to save replay"),
+        }
+    }
+
+    /// Writes the narration of `events` to the event log, if the player asked for it.
+    fn narrate(&self, events: &[GameEvent]) {
+        if !self.settings.narrate_events {
+            return;
+        }
+        for line in events.iter().filter_map(GameEvent::narration) {
+            info!(target: "narration", "{line}");
+        }
+    }
+
+    /// Adds the combat lines of the game's events from index `seen` on to the combat log,
+    /// dropping the oldest lines past [`COMBAT_LOG_LEN`].
+    fn log_combat(&mut self, seen: usize) {
+        let Some(game) = self.game.as_ref() else {
+            return;
+        };
+        let lines = game.events[seen..]
+            .iter()
+            .filter_map(GameEvent::combat_line)
+            .collect::<Vec<_>>();
+        for line in lines {
+            if self.combat_log.len() == COMBAT_LOG_LEN {
+                self.combat_log.pop_front();
+            }
+            self.combat_log.push_back(line);
+        }
+    }
+
+    /// Ages the damage popups and adds the hits of the latest update, if `is_fresh`. An ended
+    /// game keeps its last events around, which must not be shown again.
+    fn update_damage_popups(&mut self, is_fresh: bool) {
+        self.damage_popups.retain(|_, popup| {
+            popup.ticks_left -= 1;
+            popup.ticks_left > 0
+        });
+        let Some(game) = self.game.as_ref().filter(|_| is_fresh) else {
+            return;
+        };
+        for event in &game.events {
+            let GameEvent::Hit {
+                cell,
+                damage,
+                effectiveness,
+            } = *event
+            else {
+                continue;
+            };
+            let popup = self.damage_popups.entry(cell).or_insert(DamagePopup {
+                damage: 0,
+                effectiveness,
+                ticks_left: DAMAGE_POPUP_TICKS,
+            });
+            // Hits of the same update add up, a later hit replaces the popup
+            if popup.ticks_left < DAMAGE_POPUP_TICKS {
+                popup.damage = 0;
+            }
+            popup.damage += damage;
+            popup.effectiveness = effectiveness;
+            popup.ticks_left = DAMAGE_POPUP_TICKS;
+        }
+    }
+
+    /// Adds a finished game to the high scores, once.
+    fn record_high_score(&mut self) {
+        let Some(game) = self.game.as_ref() else {
+            return;
+        };
+        if self.is_score_recorded
+            || !game.mode.is_ranked()
+            || !matches!(game.game_state, GameState::End)
+        {
+            return;
+        }
+        self.is_score_recorded = true;
+        let entry = HighScoreEntry {
+            score: game.score(),
+            waves_cleared: game.waves_cleared,
+            seconds_survived: game.elapsed_seconds() as u64,
+        };
+        if let Some(rank) = self.high_scores.insert(&game.high_score_table(), entry) {
+            info!(rank = rank + 1, mode = game.mode.name(), "new high score");
+        }
+        if let Err(err) = self.high_scores.save() {
+            warn!(%err, "failed to save high scores");
         }
     }
 
@@ -238,10 +1332,37 @@ impl App {
     }
 
     pub fn increment_counter(&mut self) {
-        self.counter = self.counter.saturating_add(1);
+        self.counter = (self.counter + 1).min(MAX_BUY_QUANTITY);
     }
 
     pub fn decrement_counter(&mut self) {
-        self.counter = self.counter.saturating_sub(1);
+        self.counter = self.counter.saturating_sub(1).max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage_popups_expire_after_the_game_ends() {
+        let mut app = App::with_picker(Picker::from_fontsize((8, 16)));
+        // Keeps the test away from the high scores on disk
+        app.is_score_recorded = true;
+        let mut game = Game::new();
+        game.game_state = GameState::End;
+        game.events = vec![GameEvent::Hit {
+            cell: (0, 0),
+            damage: 5,
+            effectiveness: 1.0,
+        }];
+        app.game = Some(game);
+        app.update_damage_popups(true);
+        assert!(app.damage_popups.contains_key(&(0, 0)));
+
+        for _ in 0..DAMAGE_POPUP_TICKS {
+            app.tick();
+        }
+        assert!(app.damage_popups.is_empty());
     }
 }
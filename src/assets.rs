@@ -0,0 +1,63 @@
+//! Finding the `assets` directory, so the game runs from anywhere and not just the project root.
+
+use crate::setup_logging::PROJECT_NAME;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Asset directory set through the `<PROJECT>_ASSETS` environment variable, if any.
+pub static ASSETS_FOLDER: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    std::env::var(format!("{}_ASSETS", PROJECT_NAME.clone()))
+        .ok()
+        .map(PathBuf::from)
+});
+
+/// The asset directory to load from: the configured one, then `assets` next to the executable,
+/// then `assets` in the working directory, whichever exists first. Falls back to the last one
+/// so errors name a sensible path.
+pub fn asset_dir() -> PathBuf {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let cwd = PathBuf::from(".");
+    resolve_asset_dir(ASSETS_FOLDER.clone(), exe_dir, &cwd).unwrap_or_else(|| cwd.join("assets"))
+}
+
+/// First existing directory among `configured`, `exe_dir/assets` and `cwd/assets`.
+pub fn resolve_asset_dir(
+    configured: Option<PathBuf>,
+    exe_dir: Option<PathBuf>,
+    cwd: &Path,
+) -> Option<PathBuf> {
+    configured
+        .into_iter()
+        .chain(exe_dir.map(|dir| dir.join("assets")))
+        .chain([cwd.join("assets")])
+        .find(|dir| dir.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolution_prefers_configured_then_executable_then_cwd() {
+        let root = std::env::temp_dir().join(format!("brainrot-assets-{}", std::process::id()));
+        let (configured, exe_dir, cwd) =
+            (root.join("configured"), root.join("bin"), root.join("cwd"));
+        for dir in [&configured, &exe_dir.join("assets"), &cwd.join("assets")] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+
+        let resolve = |configured: &Path| {
+            resolve_asset_dir(Some(configured.to_path_buf()), Some(exe_dir.clone()), &cwd)
+        };
+        assert_eq!(resolve(&configured), Some(configured.clone()));
+        assert_eq!(resolve(&root.join("missing")), Some(exe_dir.join("assets")));
+        std::fs::remove_dir_all(exe_dir.join("assets")).unwrap();
+        assert_eq!(resolve(&root.join("missing")), Some(cwd.join("assets")));
+        std::fs::remove_dir_all(cwd.join("assets")).unwrap();
+        assert_eq!(resolve(&root.join("missing")), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+//! Copying text to the system clipboard through the terminal, with the OSC 52 escape sequence.
+//!
+//! Terminals that don't support it ignore the sequence, so there is no telling whether the copy
+//! took; callers keep another way to get at the text.
+
+use std::io::Write;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Asks the terminal on stdout to put `text` on the clipboard.
+pub fn copy(text: &str) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "{}", osc52(text))?;
+    stdout.flush()
+}
+
+/// The OSC 52 sequence setting the clipboard to `text`.
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+/// Standard padded base64 of `bytes`, as OSC 52 expects.
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_like_standard_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(
+            base64(b"12345678901234567890"),
+            "MTIzNDU2Nzg5MDEyMzQ1Njc4OTA="
+        );
+        assert_eq!(osc52("foo"), "\x1b]52;c;Zm9v\x07");
+    }
+}
@@ -9,6 +9,9 @@ pub trait IndexResolver<T: Clone> {
 pub struct PingPongCycle;
 #[derive(Clone, Debug)]
 pub struct RepeatingCycle;
+/// Holds the last color past the end, for gradients driven by progress rather than time.
+#[derive(Clone, Debug)]
+pub struct ClampedCycle;
 
 #[derive(Clone, Debug)]
 pub struct ColorCycle<T: IndexResolver<Color>> {
@@ -37,8 +40,16 @@ impl IndexResolver<Color> for RepeatingCycle {
     }
 }
 
+impl IndexResolver<Color> for ClampedCycle {
+    fn resolve(idx: usize, data: &[Color]) -> &Color {
+        data.get(idx.min(data.len() - 1))
+            .expect("ColorCycle: index out of bounds")
+    }
+}
+
 pub type PingPongColorCycle = ColorCycle<PingPongCycle>;
 pub type RepeatingColorCycle = ColorCycle<RepeatingCycle>;
+pub type ClampedColorCycle = ColorCycle<ClampedCycle>;
 
 impl<T> ColorCycle<T>
 where
@@ -66,4 +77,10 @@ where
     pub fn color_at(&self, idx: usize) -> &Color {
         T::resolve(idx, &self.colors)
     }
+
+    /// The color `progress` of the way through one pass of the cycle, `0.0..=1.0`.
+    pub fn color_at_progress(&self, progress: f32) -> &Color {
+        let idx = (progress.clamp(0.0, 1.0) * (self.colors.len() - 1) as f32).round();
+        self.color_at(idx as usize)
+    }
 }
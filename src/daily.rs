@@ -0,0 +1,58 @@
+//! The daily challenge: one seed per UTC day, so every player gets the same run that day.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Days since 1970-01-01, in UTC.
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / (24 * 60 * 60))
+        .unwrap_or_default()
+}
+
+/// The seed of the challenge on `day` (days since 1970-01-01).
+pub fn seed_for(day: u64) -> u64 {
+    // splitmix64, so neighbouring days get unrelated seeds
+    let mut z = day.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// The seed of today's challenge.
+pub fn daily_seed() -> u64 {
+    seed_for(today())
+}
+
+/// `day` as `YYYY-MM-DD`.
+pub fn date_string(day: u64) -> String {
+    // Howard Hinnant's civil_from_days
+    let z = day as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_formatted_in_utc() {
+        assert_eq!(date_string(0), "1970-01-01");
+        assert_eq!(date_string(19_723), "2024-01-01");
+        assert_eq!(date_string(19_782), "2024-02-29");
+    }
+
+    #[test]
+    fn each_day_has_its_own_seed() {
+        assert_eq!(seed_for(19_723), seed_for(19_723));
+        assert_ne!(seed_for(19_723), seed_for(19_724));
+    }
+}
@@ -36,18 +36,89 @@ pub enum Event {
 /// You can extend this enum with your own custom events.
 #[derive(Clone, Debug)]
 pub enum AppEvent {
-    /// Increment the counter.
+    /// Buy one more ally per press of Space.
     Increment,
-    /// Decrement the counter.
+    /// Buy one ally fewer per press of Space.
     Decrement,
     /// Quit the application.
     Quit,
-    /// Start the game.
-    StartGame,
+    /// Start a game in the given mode.
+    StartGame(crate::game::GameMode),
+    /// Leave a finished game and return to the menu.
+    BackToMenu,
+    /// Move the highlighted menu entry
+    MenuMove(crate::game::Direction),
+    /// Open the settings screen from the menu.
+    OpenSettings,
+    /// Leave the settings screen for the one it was opened from.
+    CloseSettings,
+    /// Move the highlighted pause menu entry
+    PauseMenuMove(crate::game::Direction),
+    /// Do what the highlighted pause menu entry says.
+    PauseMenuSelect,
+    /// Start the current game's mode over.
+    RestartGame,
+    /// Move the highlighted settings row
+    SettingsMove(crate::game::Direction),
+    /// Step the highlighted setting to its next value and save.
+    ChangeSetting,
     /// Move cursor in game
     MoveCursor(crate::game::Direction),
     ToggleSelection,
+    /// Buy allies at random, or open the element picker if the config lets the player choose
     BuyAlly,
+    /// Move the highlighted element picker entry
+    ElementPickerMove(crate::game::Direction),
+    /// Buy allies of an element and close the element picker
+    BuyAllyOf(crate::game::AllyElement),
+    /// Close the element picker without buying
+    CloseElementPicker,
+    /// Open the element picker to build an ally on the empty cell under the cursor
+    OpenBuildMenu,
+    /// Build an ally of an element on the cell the element picker was opened for, and close it
+    BuildAlly(crate::game::AllyElement),
+    /// Sell the ally under the cursor
+    SellAlly,
+    /// Sell every single-element ally sharing the element of the one under the cursor
+    SellAllOfElement,
+    /// Go ahead with the sale awaiting confirmation
+    ConfirmSell,
+    /// Drop the sale awaiting confirmation
+    CancelSell,
+    /// Pause or resume the running game
+    TogglePause,
+    /// Split the latest merge back into its two allies, or move the latest moved ally back
+    Undo,
+    /// Show or hide the `(row, col)` overlay on ally cells
+    ToggleCoordinates,
+    /// Enter or leave inspect mode
+    ToggleInspect,
+    /// Move the inspect cursor to a neighboring board cell
+    InspectMove(crate::game::Direction),
+    /// Write the game state to a JSON file for outside tools
+    ExportState,
+    /// Show or hide the frame time overlay
+    TogglePerfOverlay,
+    /// Switch how the ally under the cursor picks its target
+    CycleTargeting,
+    /// Turn the selected Aoe ally to face the next direction
+    RotateAlly,
+    /// Make allies focus the enemy nearest to the cursor, or clear the order
+    FocusFire,
+    /// Move the keyboard focus to the next panel
+    CycleFocus,
+    /// Scroll the event log
+    ScrollLog(crate::game::Direction),
+    /// Leave the tutorial steps and let the game run
+    SkipTutorial,
+    /// Spawn an enemy of a kind at the sandbox spawn point
+    SandboxSpawn(crate::game::EnemyKind),
+    /// Move the sandbox spawn point along the path
+    MoveSandboxSpawn(crate::game::Direction),
+    /// Grant sandbox coins
+    GrantCoins,
+    /// Copy the seed of the finished run to the clipboard
+    CopySeed,
 }
 
 /// Terminal event handler.
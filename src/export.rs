@@ -0,0 +1,97 @@
+//! JSON snapshots of a running game, for analyzers, bots and other tools outside the game.
+
+use crate::game::{Board, Game, GameMode, GameState};
+use crate::setup_logging::get_data_dir;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Version of the [`GameSnapshot`] schema, bumped whenever a field is renamed or removed.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// A game at one tick. The fields, and those of the game types it embeds, are the schema tools
+/// read, so they are only ever added to within a [`SNAPSHOT_VERSION`].
+#[derive(Debug, Serialize)]
+pub struct GameSnapshot<'a> {
+    pub version: u32,
+    pub mode: GameMode,
+    pub state: &'a GameState,
+    /// Updates run so far, at 60 per in-game second.
+    pub tick: usize,
+    /// Current wave, starting from 1.
+    pub wave: usize,
+    pub waves_cleared: usize,
+    pub lives: usize,
+    pub coins: usize,
+    /// Scrap salvaged from sold allies.
+    pub scrap: usize,
+    pub kills: usize,
+    pub score: u64,
+    /// Allies by grid row and column, enemies on the path with their position, HP and debuffs,
+    /// and enemies waiting to spawn with the ticks left until they do.
+    pub board: &'a Board,
+}
+
+impl<'a> GameSnapshot<'a> {
+    pub fn of(game: &'a Game) -> GameSnapshot<'a> {
+        GameSnapshot {
+            version: SNAPSHOT_VERSION,
+            mode: game.mode,
+            state: &game.game_state,
+            tick: game.ticks_elapsed,
+            wave: game.level,
+            waves_cleared: game.waves_cleared,
+            lives: game.lives,
+            coins: game.coin,
+            scrap: game.scrap,
+            kills: game.kills,
+            score: game.score(),
+            board: &game.board,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Writes a snapshot of `game` to `snapshots/tick-<tick>.json` in the data directory, returning
+/// the path written.
+pub fn export(game: &Game) -> Result<PathBuf> {
+    let snapshot = GameSnapshot::of(game);
+    let dir = get_data_dir().join("snapshots");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("tick-{}.json", snapshot.tick));
+    std::fs::write(&path, snapshot.to_json()?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Ally, Enemy};
+
+    #[test]
+    fn snapshot_lists_board_and_run_state() {
+        let mut game = Game::new();
+        game.board.ally_grid[1][2] = Some(Ally {
+            level: 2,
+            ..Default::default()
+        });
+        game.board.enemies = vec![Enemy {
+            hp: 42,
+            position: 3.5,
+            ..Default::default()
+        }];
+
+        let json = GameSnapshot::of(&game).to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], SNAPSHOT_VERSION);
+        assert_eq!(value["lives"], game.lives);
+        assert_eq!(value["scrap"], 0);
+        assert_eq!(value["board"]["ally_grid"][1][2]["level"], 2);
+        assert_eq!(value["board"]["enemies"][0]["hp"], 42);
+        assert_eq!(value["board"]["enemies"][0]["position"], 3.5);
+        assert!(value["board"]["enemies"][0]["slow_list"].is_array());
+    }
+}
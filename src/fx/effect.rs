@@ -1,6 +1,7 @@
 // use crate::app::KeyMapContext;
 use crate::color_cycle::{
-    ColorCycle, IndexResolver, PingPongColorCycle, RepeatingColorCycle, RepeatingCycle,
+    ClampedColorCycle, ColorCycle, IndexResolver, PingPongColorCycle, RepeatingColorCycle,
+    RepeatingCycle,
 };
 // use crate::dispatcher::Dispatcher;
 // use crate::exabind_event::ExabindEvent;
@@ -13,13 +14,15 @@ use ratatui::layout::{Margin, Position, Rect, Size};
 use ratatui::prelude::Buffer;
 use ratatui::style::{Color, Style};
 use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::mpsc::Sender;
 use std::time::Instant;
 use tachyonfx::Motion::UpToDown;
 use tachyonfx::fx::{effect_fn_buf, parallel, prolong_start, sequence, sleep, sweep_in};
 use tachyonfx::{
-    CellFilter, Duration, Effect, EffectManager, EffectTimer, HslConvertable, Interpolation,
-    IntoEffect, RangeSampler, SimpleRng, fx,
+    CellFilter, Duration, Effect, EffectManager, EffectTimer, HslConvertable, Interpolatable,
+    Interpolation, IntoEffect, RangeSampler, SimpleRng, fx,
 };
 
 /// Creates an animated border effect for the selected category using color cycling.
@@ -75,6 +78,90 @@ pub fn selected_category(base_color: Color, area: Rect) -> Effect {
     effect.with_area(area)
 }
 
+/// Creates a glow over an ally cell's background that follows its attack cooldown.
+///
+/// # Arguments
+/// * `colors` - Gradient from a freshly fired ally to a ready one
+/// * `readiness` - Bits of an `f32` the owner keeps updated, `0.0` right after an attack up to
+///   `1.0` when ready
+/// * `flash_duration` - Milliseconds the white flash lasts when the ally fires
+///
+/// # Returns
+/// An Effect blending the gradient color for the current readiness into the background, and
+/// flashing white whenever readiness drops, i.e. the ally attacked
+pub fn cooldown_glow(
+    colors: ClampedColorCycle,
+    readiness: Arc<AtomicU32>,
+    flash_duration: u32,
+) -> Effect {
+    const GLOW_STRENGTH: f32 = 0.5;
+
+    fx::effect_fn(
+        (colors, 1.0_f32, 0.0_f32),
+        u32::MAX,
+        move |(colors, last, flash), ctx, cell_iter| {
+            let progress = f32::from_bits(readiness.load(Ordering::Relaxed));
+            if progress < *last {
+                *flash = 1.0;
+            }
+            *last = progress;
+            let fade = ctx.last_tick.as_millis() as f32 / flash_duration.max(1) as f32;
+            *flash = (*flash - fade).max(0.0);
+
+            let glow = *colors.color_at_progress(progress);
+            cell_iter.for_each(|(_, cell)| {
+                let color = cell
+                    .bg
+                    .lerp(&glow, GLOW_STRENGTH)
+                    .lerp(&Color::White, *flash);
+                cell.set_bg(color);
+            });
+        },
+    )
+}
+
+/// Creates an effect sending a single glyph from one cell to another.
+///
+/// # Arguments
+/// * `glyph` - Character drawn at the projectile's position
+/// * `color` - Foreground color of the glyph
+/// * `from` - Where the projectile starts
+/// * `to` - Where the projectile lands
+/// * `trail` - Whether the cells already crossed stay drawn, leaving a streak
+/// * `duration` - Milliseconds the flight takes
+///
+/// # Returns
+/// An Effect that draws the projectile over whatever was rendered beneath it
+pub fn projectile(
+    glyph: char,
+    color: Color,
+    from: Position,
+    to: Position,
+    trail: bool,
+    duration: u32,
+) -> Effect {
+    let steps = from.x.abs_diff(to.x).max(from.y.abs_diff(to.y)).max(1);
+    let at = move |step: u16| {
+        let t = step as f32 / steps as f32;
+        let lerp = |a: u16, b: u16| (a as f32 + (b as f32 - a as f32) * t).round() as u16;
+        (lerp(from.x, to.x), lerp(from.y, to.y))
+    };
+
+    fx::effect_fn_buf(
+        (),
+        (duration, Interpolation::QuadOut),
+        move |_, ctx, buf| {
+            let reached = (ctx.alpha() * steps as f32).round() as u16;
+            let first = if trail { 0 } else { reached };
+            for step in first..=reached {
+                if let Some(cell) = buf.cell_mut(at(step)) {
+                    cell.set_char(glyph).set_fg(color);
+                }
+            }
+        },
+    )
+}
+
 /// Animates the opening of all category widgets with staggered timing.
 ///
 /// # Arguments
@@ -1,14 +1,16 @@
+use crate::daily;
 use color_eyre::eyre::Result;
 use rand::prelude::IndexedRandom;
-use rand::thread_rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use ratatui_image::protocol::Protocol;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 use std::path::PathBuf;
-use tracing::info;
+use tracing::{info, warn};
 
-#[derive(Debug, Default, Clone, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub enum GameState {
     #[default]
     Init,
@@ -17,14 +19,71 @@ pub enum GameState {
     End,
 }
 
-#[derive(Debug, Default, Clone, Deserialize)]
+/// Which ruleset a run is played with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// A fixed set of enemies; clearing them all wins the run.
+    #[default]
+    Campaign,
+    /// Waves keep coming, each harder than the last, until the player runs out of lives.
+    Endless,
+    /// A campaign seeded from the date, so every player gets the same run that day.
+    Daily,
+    /// A campaign that starts paused, with steps walking through the controls.
+    Tutorial,
+    /// No waves and no end. Enemies are spawned by hand and coins granted on demand, for
+    /// balancing and reproducing bugs.
+    Sandbox,
+}
+
+impl GameMode {
+    /// Every mode, in the order they're listed on the menu.
+    pub const ALL: [GameMode; 5] = [
+        GameMode::Campaign,
+        GameMode::Endless,
+        GameMode::Daily,
+        GameMode::Tutorial,
+        GameMode::Sandbox,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Campaign => "Campaign",
+            GameMode::Endless => "Endless",
+            GameMode::Daily => "Daily Challenge",
+            GameMode::Tutorial => "Tutorial",
+            GameMode::Sandbox => "Sandbox",
+        }
+    }
+
+    /// Whether runs in this mode enter the high scores. The tutorial and sandbox are practice.
+    pub fn is_ranked(&self) -> bool {
+        !matches!(self, GameMode::Tutorial | GameMode::Sandbox)
+    }
+
+    /// Key of the high-score table runs in this mode are ranked in. Daily challenges get a table
+    /// per `day` (days since 1970-01-01); other modes ignore it.
+    pub fn high_score_table(&self, day: u64) -> String {
+        match self {
+            GameMode::Campaign => "campaign".to_string(),
+            GameMode::Endless => "endless".to_string(),
+            GameMode::Daily => format!("daily-{}", daily::date_string(day)),
+            GameMode::Tutorial => "tutorial".to_string(),
+            GameMode::Sandbox => "sandbox".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Board {
     pub ally_grid: Vec<Vec<Option<Ally>>>,
+    /// Enemies on the path in the order they spawned, those spawning on the same tick by id.
+    /// Targeting ties are broken by this order, so seeded runs play out the same.
     pub enemies: Vec<Enemy>,
     pub enemy_ready2spawn: Vec<(Enemy, usize)>,
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ally {
     pub element: AllyElement,
     pub second_element: Option<AllyElement>,
@@ -32,13 +91,208 @@ pub struct Ally {
     pub range: usize,
     pub aoe_range: usize,
     pub level: usize,
-    pub atk_speed: f32,
+    /// Attacks made each second. Higher is faster: the cooldown after each is the inverse.
+    pub attacks_per_second: f32,
     pub attack_cooldown: f32,
     pub levelup_ratio: f32,
     pub special_value: f32,
+    /// Chance in `0.0..=1.0` for a hit to stun its target.
+    pub stun_chance: f32,
+    /// Seconds a stun keeps the target in place.
+    pub stun_duration: f32,
+    pub stats: AllyStats,
+    #[serde(default)]
+    pub targeting: TargetingMode,
+    /// Ticks since the ally last attacked, or since it was placed.
+    #[serde(default)]
+    pub idle_ticks: u32,
+    /// Direction an Aoe ally's splash is narrowed to, a full circle around the target if `None`.
+    #[serde(default)]
+    pub facing: Option<Direction>,
+    /// Which enemy in range an Aoe ally centers its splash on.
+    #[serde(default)]
+    pub aoe_center: AoeCenter,
+    /// HP left. Damage a [`EnemyKind::Reflector`] sends back chips it away, destroying the ally
+    /// at `0`.
+    #[serde(default)]
+    pub hp: usize,
+    /// HP it was placed with, restored when it merges.
+    #[serde(default)]
+    pub max_hp: usize,
+}
+
+/// Why [`Game::ally_merge`] refused to merge two allies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeError {
+    /// Only allies of the same level merge, unless [`MergeRule::Absorb`] is on.
+    LevelMismatch,
+    /// Both are the same kind of ally, already at the level cap.
+    MaxLevel,
+    /// Two dual allies of different element pairs.
+    IncompatibleElements,
+    /// A dual ally can't take on the element of a different single-element one.
+    AlreadyDualElement,
+}
+
+impl MergeError {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            MergeError::LevelMismatch => "Levels differ",
+            MergeError::MaxLevel => "Max level",
+            MergeError::IncompatibleElements => "Different element pairs",
+            MergeError::AlreadyDualElement => "Already has two elements",
+        }
+    }
+}
+
+/// Which same-element allies [`Game::ally_merge`] upgrades, set by `merge_rule` in the config.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum MergeRule {
+    /// Only allies of the same level merge, into one a level higher.
+    #[default]
+    Strict,
+    /// A higher-level ally can also absorb a lower-level one of the same elements. It keeps its
+    /// level but gains the share of a level-up the lower ally's level makes up of its own.
+    Absorb,
+}
+
+/// What happens to enemies due to spawn while `max_enemies` are already on the path, set by
+/// `enemy_overflow` in the config.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum EnemyOverflow {
+    /// They wait, spawning in order as soon as enemies die or leak.
+    #[default]
+    Delay,
+    /// They are dropped from the wave, so the enemies already on the path keep their place.
+    Drop,
+}
+
+/// What enemy speeds are measured in, set by `speed_unit` in the config.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum SpeedUnit {
+    /// Path cells per second, so enemies on a shorter path reach the exit sooner.
+    #[default]
+    Cells,
+    /// Whole paths per second: an unhindered enemy of speed `s` reaches the exit in `1 / s`
+    /// seconds, however long its path is.
+    Loops,
+}
+
+impl SpeedUnit {
+    /// Cells per second an enemy of `move_speed` walks along a path `path_length` cells long.
+    pub fn cells_per_second(self, move_speed: f32, path_length: f32) -> f32 {
+        match self {
+            SpeedUnit::Cells => move_speed,
+            SpeedUnit::Loops => move_speed * path_length,
+        }
+    }
+}
+
+/// How the element of a bought ally is decided, set by `ally_selection_mode` in the config.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum AllySelectionMode {
+    /// It's rolled at random, for [`ALLY_COST`].
+    #[default]
+    Random,
+    /// The player picks it, for `chosen_ally_cost`. Auto-buy still rolls it.
+    Choose,
+}
+
+/// How an ally picks which enemy in range to attack.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetingMode {
+    /// The nearest enemy.
+    #[default]
+    Closest,
+    /// The enemy it can kill with this hit, the toughest one if several, to waste as little
+    /// damage as possible. Falls back to the nearest enemy when none can be finished.
+    SmartFinish,
+}
+
+impl TargetingMode {
+    pub fn next(self) -> TargetingMode {
+        match self {
+            TargetingMode::Closest => TargetingMode::SmartFinish,
+            TargetingMode::SmartFinish => TargetingMode::Closest,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TargetingMode::Closest => "Closest",
+            TargetingMode::SmartFinish => "Smart finish",
+        }
+    }
+}
+
+/// Which enemy in range an Aoe ally aims its splash at, set by `aoe_center` in the config.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AoeCenter {
+    /// The nearest enemy, or whichever its [`TargetingMode`] picks.
+    #[default]
+    Nearest,
+    /// The enemy furthest away that is still in range.
+    Furthest,
+    /// The enemy with the most others within `aoe_range` of it, so the splash hits the most.
+    /// Ties go to the nearest one.
+    Densest,
+}
+
+impl AoeCenter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AoeCenter::Nearest => "Nearest",
+            AoeCenter::Furthest => "Furthest",
+            AoeCenter::Densest => "Densest",
+        }
+    }
+}
+
+/// What an ally contributed over its lifetime. Merging two allies adds up both of theirs.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AllyStats {
+    /// Direct damage dealt, after armor and not counting overkill.
+    pub damage_dealt: usize,
+    /// Enemies finished off by a direct hit.
+    pub kills: usize,
+}
+
+impl AllyStats {
+    fn combine(&self, other: &AllyStats) -> AllyStats {
+        AllyStats {
+            damage_dealt: self.damage_dealt + other.damage_dealt,
+            kills: self.kills + other.kills,
+        }
+    }
 }
 
 impl Ally {
+    /// Whether either of the ally's elements is `element`.
+    pub fn has_element(&self, element: AllyElement) -> bool {
+        self.element == element || self.second_element == Some(element)
+    }
+
+    /// Seconds between two attacks, `0.0` for an ally without an attack rate, which attacks
+    /// every tick.
+    pub fn cooldown(&self) -> f32 {
+        if self.attacks_per_second > 0.0 {
+            1.0 / self.attacks_per_second
+        } else {
+            0.0
+        }
+    }
+
+    /// How far the ally's attack has come off cooldown, from `0.0` right after attacking to
+    /// `1.0` once it can attack again.
+    pub fn readiness(&self) -> f32 {
+        let cooldown = self.cooldown();
+        if cooldown > 0.0 {
+            (1.0 - self.attack_cooldown / cooldown).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+
     pub fn name(&self) -> &'static str {
         let elems = match self.second_element {
             None => vec![self.element],
@@ -50,6 +304,9 @@ impl Ally {
             &[AllyElement::Aoe] => "Bombardiro Crocodilo",
             &[AllyElement::Dot] => "Lirili Larila",
             &[AllyElement::Critical] => "Capuccino Assassino",
+            &[AllyElement::Chain] => "Zappalino Fulminetti",
+            &[AllyElement::Coin] => "Soldino Monetino",
+            &[AllyElement::Aura] => "Scudino Lentissimo",
             &[AllyElement::Basic, AllyElement::Slow] => "Tralatung Sahurrissimo",
             &[AllyElement::Basic, AllyElement::Aoe] => "Bombatung Croco Sahurrissimo",
             &[AllyElement::Basic, AllyElement::Dot] => "Liritung Sahurilla",
@@ -60,33 +317,73 @@ impl Ally {
             &[AllyElement::Aoe, AllyElement::Dot] => "Bombilì Larilocodilo Lari",
             &[AllyElement::Aoe, AllyElement::Critical] => "Bombacino Crocossino Assa",
             &[AllyElement::Dot, AllyElement::Critical] => "Liricino Assalila Cappu",
+            &[AllyElement::Basic, AllyElement::Chain] => "Zappatung Sahurrino",
+            &[AllyElement::Slow, AllyElement::Chain] => "Tralazappa Fulminala",
+            &[AllyElement::Aoe, AllyElement::Chain] => "Bombazappo Crocofulmino",
+            &[AllyElement::Dot, AllyElement::Chain] => "Lirizappa Fulmilla",
+            &[AllyElement::Critical, AllyElement::Chain] => "Capuzappo Assafulmino",
+            &[AllyElement::Basic, AllyElement::Coin] => "Tung Tung Soldino",
+            &[AllyElement::Slow, AllyElement::Coin] => "Tralalero Monetala",
+            &[AllyElement::Aoe, AllyElement::Coin] => "Bombardiro Soldilo",
+            &[AllyElement::Dot, AllyElement::Coin] => "Lirili Monetila",
+            &[AllyElement::Critical, AllyElement::Coin] => "Capuccino Soldino",
+            &[AllyElement::Chain, AllyElement::Coin] => "Zappalino Monetini",
+            &[AllyElement::Basic, AllyElement::Aura] => "Tung Tung Scudino",
+            &[AllyElement::Slow, AllyElement::Aura] => "Tralalero Lentissimo",
+            &[AllyElement::Aoe, AllyElement::Aura] => "Bombardiro Scudilo",
+            &[AllyElement::Dot, AllyElement::Aura] => "Lirili Scudila",
+            &[AllyElement::Critical, AllyElement::Aura] => "Capuccino Scudino",
+            &[AllyElement::Chain, AllyElement::Aura] => "Zappalino Scudetti",
+            &[AllyElement::Coin, AllyElement::Aura] => "Soldino Scudino",
             _ => {
                 unreachable!()
             }
         }
     }
 
+    /// Path of the ally's avatar image, relative to [`crate::assets::asset_dir`].
     pub fn avatar_path(&self) -> &'static str {
         let elems = match self.second_element {
             None => vec![self.element],
             Some(e) => vec![self.element, e],
         };
         match elems.as_slice() {
-            &[AllyElement::Basic] => "assets/avatars/basic.png",
-            &[AllyElement::Slow] => "assets/avatars/slow.png",
-            &[AllyElement::Aoe] => "assets/avatars/aoe.png",
-            &[AllyElement::Dot] => "assets/avatars/dot.png",
-            &[AllyElement::Critical] => "assets/avatars/critical.png",
-            &[AllyElement::Basic, AllyElement::Slow] => "assets/avatars/basic_slow.png",
-            &[AllyElement::Basic, AllyElement::Aoe] => "assets/avatars/basic_aoe.png",
-            &[AllyElement::Basic, AllyElement::Dot] => "assets/avatars/basic_dot.png",
-            &[AllyElement::Basic, AllyElement::Critical] => "assets/avatars/basic_critical.png",
-            &[AllyElement::Slow, AllyElement::Aoe] => "assets/avatars/slow_aoe.png",
-            &[AllyElement::Slow, AllyElement::Dot] => "assets/avatars/slow_dot.png",
-            &[AllyElement::Slow, AllyElement::Critical] => "assets/avatars/slow_critical.png",
-            &[AllyElement::Aoe, AllyElement::Dot] => "assets/avatars/aoe_dot.png",
-            &[AllyElement::Aoe, AllyElement::Critical] => "assets/avatars/aoe_critical.png",
-            &[AllyElement::Dot, AllyElement::Critical] => "assets/avatars/dot_critical.png",
+            &[AllyElement::Basic] => "avatars/basic.png",
+            &[AllyElement::Slow] => "avatars/slow.png",
+            &[AllyElement::Aoe] => "avatars/aoe.png",
+            &[AllyElement::Dot] => "avatars/dot.png",
+            &[AllyElement::Critical] => "avatars/critical.png",
+            &[AllyElement::Chain] => "avatars/chain.png",
+            &[AllyElement::Coin] => "avatars/coin.png",
+            &[AllyElement::Aura] => "avatars/aura.png",
+            &[AllyElement::Basic, AllyElement::Slow] => "avatars/basic_slow.png",
+            &[AllyElement::Basic, AllyElement::Aoe] => "avatars/basic_aoe.png",
+            &[AllyElement::Basic, AllyElement::Dot] => "avatars/basic_dot.png",
+            &[AllyElement::Basic, AllyElement::Critical] => "avatars/basic_critical.png",
+            &[AllyElement::Slow, AllyElement::Aoe] => "avatars/slow_aoe.png",
+            &[AllyElement::Slow, AllyElement::Dot] => "avatars/slow_dot.png",
+            &[AllyElement::Slow, AllyElement::Critical] => "avatars/slow_critical.png",
+            &[AllyElement::Aoe, AllyElement::Dot] => "avatars/aoe_dot.png",
+            &[AllyElement::Aoe, AllyElement::Critical] => "avatars/aoe_critical.png",
+            &[AllyElement::Dot, AllyElement::Critical] => "avatars/dot_critical.png",
+            &[AllyElement::Basic, AllyElement::Chain] => "avatars/basic_chain.png",
+            &[AllyElement::Slow, AllyElement::Chain] => "avatars/slow_chain.png",
+            &[AllyElement::Aoe, AllyElement::Chain] => "avatars/aoe_chain.png",
+            &[AllyElement::Dot, AllyElement::Chain] => "avatars/dot_chain.png",
+            &[AllyElement::Critical, AllyElement::Chain] => "avatars/critical_chain.png",
+            &[AllyElement::Basic, AllyElement::Coin] => "avatars/basic_coin.png",
+            &[AllyElement::Slow, AllyElement::Coin] => "avatars/slow_coin.png",
+            &[AllyElement::Aoe, AllyElement::Coin] => "avatars/aoe_coin.png",
+            &[AllyElement::Dot, AllyElement::Coin] => "avatars/dot_coin.png",
+            &[AllyElement::Critical, AllyElement::Coin] => "avatars/critical_coin.png",
+            &[AllyElement::Chain, AllyElement::Coin] => "avatars/chain_coin.png",
+            &[AllyElement::Basic, AllyElement::Aura] => "avatars/basic_aura.png",
+            &[AllyElement::Slow, AllyElement::Aura] => "avatars/slow_aura.png",
+            &[AllyElement::Aoe, AllyElement::Aura] => "avatars/aoe_aura.png",
+            &[AllyElement::Dot, AllyElement::Aura] => "avatars/dot_aura.png",
+            &[AllyElement::Critical, AllyElement::Aura] => "avatars/critical_aura.png",
+            &[AllyElement::Chain, AllyElement::Aura] => "avatars/chain_aura.png",
+            &[AllyElement::Coin, AllyElement::Aura] => "avatars/coin_aura.png",
             _ => {
                 unreachable!()
             }
@@ -94,7 +391,7 @@ impl Ally {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default, Eq, PartialOrd, Ord, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AllyElement {
     #[default]
     Basic,
@@ -102,24 +399,406 @@ pub enum AllyElement {
     Aoe,
     Dot,
     Critical,
+    Chain,
+    /// Never attacks, earning coins instead.
+    Coin,
+    /// Never attacks, slowing every enemy in range instead.
+    Aura,
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
+impl AllyElement {
+    pub const ALL: [AllyElement; 8] = [
+        AllyElement::Basic,
+        AllyElement::Slow,
+        AllyElement::Aoe,
+        AllyElement::Dot,
+        AllyElement::Critical,
+        AllyElement::Chain,
+        AllyElement::Coin,
+        AllyElement::Aura,
+    ];
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Enemy {
     pub hp: usize,
+    /// HP it spawned with, the cap for regeneration.
+    #[serde(default)]
+    pub max_hp: usize,
+    /// HP regained per second while damaged.
+    #[serde(default)]
+    pub hp_regen: f32,
+    /// Fraction of a point of HP regenerated so far, so slow regeneration isn't rounded away.
+    #[serde(default)]
+    pub regen_progress: f32,
     pub move_speed: f32,
     pub position: f32, // from 0 to 24
     pub dot_list: Vec<Debuff>,
     pub slow_list: Vec<Debuff>,
+    /// While any stun is active the enemy doesn't move at all.
+    pub stun_list: Vec<Debuff>,
+    pub kind: EnemyKind,
+    /// Flat reduction applied to every direct hit.
+    pub armor: usize,
+    /// Active shreds, each lowering armor by its `value` until it expires.
+    pub armor_shred_list: Vec<Debuff>,
+    /// Coins paid out when it's killed.
+    #[serde(default)]
+    pub bounty: usize,
+    /// Assigned when it's queued to spawn, unique within a run.
+    #[serde(default)]
+    pub id: u64,
+    /// Which attack shape hurts it most, see [`ArmorTypeConfig`].
+    #[serde(default)]
+    pub armor_type: ArmorType,
+    /// Branch it takes where the path forks, `None` while the path is a single loop.
+    #[serde(default)]
+    pub branch: Option<PathBranch>,
+    /// Share of each direct hit's damage sent back to the ally that dealt it.
+    #[serde(default)]
+    pub reflect: f32,
 }
 
-#[derive(Debug, Clone, Default, Deserialize)]
+/// The two ways out of a path that forks at the entrance, both leaving the board at the
+/// bottom-right corner. Set up by `branch_split` in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathBranch {
+    /// Along the top edge, then down the right one.
+    Upper,
+    /// Down the left edge, then along the bottom one.
+    Lower,
+}
+
+/// Kind of armor an enemy wears. Light armor gives way to single-target hits, heavy armor to
+/// splash damage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ArmorType {
+    #[default]
+    Unarmored,
+    Light,
+    Heavy,
+}
+
+/// Whether a hit struck a single enemy or splashed over an area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitShape {
+    Single,
+    Aoe,
+}
+
+impl HitShape {
+    /// Shape of the hits of an ally with `elements`: splash if either is Aoe.
+    pub fn of(elements: [Option<AllyElement>; 2]) -> HitShape {
+        if elements.contains(&Some(AllyElement::Aoe)) {
+            HitShape::Aoe
+        } else {
+            HitShape::Single
+        }
+    }
+}
+
+impl Enemy {
+    pub fn is_stunned(&self) -> bool {
+        !self.stun_list.is_empty()
+    }
+
+    /// Whether it's alive, damaged and healing back up.
+    pub fn is_regenerating(&self) -> bool {
+        self.hp_regen > 0.0 && self.hp > 0 && self.hp < self.max_hp
+    }
+
+    /// Armor left after all active shreds, never below zero.
+    pub fn effective_armor(&self) -> usize {
+        let shred: usize = self.armor_shred_list.iter().map(|d| d.value).sum();
+        self.armor.saturating_sub(shred)
+    }
+
+    /// Distance it walks before leaving the board: the whole loop, or half of it on a branch.
+    pub fn path_length(&self) -> f32 {
+        match self.branch {
+            None => PATH_LENGTH,
+            Some(_) => BRANCH_LENGTH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EnemyKind {
+    #[default]
+    Grunt,
+    /// Slow and armored, so it shrugs off weak hits unless its armor is shredded.
+    Tank,
+    /// Fast and frail.
+    Runner,
+    /// Sends part of the damage of every direct hit back to the ally that dealt it, see
+    /// [`ConfigFile::reflect_fraction`]. Only spawned by `[[wave]]` groups.
+    Reflector,
+}
+
+impl EnemyKind {
+    pub const ALL: [EnemyKind; 4] = [
+        EnemyKind::Grunt,
+        EnemyKind::Tank,
+        EnemyKind::Runner,
+        EnemyKind::Reflector,
+    ];
+
+    /// Armor enemies of this kind spawn with.
+    pub fn armor_type(&self) -> ArmorType {
+        match self {
+            EnemyKind::Grunt => ArmorType::Unarmored,
+            EnemyKind::Tank => ArmorType::Heavy,
+            EnemyKind::Runner => ArmorType::Light,
+            EnemyKind::Reflector => ArmorType::Unarmored,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EnemyKind::Grunt => "Grunt",
+            EnemyKind::Tank => "Tank",
+            EnemyKind::Runner => "Runner",
+            EnemyKind::Reflector => "Reflector",
+        }
+    }
+
+    /// Whether enemies of this kind are the bosses of a wave, whose health gets a bar of its
+    /// own. Tanks are the toughest enemies there are, so they fill that role.
+    pub fn is_boss(&self) -> bool {
+        matches!(self, EnemyKind::Tank)
+    }
+
+    /// The kind called `name`, ignoring case.
+    pub fn from_name(name: &str) -> Option<EnemyKind> {
+        EnemyKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// How many enemies are left in the run, see [`Game::enemy_counts`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnemyCounts {
+    /// Enemies walking the path.
+    pub on_field: usize,
+    /// Enemies waiting to spawn.
+    pub incoming: usize,
+    /// Enemies on the field or incoming of each kind, in [`EnemyKind::ALL`] order.
+    pub by_kind: [usize; EnemyKind::ALL.len()],
+}
+
+/// Something that happened during the latest [`Game::update`], or a player action since, for
+/// the UI to react to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// An ally's attack hit an enemy standing in board cell `cell` (row, column).
+    Hit {
+        cell: (usize, usize),
+        /// HP taken off the enemy.
+        damage: usize,
+        /// Multiplier from the element/kind matchup, see [`ConfigFile::effectiveness`].
+        effectiveness: f32,
+    },
+    /// The ally in grid cell `ally` (row, column of the ally grid) attacked the enemy standing
+    /// in board cell `target`.
+    Attack {
+        ally: (usize, usize),
+        target: (usize, usize),
+    },
+    /// A Chain attack bounced from the enemy in board cell `from` to the one in `to`.
+    Bounce {
+        from: (usize, usize),
+        to: (usize, usize),
+    },
+    /// Auto-buy bought an ally.
+    AutoBought,
+    /// Wave `wave` began, counting from 1.
+    WaveStarted { wave: usize },
+    /// An enemy of `kind` entered the path.
+    EnemySpawned { kind: EnemyKind },
+    /// Enemies reached the exit, costing `lost` lives and leaving `remaining`.
+    LifeLost { lost: usize, remaining: usize },
+    /// An enemy of `kind` died, to a hit of the ally named `ally` or, if `None`, to damage over
+    /// time.
+    Killed {
+        ally: Option<&'static str>,
+        kind: EnemyKind,
+    },
+    /// Wave `wave` was fully cleared.
+    WaveCleared { wave: usize },
+    /// The ally named `ally` in grid cell `cell` (row, column of the ally grid) was destroyed by
+    /// damage a [`EnemyKind::Reflector`] sent back.
+    AllyDestroyed {
+        cell: (usize, usize),
+        ally: &'static str,
+    },
+    /// Two allies merged into one of `level` in grid cell `cell` (row, column of the ally grid).
+    Merged { cell: (usize, usize), level: usize },
+    /// The run is over, won or lost.
+    RunEnded { victory: bool },
+}
+
+impl GameEvent {
+    /// A sentence describing the event for the narration log, `None` for those too frequent or
+    /// minor to read out.
+    pub fn narration(&self) -> Option<String> {
+        match self {
+            GameEvent::WaveStarted { wave } => Some(format!("Wave {wave} started")),
+            // Grunts and runners come in droves; only the tough ones are worth a line
+            GameEvent::EnemySpawned {
+                kind: EnemyKind::Tank,
+            } => Some("A tank entered the path".to_string()),
+            GameEvent::LifeLost { lost: 1, remaining } => {
+                Some(format!("Lost a life, {remaining} remaining"))
+            }
+            GameEvent::LifeLost { lost, remaining } => {
+                Some(format!("Lost {lost} lives, {remaining} remaining"))
+            }
+            GameEvent::Merged {
+                cell: (row, col),
+                level,
+            } => Some(format!(
+                "Ally merged to level {level} at row {} col {}",
+                row + 1,
+                col + 1
+            )),
+            GameEvent::AllyDestroyed {
+                cell: (row, col),
+                ally,
+            } => Some(format!(
+                "{ally} at row {} col {} was destroyed by reflected damage",
+                row + 1,
+                col + 1
+            )),
+            GameEvent::RunEnded { victory: true } => Some("Victory, all waves cleared".to_string()),
+            GameEvent::RunEnded { victory: false } => Some("Game over, no lives left".to_string()),
+            _ => None,
+        }
+    }
+
+    /// A line for the combat log, `None` for events that aren't about the fighting.
+    pub fn combat_line(&self) -> Option<String> {
+        match self {
+            GameEvent::Killed {
+                ally: Some(ally),
+                kind,
+            } => Some(format!("{ally} killed a {}", kind.name())),
+            GameEvent::Killed { ally: None, kind } => {
+                Some(format!("A {} succumbed to its wounds", kind.name()))
+            }
+            GameEvent::LifeLost { lost: 1, .. } => Some("Lost a life!".to_string()),
+            GameEvent::LifeLost { lost, .. } => Some(format!("Lost {lost} lives!")),
+            GameEvent::WaveStarted { wave } => Some(format!("Wave {wave} started")),
+            GameEvent::WaveCleared { wave } => Some(format!("Wave {wave} cleared")),
+            GameEvent::AllyDestroyed { ally, .. } => Some(format!("{ally} was destroyed!")),
+            GameEvent::RunEnded { victory: true } => Some("Victory!".to_string()),
+            GameEvent::RunEnded { victory: false } => Some("Game over".to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Debuff {
     pub value: usize,
     pub cooldown: f32,
 }
 
+/// Width of the rendered board in cells, including the enemy path around the ally grid.
+pub const BOARD_WIDTH: usize = 9;
+/// Height of the rendered board in cells, including the enemy path around the ally grid.
+pub const BOARD_HEIGHT: usize = 5;
+/// Length of the enemy path, which loops once around the board's perimeter.
+pub const PATH_LENGTH: f32 = (2 * (BOARD_WIDTH - 1) + 2 * (BOARD_HEIGHT - 1)) as f32;
+/// Length of each branch of a forking path, from the top-left corner to the bottom-right one.
+pub const BRANCH_LENGTH: f32 = PATH_LENGTH / 2.0;
+/// Lives at the start of a run. Each enemy that reaches the end of the path costs some, see
+/// [`LeakCostConfig`].
+pub const STARTING_LIVES: usize = 20;
+/// Coins a single grant adds in sandbox mode.
+pub const SANDBOX_COIN_GRANT: usize = 1000;
+/// Ticks before an enemy spawns during which its spawn cell is telegraphed.
+pub const SPAWN_TELEGRAPH_TICKS: usize = 30;
+/// Seconds after a merge during which it can be undone.
+pub const MERGE_UNDO_SECONDS: f32 = 3.0;
+/// Seconds a move of an ally to an empty cell can still be undone.
+pub const MOVE_UNDO_SECONDS: f32 = 3.0;
+/// Coins a new ally costs.
+pub const ALLY_COST: usize = 10;
+/// Coins an ally of the player's choosing costs, unless `config.toml` sets `chosen_ally_cost`.
+pub const DEFAULT_CHOSEN_ALLY_COST: usize = 15;
+/// Highest level an ally can be merged to, unless `config.toml` sets `max_level`.
+pub const DEFAULT_MAX_LEVEL: usize = 10;
+/// Most ticks a campaign enemy waits before spawning, unless `config.toml` sets
+/// `spawn_delay_max`.
+pub const DEFAULT_SPAWN_DELAY_MAX: usize = 1000;
+/// Seconds without attacking before an ally is shown as idle, if the config doesn't set it.
+pub const DEFAULT_IDLE_SECONDS: f32 = 3.0;
+/// Shortest cooldown in seconds the older `atk_speed` config field can set.
+pub const MIN_ATK_SPEED: f32 = 0.1;
+/// Fastest attack rate a config can set, the same as [`MIN_ATK_SPEED`].
+pub const MAX_ATTACKS_PER_SECOND: f32 = 1.0 / MIN_ATK_SPEED;
+/// Slowest attack rate a config can set, so allies always attack again.
+pub const MIN_ATTACKS_PER_SECOND: f32 = 0.1;
+/// Longest cooldown in seconds the older `atk_speed` config field can set, the same as
+/// [`MIN_ATTACKS_PER_SECOND`].
+pub const MAX_ATK_SPEED: f32 = 1.0 / MIN_ATTACKS_PER_SECOND;
+/// Most enemies an attack moves on to after killing its target, see [`Game::ally_damage`].
+pub const MAX_KILL_FOLLOW_UPS: usize = 2;
+/// Width in degrees of the cone a facing Aoe ally splashes in, if the config doesn't set it.
+pub const DEFAULT_AOE_CONE_DEGREES: f32 = 90.0;
+/// HP allies are placed with, unless `config.toml` sets `ally_hp`.
+pub const DEFAULT_ALLY_HP: usize = 100;
+/// Share of a hit's damage a reflector sends back, unless `config.toml` sets `reflect_fraction`.
+pub const DEFAULT_REFLECT_FRACTION: f32 = 0.25;
+
+/// An attack flying toward its target, see [`ConfigFile::projectile_speed`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingHit {
+    /// [`Enemy::id`] of the target.
+    pub target_id: u64,
+    /// Ally grid cell (row, column) of the ally that fired it. The hit is dropped once that ally
+    /// leaves the cell.
+    pub ally: (usize, usize),
+    pub damage: usize,
+    /// [`Game::ticks_elapsed`] at which it lands.
+    pub land_tick: usize,
+}
+
+/// What a merge replaced, so [`Game::undo_merge`] can put it back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeUndo {
+    /// Where the dragged ally came from, and the ally.
+    pub from: ((usize, usize), Ally),
+    /// Where it was dropped, and the ally it merged into.
+    pub onto: ((usize, usize), Ally),
+    /// Seconds left to undo.
+    pub time_left: f32,
+}
+
+/// A move of an ally to an empty cell that can still be taken back.
 #[derive(Debug, Clone)]
+pub struct MoveUndo {
+    /// Cell the ally was moved from.
+    pub from: (usize, usize),
+    /// Cell it was moved to.
+    pub to: (usize, usize),
+    /// Seconds left to undo.
+    pub time_left: f32,
+}
+
+/// Two orthogonally adjacent allies that share a configured synergy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynergyLink {
+    /// Cell of the left or upper ally.
+    pub from: (usize, usize),
+    /// Cell of its right or lower neighbor.
+    pub to: (usize, usize),
+    /// Damage multiplier both of them get from the link.
+    pub multiplier: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
@@ -127,654 +806,5716 @@ pub enum Direction {
     Right,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl Direction {
+    /// Unit vector on the board, `y` growing downwards.
+    pub fn vector(self) -> (f32, f32) {
+        match self {
+            Direction::Up => (0.0, -1.0),
+            Direction::Down => (0.0, 1.0),
+            Direction::Left => (-1.0, 0.0),
+            Direction::Right => (1.0, 0.0),
+        }
+    }
+
+    /// Board cell next to `cell` (row, column) this way, or `cell` itself at the edge of the
+    /// board.
+    pub fn step_on_board(self, (row, col): (usize, usize)) -> (usize, usize) {
+        match self {
+            Direction::Up => (row.saturating_sub(1), col),
+            Direction::Down => ((row + 1).min(BOARD_HEIGHT - 1), col),
+            Direction::Left => (row, col.saturating_sub(1)),
+            Direction::Right => (row, (col + 1).min(BOARD_WIDTH - 1)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct AllyConfig {
     atk: Option<usize>,
     range: Option<usize>,
     aoe_range: Option<usize>,
     level: Option<usize>,
+    /// Attacks each second, higher being faster.
+    attacks_per_second: Option<f32>,
+    /// Seconds between attacks, read by configs written before `attacks_per_second`.
     atk_speed: Option<f32>,
     attack_cooldown: Option<f32>,
     levelup_ratio: Option<f32>,
     special_value: Option<f32>,
+    stun_chance: Option<f32>,
+    stun_duration: Option<f32>,
+    /// Which enemy in range Aoe allies center their splash on.
+    aoe_center: Option<AoeCenter>,
+    /// Halvings of speed per slow debuff.
+    slow_value: Option<usize>,
+    /// Seconds a slow debuff lasts.
+    slow_duration: Option<f32>,
+    /// Damage per tick of a damage over time debuff.
+    dot_value: Option<usize>,
+    /// Seconds a damage over time debuff lasts.
+    dot_duration: Option<f32>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct ConfigFile {
-    default: AllyConfig,
+impl AllyConfig {
+    /// Attacks each second, from `attacks_per_second` or else the older `atk_speed`. A rate read
+    /// from `atk_speed` is kept within the bounds `attacks_per_second` is clamped to.
+    pub fn attacks_per_second(&self) -> Option<f32> {
+        let legacy = self
+            .atk_speed
+            .map(|seconds| (1.0 / seconds).clamp(MIN_ATTACKS_PER_SECOND, MAX_ATTACKS_PER_SECOND));
+        self.attacks_per_second.or(legacy)
+    }
+
+    /// Fills every field missing from `self` with the one from `fallback`.
+    fn or(&self, fallback: &AllyConfig) -> AllyConfig {
+        // Either way of setting the attack rate overrides both of the fallback's
+        let rate = if self.attacks_per_second().is_some() {
+            self
+        } else {
+            fallback
+        };
+        AllyConfig {
+            atk: self.atk.or(fallback.atk),
+            range: self.range.or(fallback.range),
+            aoe_range: self.aoe_range.or(fallback.aoe_range),
+            level: self.level.or(fallback.level),
+            attacks_per_second: rate.attacks_per_second,
+            atk_speed: rate.atk_speed,
+            attack_cooldown: self.attack_cooldown.or(fallback.attack_cooldown),
+            levelup_ratio: self.levelup_ratio.or(fallback.levelup_ratio),
+            special_value: self.special_value.or(fallback.special_value),
+            stun_chance: self.stun_chance.or(fallback.stun_chance),
+            stun_duration: self.stun_duration.or(fallback.stun_duration),
+            aoe_center: self.aoe_center.or(fallback.aoe_center),
+            slow_value: self.slow_value.or(fallback.slow_value),
+            slow_duration: self.slow_duration.or(fallback.slow_duration),
+            dot_value: self.dot_value.or(fallback.dot_value),
+            dot_duration: self.dot_duration.or(fallback.dot_duration),
+        }
+    }
+
+    /// Clamps values that would break allies to the nearest safe one, describing each fix in
+    /// `problems` under `section`.
+    fn clamp_to_safe(&mut self, section: &str, problems: &mut Vec<String>) {
+        let mut report = |field: &str, value: String, fixed: String| {
+            problems.push(format!("{section}.{field} is {value}, using {fixed}"));
+        };
+        if let Some(atk_speed) = self
+            .atk_speed
+            .filter(|v| !(MIN_ATK_SPEED..=MAX_ATK_SPEED).contains(v))
+        {
+            let fixed = if atk_speed > MAX_ATK_SPEED {
+                MAX_ATK_SPEED
+            } else {
+                MIN_ATK_SPEED
+            };
+            report("atk_speed", atk_speed.to_string(), fixed.to_string());
+            self.atk_speed = Some(fixed);
+        }
+        if let Some(rate) = self
+            .attacks_per_second
+            .filter(|v| !(MIN_ATTACKS_PER_SECOND..=MAX_ATTACKS_PER_SECOND).contains(v))
+        {
+            let fixed = if rate > MAX_ATTACKS_PER_SECOND {
+                MAX_ATTACKS_PER_SECOND
+            } else {
+                MIN_ATTACKS_PER_SECOND
+            };
+            report("attacks_per_second", rate.to_string(), fixed.to_string());
+            self.attacks_per_second = Some(fixed);
+        }
+        if self.range == Some(0) {
+            report("range", "0".to_string(), "1".to_string());
+            self.range = Some(1);
+        }
+        if self.level == Some(0) {
+            report("level", "0".to_string(), "1".to_string());
+            self.level = Some(1);
+        }
+        if let Some(ratio) = self.levelup_ratio.filter(|&v| v.is_nan() || v < 1.0) {
+            report("levelup_ratio", ratio.to_string(), "1".to_string());
+            self.levelup_ratio = Some(1.0);
+        }
+        if let Some(chance) = self.stun_chance.filter(|v| !(0.0..=1.0).contains(v)) {
+            let fixed = if chance > 1.0 { 1.0 } else { 0.0 };
+            report("stun_chance", chance.to_string(), fixed.to_string());
+            self.stun_chance = Some(fixed);
+        }
+        for (field, value) in [
+            ("attack_cooldown", &mut self.attack_cooldown),
+            ("special_value", &mut self.special_value),
+            ("stun_duration", &mut self.stun_duration),
+            ("slow_duration", &mut self.slow_duration),
+            ("dot_duration", &mut self.dot_duration),
+        ] {
+            if let Some(v) = value.filter(|&v| v.is_nan() || v < 0.0) {
+                report(field, v.to_string(), "0".to_string());
+                *value = Some(0.0);
+            }
+        }
+    }
+}
+
+/// Per-element overrides, read from the `[allies.<element>]` tables.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AlliesConfig {
     basic: Option<AllyConfig>,
     slow: Option<AllyConfig>,
+    #[serde(alias = "AOE")]
     aoe: Option<AllyConfig>,
+    #[serde(alias = "Dot")]
     dot: Option<AllyConfig>,
+    #[serde(alias = "Critical")]
     critical: Option<AllyConfig>,
+    #[serde(alias = "Chain")]
+    chain: Option<AllyConfig>,
+    #[serde(alias = "Coin")]
+    coin: Option<AllyConfig>,
+    #[serde(alias = "Aura")]
+    aura: Option<AllyConfig>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Game {
-    pub level: usize,
-    pub game_state: GameState,
-    pub board: Board,
-    pub cursor: (usize, usize),
-    pub selected: Option<(usize, usize)>,
-    pub coin: usize,
-    pub config: Option<ConfigFile>,
+/// Difficulty curve of endless mode, read from the `[endless]` table. Wave `n` gets
+/// `base * growth^(n - 1)` of each stat.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EndlessConfig {
+    pub base_count: usize,
+    pub count_growth: f32,
+    pub base_hp: usize,
+    pub hp_growth: f32,
+    pub base_speed: f32,
+    pub speed_growth: f32,
+    /// Ticks between two consecutive spawns of a wave.
+    pub spawn_interval: usize,
+    /// Coins paid out for each enemy killed.
+    pub bounty: usize,
 }
 
-impl Game {
-    pub fn new() -> Game {
-        Game {
-            level: 1,
-            cursor: (0, 0),
-            selected: None,
-            coin: 100,
-            game_state: GameState::Init,
-            board: Board {
-                ally_grid: vec![vec![None; 7]; 3],
-                enemies: Vec::new(),
-                enemy_ready2spawn: Vec::new(),
-            },
-            config: None,
+impl Default for EndlessConfig {
+    fn default() -> Self {
+        Self {
+            base_count: 10,
+            count_growth: 1.2,
+            base_hp: 100,
+            hp_growth: 1.15,
+            base_speed: 1.0,
+            speed_growth: 1.03,
+            spawn_interval: 60,
+            bounty: 10,
         }
     }
+}
 
-    pub fn load_config(&self) -> ConfigFile {
-        use std::fs;
-
-        let config_file = fs::read_to_string("config.toml");
-        match config_file {
-            Ok(content) => toml::from_str(&content).unwrap_or_else(|_| self.default_config_file()),
-            Err(_) => self.default_config_file(),
-        }
+impl EndlessConfig {
+    /// Generates the enemies of endless wave `wave` (starting from 1) with their spawn timers.
+    pub fn wave(&self, wave: usize) -> Vec<(Enemy, usize)> {
+        let exponent = wave.saturating_sub(1) as i32;
+        let count = (self.base_count as f32 * self.count_growth.powi(exponent)).round() as usize;
+        let hp = (self.base_hp as f32 * self.hp_growth.powi(exponent)).round() as usize;
+        let move_speed = self.base_speed * self.speed_growth.powi(exponent);
+        (0..count.max(1))
+            .map(|k| {
+                let enemy = Enemy {
+                    hp: hp.max(1),
+                    move_speed,
+                    bounty: self.bounty,
+                    ..Default::default()
+                };
+                (enemy, k * self.spawn_interval)
+            })
+            .collect()
     }
+}
 
-    // This should be outside the function, or make it pub(crate) if needed elsewhere
-    fn default_config_file(&self) -> ConfigFile {
-        let default_ally_config = AllyConfig {
-            atk: Some(10),
-            range: Some(2),
-            aoe_range: Some(0),
-            level: Some(1),
-            atk_speed: Some(1.0),
-            attack_cooldown: Some(0.0),
-            levelup_ratio: Some(1.5),
-            special_value: Some(2.0),
-        };
+/// How well an element does against an enemy kind, read from an `[[effectiveness]]` entry.
+/// Matchups without an entry deal normal damage.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EffectivenessConfig {
+    pub element: AllyElement,
+    pub kind: EnemyKind,
+    pub multiplier: f32,
+}
 
-        ConfigFile {
-            default: default_ally_config.clone(),
-            basic: Some(default_ally_config.clone()),
-            slow: Some(default_ally_config.clone()),
-            aoe: Some(default_ally_config.clone()),
-            dot: Some(default_ally_config.clone()),
-            critical: Some(default_ally_config.clone()),
+/// A group of enemies in the campaign, read from a `[[wave]]` entry. The kind is kept as written
+/// so a typo is reported by [`ConfigFile::validate`] instead of failing the whole file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WaveConfig {
+    /// `"Grunt"`, `"Tank"` or `"Runner"`.
+    pub kind: String,
+    pub count: usize,
+    /// HP of each enemy, the kind's own if unset.
+    pub hp: Option<f32>,
+    /// Move speed of each enemy, the kind's own if unset.
+    pub speed: Option<f32>,
+}
+
+impl WaveConfig {
+    /// Reports the problems of the group to `problems`, dropping an HP or speed that isn't a
+    /// positive number for the kind's own. Returns whether the group can spawn at all, which it
+    /// can't with an unknown kind or a count of 0.
+    fn clamp_to_safe(&mut self, problems: &mut Vec<String>) -> bool {
+        if EnemyKind::from_name(&self.kind).is_none() {
+            problems.push(format!(
+                "wave.kind {:?} is unknown, skipping the group",
+                self.kind
+            ));
+            return false;
+        }
+        if self.count == 0 {
+            problems.push(format!(
+                "wave.count of {} is 0, skipping the group",
+                self.kind
+            ));
+            return false;
         }
+        for (field, value) in [("hp", &mut self.hp), ("speed", &mut self.speed)] {
+            if let Some(v) = value.filter(|v| !v.is_finite() || *v <= 0.0) {
+                problems.push(format!(
+                    "wave.{field} of {} is {v}, using the kind's own",
+                    self.kind
+                ));
+                *value = None;
+            }
+        }
+        true
     }
+}
 
-    pub fn init_game(&mut self) {
-        self.enemy_spawn();
-        self.config = Some(self.load_config());
+/// An ally placed on the board when a run starts, read from a `[[starting_ally]]` entry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StartingAllyConfig {
+    pub element: AllyElement,
+    pub second_element: Option<AllyElement>,
+    #[serde(default = "StartingAllyConfig::default_level")]
+    pub level: usize,
+    /// Index into the ally grid, 0..3.
+    pub row: usize,
+    /// Index into the ally grid, 0..7.
+    pub col: usize,
+}
+
+impl StartingAllyConfig {
+    fn default_level() -> usize {
+        1
     }
+}
 
-    pub fn update(&mut self) {
-        // at 60 FPS, called every frame
-        self.ally_update();
-        self.enemy_update();
-        if self.state_checkwin() {
-            self.game_state = GameState::End;
+/// A bonus between two elements, read from a `[[synergies]]` entry. Adjacent allies carrying
+/// the two elements each deal `damage_multiplier` times their damage.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SynergyConfig {
+    pub elements: (AllyElement, AllyElement),
+    pub damage_multiplier: f32,
+}
+
+/// Weights of the score formula, read from the `[score]` table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScoreConfig {
+    pub per_wave: u64,
+    pub per_kill: u64,
+    pub per_coin: u64,
+    /// Points per whole second survived.
+    pub per_second: u64,
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        Self {
+            per_wave: 1000,
+            per_kill: 10,
+            per_coin: 1,
+            per_second: 1,
         }
     }
+}
 
-    fn ally_update(&mut self) {
-        // Collect positions of allies that are ready to attack after updating cooldowns
-        let mut ready_to_attack = Vec::new();
+/// Coins paid for clearing a wave, read from the `[wave_bonus]` table:
+/// `lives * per_life`, plus `flawless` if no life was lost during the wave.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WaveBonusConfig {
+    pub per_life: usize,
+    pub flawless: usize,
+}
 
-        for (i, row) in self.board.ally_grid.iter_mut().enumerate() {
-            for (j, cell) in row.iter_mut().enumerate() {
-                if let Some(ally) = cell {
-                    // Decrease attack_cooldown if above zero
-                    if ally.attack_cooldown > 0.0 {
-                        ally.attack_cooldown -= 1.0 / 60.0;
-                        if ally.attack_cooldown < 0.0 {
-                            ally.attack_cooldown = 0.0;
-                        }
-                    }
-                    // If cooldown is zero or less, mark for attack
-                    if ally.attack_cooldown <= 0.0 {
-                        ready_to_attack.push((i, j));
-                    }
-                }
-            }
+impl Default for WaveBonusConfig {
+    fn default() -> Self {
+        Self {
+            per_life: 2,
+            flawless: 50,
         }
+    }
+}
 
-        let mut atk_speeds = Vec::new();
-        for &(i, j) in &ready_to_attack {
-            if let Some(ally) = self.board.ally_grid[i][j].as_ref() {
-                atk_speeds.push((i, j, ally.atk_speed));
-            }
+impl WaveBonusConfig {
+    pub fn bonus(&self, lives: usize, flawless: bool) -> usize {
+        lives * self.per_life + if flawless { self.flawless } else { 0 }
+    }
+}
+
+/// Kill rewards scaled by the coins the player holds, read from the `[reward_falloff]` table:
+/// `broke_multiplier` below `broke_below` coins, `hoard_multiplier` from `hoard_above` on, and
+/// unchanged in between. Off unless `enabled`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RewardFalloffConfig {
+    pub enabled: bool,
+    pub broke_below: usize,
+    pub broke_multiplier: f32,
+    pub hoard_above: usize,
+    pub hoard_multiplier: f32,
+}
+
+impl Default for RewardFalloffConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            broke_below: 20,
+            broke_multiplier: 1.5,
+            hoard_above: 300,
+            hoard_multiplier: 0.5,
         }
+    }
+}
 
-        for (i, j, atk_speed) in atk_speeds {
-            self.ally_ready2attack((i, j));
-            if let Some(ally) = self.board.ally_grid[i][j].as_mut() {
-                ally.attack_cooldown = atk_speed;
-            }
+impl RewardFalloffConfig {
+    /// Multiplier on kill rewards while holding `coin` coins.
+    pub fn multiplier(&self, coin: usize) -> f32 {
+        if !self.enabled {
+            1.0
+        } else if coin >= self.hoard_above {
+            self.hoard_multiplier
+        } else if coin < self.broke_below {
+            self.broke_multiplier
+        } else {
+            1.0
         }
     }
+}
 
-    fn ally_ready2attack(&mut self, pos: (usize, usize)) {
-        let (i, j) = pos;
-        if let Some(ally) = self.board.ally_grid[i][j].as_ref() {
-            if ally.element == AllyElement::Aoe || ally.second_element == Some(AllyElement::Aoe) {
-                self.ally_AOE_damage(pos);
-            } else {
-                self.ally_damage(pos);
-            }
+/// Enemies speeding up the longer a wave drags on, read from the `[frenzy]` table: from
+/// `after_seconds` into a wave, their speed is multiplied by `1 + growth_per_second` for every
+/// second past it, up to `max_multiplier`. Off unless `enabled`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FrenzyConfig {
+    pub enabled: bool,
+    pub after_seconds: f32,
+    pub growth_per_second: f32,
+    pub max_multiplier: f32,
+}
+
+impl Default for FrenzyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            after_seconds: 60.0,
+            growth_per_second: 0.02,
+            max_multiplier: 3.0,
         }
     }
+}
 
-    // Find the nearest enemy within range and attack it
-    // The ally position is its (i, j) on the grid (3x7), which is mapped to (x, y) in world space as (j+1, i+1)
-    // get the enemys position from
-    fn ally_damage(&mut self, _pos: (usize, usize)) {
-        let (i, j) = _pos;
-        let ally_position = (j as f32 + 1.0, i as f32 + 1.0);
+impl FrenzyConfig {
+    /// Multiplier on enemy speed `seconds` into a wave.
+    pub fn multiplier(&self, seconds: f32) -> f32 {
+        if !self.enabled || seconds < self.after_seconds {
+            1.0
+        } else {
+            (1.0 + (seconds - self.after_seconds) * self.growth_per_second).min(self.max_multiplier)
+        }
+    }
+}
 
-        // Find the nearest enemy within range
-        let mut nearest_enemy_idx: Option<usize> = None;
-        let mut nearest_dist: f32 = f32::MAX;
-        let mut ally_range = 1;
-        let mut ally_atk = 0;
-        let mut first_element = AllyElement::Basic;
-        let mut second_element = None;
+/// How Chain attacks bounce, read from the `[chain]` table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ChainConfig {
+    /// Enemies hit after the first one.
+    pub bounces: usize,
+    /// Damage of each bounce relative to the hit before it.
+    pub decay: f32,
+    /// Farthest, in cells, a bounce jumps from the enemy it was hit before.
+    pub radius: f32,
+}
 
-        if let Some(ally) = self.board.ally_grid[i][j].as_ref() {
-            ally_range = ally.range;
-            ally_atk = ally.atk;
-            first_element = ally.element.clone();
-            second_element = ally.second_element.clone();
+impl Default for ChainConfig {
+    fn default() -> Self {
+        Self {
+            bounces: 3,
+            decay: 0.7,
+            radius: 2.0,
+        }
+    }
+}
+
+/// How slows stack, read from the `[slow]` table. A slow of value `v` halves speed `v` times,
+/// but each further stack on the same enemy counts for `diminishing` times the one before it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SlowConfig {
+    /// Weight of each stack relative to the previous one, in `0.0..=1.0`.
+    pub diminishing: f32,
+    /// Lowest fraction of its speed a slowed enemy keeps.
+    pub floor: f32,
+}
+
+impl Default for SlowConfig {
+    fn default() -> Self {
+        Self {
+            diminishing: 0.5,
+            floor: 0.2,
+        }
+    }
+}
+
+impl SlowConfig {
+    /// Speed multiplier of an enemy under slows of `values`. The strongest stack counts in full.
+    pub fn factor(&self, values: impl IntoIterator<Item = usize>) -> f32 {
+        let mut values = values.into_iter().collect::<Vec<_>>();
+        values.sort_unstable_by(|a, b| b.cmp(a));
+        let diminishing = self.diminishing.clamp(0.0, 1.0);
+        let halvings: f32 = values
+            .iter()
+            .zip(std::iter::successors(Some(1.0), |w| Some(w * diminishing)))
+            .map(|(&value, weight)| value as f32 * weight)
+            .sum();
+        0.5_f32.powf(halvings).max(self.floor.clamp(0.0, 1.0))
+    }
+}
+
+/// Coins paid out for killing each enemy kind in campaigns, read from the `[bounty]` table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BountyConfig {
+    pub grunt: usize,
+    pub tank: usize,
+    pub runner: usize,
+    pub reflector: usize,
+}
+
+impl Default for BountyConfig {
+    fn default() -> Self {
+        Self {
+            grunt: 10,
+            tank: 25,
+            runner: 10,
+            reflector: 20,
+        }
+    }
+}
+
+impl BountyConfig {
+    pub fn of(&self, kind: EnemyKind) -> usize {
+        match kind {
+            EnemyKind::Grunt => self.grunt,
+            EnemyKind::Tank => self.tank,
+            EnemyKind::Runner => self.runner,
+            EnemyKind::Reflector => self.reflector,
+        }
+    }
+}
+
+/// Lives lost when an enemy of each kind reaches the exit, read from the `[leak_cost]` table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LeakCostConfig {
+    pub grunt: usize,
+    pub tank: usize,
+    pub runner: usize,
+    pub reflector: usize,
+}
+
+impl Default for LeakCostConfig {
+    fn default() -> Self {
+        Self {
+            grunt: 1,
+            tank: 3,
+            runner: 1,
+            reflector: 2,
+        }
+    }
+}
+
+impl LeakCostConfig {
+    pub fn of(&self, kind: EnemyKind) -> usize {
+        match kind {
+            EnemyKind::Grunt => self.grunt,
+            EnemyKind::Tank => self.tank,
+            EnemyKind::Runner => self.runner,
+            EnemyKind::Reflector => self.reflector,
+        }
+    }
+}
+
+/// Damage multipliers of each armor type against single-target and splash hits, read from the
+/// `[armor_type]` table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ArmorTypeConfig {
+    pub light_single: f32,
+    pub light_aoe: f32,
+    pub heavy_single: f32,
+    pub heavy_aoe: f32,
+}
+
+impl Default for ArmorTypeConfig {
+    fn default() -> Self {
+        Self {
+            light_single: 1.25,
+            light_aoe: 1.0,
+            heavy_single: 1.0,
+            heavy_aoe: 1.25,
+        }
+    }
+}
+
+impl ArmorTypeConfig {
+    pub fn multiplier(&self, armor_type: ArmorType, shape: HitShape) -> f32 {
+        match (armor_type, shape) {
+            (ArmorType::Unarmored, _) => 1.0,
+            (ArmorType::Light, HitShape::Single) => self.light_single,
+            (ArmorType::Light, HitShape::Aoe) => self.light_aoe,
+            (ArmorType::Heavy, HitShape::Single) => self.heavy_single,
+            (ArmorType::Heavy, HitShape::Aoe) => self.heavy_aoe,
+        }
+    }
+}
+
+/// HP per second each enemy kind regenerates while damaged, read from the `[regen]` table.
+/// Nothing regenerates by default.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RegenConfig {
+    pub grunt: f32,
+    pub tank: f32,
+    pub runner: f32,
+    pub reflector: f32,
+}
+
+impl RegenConfig {
+    pub fn of(&self, kind: EnemyKind) -> f32 {
+        match kind {
+            EnemyKind::Grunt => self.grunt,
+            EnemyKind::Tank => self.tank,
+            EnemyKind::Runner => self.runner,
+            EnemyKind::Reflector => self.reflector,
+        }
+    }
+}
+
+/// Contents of `config.toml`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfigFile {
+    #[serde(rename = "general")]
+    default: AllyConfig,
+    #[serde(default)]
+    allies: AlliesConfig,
+    #[serde(default)]
+    pub endless: EndlessConfig,
+    #[serde(default)]
+    pub score: ScoreConfig,
+    #[serde(default)]
+    pub bounty: BountyConfig,
+    #[serde(default)]
+    pub leak_cost: LeakCostConfig,
+    #[serde(default)]
+    pub regen: RegenConfig,
+    #[serde(default)]
+    pub slow: SlowConfig,
+    #[serde(default)]
+    pub wave_bonus: WaveBonusConfig,
+    #[serde(default)]
+    pub reward_falloff: RewardFalloffConfig,
+    #[serde(default)]
+    pub frenzy: FrenzyConfig,
+    #[serde(default)]
+    pub armor_type: ArmorTypeConfig,
+    #[serde(default)]
+    pub chain: ChainConfig,
+    #[serde(default)]
+    pub merge_rule: MergeRule,
+    /// Kills needed to restore one life, up to [`STARTING_LIVES`]. `0` turns lifesteal off.
+    #[serde(default)]
+    pub lifesteal_per_kill: usize,
+    /// Random allies placed for free when a run starts, as many as there is room for.
+    #[serde(default)]
+    pub free_starting_allies: usize,
+    /// Highest level merges can reach, [`DEFAULT_MAX_LEVEL`] if unset.
+    pub max_level: Option<usize>,
+    /// Seconds without attacking before an ally is shown as idle, [`DEFAULT_IDLE_SECONDS`] if
+    /// unset. `0` never shows it.
+    pub idle_seconds: Option<f32>,
+    /// Cells per second attacks fly at before they land. Unset, attacks land instantly.
+    pub projectile_speed: Option<f32>,
+    /// Width in degrees of the cone a facing Aoe ally splashes in, [`DEFAULT_AOE_CONE_DEGREES`]
+    /// if unset.
+    pub aoe_cone_degrees: Option<f32>,
+    /// Share of enemies taking the [`PathBranch::Upper`] branch, the rest taking the lower one.
+    /// Unset, the path doesn't fork and loops around the whole board.
+    pub branch_split: Option<f32>,
+    /// Most enemies on the path at once, spawns past it handled as `enemy_overflow` says. Unset,
+    /// there is no cap.
+    pub max_enemies: Option<usize>,
+    #[serde(default)]
+    pub enemy_overflow: EnemyOverflow,
+    #[serde(default)]
+    pub speed_unit: SpeedUnit,
+    #[serde(default)]
+    pub ally_selection_mode: AllySelectionMode,
+    /// Dropping an ally on an empty cell next to one it levels up merges them instead of moving
+    /// it there.
+    #[serde(default)]
+    pub merge_snap: bool,
+    /// Coins an ally costs with [`AllySelectionMode::Choose`], [`DEFAULT_CHOSEN_ALLY_COST`] if
+    /// unset.
+    pub chosen_ally_cost: Option<usize>,
+    /// HP allies are placed with, [`DEFAULT_ALLY_HP`] if unset.
+    pub ally_hp: Option<usize>,
+    /// Share of each direct hit's damage a [`EnemyKind::Reflector`] sends back to the ally that
+    /// dealt it, [`DEFAULT_REFLECT_FRACTION`] if unset.
+    pub reflect_fraction: Option<f32>,
+    /// Fewest ticks a campaign enemy waits before spawning, `0` if unset.
+    pub spawn_delay_min: Option<usize>,
+    /// Most ticks a campaign enemy waits before spawning, [`DEFAULT_SPAWN_DELAY_MAX`] if unset.
+    pub spawn_delay_max: Option<usize>,
+    #[serde(default)]
+    pub synergies: Vec<SynergyConfig>,
+    #[serde(default)]
+    pub effectiveness: Vec<EffectivenessConfig>,
+    #[serde(default)]
+    pub starting_ally: Vec<StartingAllyConfig>,
+    /// Enemy groups of the campaign, the built-in mix of kinds if empty.
+    #[serde(default)]
+    pub wave: Vec<WaveConfig>,
+}
+
+impl ConfigFile {
+    /// Parses a config file. Malformed input is reported as an error, never a panic.
+    pub fn from_toml(content: &str) -> Result<ConfigFile, toml::de::Error> {
+        let config: ConfigFile = toml::from_str(content)?;
+        let (min, max) = config.spawn_delay_range();
+        if min > max {
+            return Err(serde::de::Error::custom(format!(
+                "spawn_delay_min ({min}) is greater than spawn_delay_max ({max})"
+            )));
+        }
+        Ok(config)
+    }
+
+    /// Lists the values that would break the game, such as a zero `atk_speed` or a
+    /// `levelup_ratio` below 1. See [`ConfigFile::clamp_to_safe`] to fix them.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let problems = self.clone().clamp_to_safe();
+        if problems.is_empty() {
+            Ok(())
         } else {
-            return;
+            Err(problems)
         }
+    }
 
-        // Use iterator methods to find the nearest enemy within range in a functional style
-        nearest_enemy_idx = self
-            .board
+    /// Clamps the values [`ConfigFile::validate`] flags to safe ones, returning what was fixed.
+    pub fn clamp_to_safe(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+        self.default.clamp_to_safe("general", &mut problems);
+        let allies = &mut self.allies;
+        for (name, overrides) in [
+            ("basic", &mut allies.basic),
+            ("slow", &mut allies.slow),
+            ("AOE", &mut allies.aoe),
+            ("Dot", &mut allies.dot),
+            ("Critical", &mut allies.critical),
+            ("Chain", &mut allies.chain),
+            ("Coin", &mut allies.coin),
+            ("Aura", &mut allies.aura),
+        ] {
+            if let Some(overrides) = overrides {
+                overrides.clamp_to_safe(&format!("allies.{name}"), &mut problems);
+            }
+        }
+        if let Some(speed) = self.projectile_speed.filter(|&v| v.is_nan() || v <= 0.0) {
+            problems.push(format!("projectile_speed is {speed}, using instant hits"));
+            self.projectile_speed = None;
+        }
+        if let Some(degrees) = self.aoe_cone_degrees.filter(|v| !(0.0..=360.0).contains(v)) {
+            let fixed = if degrees > 360.0 { 360.0 } else { 0.0 };
+            problems.push(format!("aoe_cone_degrees is {degrees}, using {fixed}"));
+            self.aoe_cone_degrees = Some(fixed);
+        }
+        let falloff = &mut self.reward_falloff;
+        for (field, value) in [
+            ("broke_multiplier", &mut falloff.broke_multiplier),
+            ("hoard_multiplier", &mut falloff.hoard_multiplier),
+        ] {
+            if value.is_nan() || *value < 0.0 {
+                problems.push(format!("reward_falloff.{field} is {value}, using 1"));
+                *value = 1.0;
+            }
+        }
+        let frenzy = &mut self.frenzy;
+        for (field, value, fixed) in [
+            ("after_seconds", &mut frenzy.after_seconds, 0.0),
+            ("growth_per_second", &mut frenzy.growth_per_second, 0.0),
+            ("max_multiplier", &mut frenzy.max_multiplier, 1.0),
+        ] {
+            if value.is_nan() || *value < fixed {
+                problems.push(format!("frenzy.{field} is {value}, using {fixed}"));
+                *value = fixed;
+            }
+        }
+        if let Some(split) = self.branch_split.filter(|v| !(0.0..=1.0).contains(v)) {
+            let fixed = if split > 1.0 { 1.0 } else { 0.0 };
+            problems.push(format!("branch_split is {split}, using {fixed}"));
+            self.branch_split = Some(fixed);
+        }
+        if self.max_enemies == Some(0) {
+            problems.push("max_enemies is 0, using 1".to_string());
+            self.max_enemies = Some(1);
+        }
+        if self.ally_hp == Some(0) {
+            problems.push("ally_hp is 0, using 1".to_string());
+            self.ally_hp = Some(1);
+        }
+        if let Some(fraction) = self.reflect_fraction.filter(|v| !(0.0..=1.0).contains(v)) {
+            let fixed = if fraction > 1.0 { 1.0 } else { 0.0 };
+            problems.push(format!("reflect_fraction is {fraction}, using {fixed}"));
+            self.reflect_fraction = Some(fixed);
+        }
+        self.wave
+            .retain_mut(|group| group.clamp_to_safe(&mut problems));
+        problems
+    }
+
+    /// Range of ticks campaign enemies wait before spawning, `spawn_delay_min..=spawn_delay_max`.
+    pub fn spawn_delay_range(&self) -> (usize, usize) {
+        (
+            self.spawn_delay_min.unwrap_or(0),
+            self.spawn_delay_max.unwrap_or(DEFAULT_SPAWN_DELAY_MAX),
+        )
+    }
+
+    /// Damage multiplier of an ally with `elements` against an enemy of `kind`. Dual allies get
+    /// the product of both their elements' matchups.
+    pub fn effectiveness(&self, elements: [Option<AllyElement>; 2], kind: EnemyKind) -> f32 {
+        self.effectiveness
+            .iter()
+            .filter(|e| e.kind == kind && elements.contains(&Some(e.element)))
+            .map(|e| e.multiplier)
+            .product()
+    }
+
+    /// Damage multiplier of a hit by an ally with `elements` on `enemy`: the elements' matchup
+    /// against its kind times how its armor type takes the shape of the hit.
+    pub fn hit_multiplier(&self, elements: [Option<AllyElement>; 2], enemy: &Enemy) -> f32 {
+        self.effectiveness(elements, enemy.kind)
+            * self
+                .armor_type
+                .multiplier(enemy.armor_type, HitShape::of(elements))
+    }
+
+    /// The stats for `element`: its `[allies.<element>]` table, with missing fields inherited
+    /// from `[general]`.
+    pub fn ally_config(&self, element: AllyElement) -> AllyConfig {
+        let overrides = match element {
+            AllyElement::Basic => &self.allies.basic,
+            AllyElement::Slow => &self.allies.slow,
+            AllyElement::Aoe => &self.allies.aoe,
+            AllyElement::Dot => &self.allies.dot,
+            AllyElement::Critical => &self.allies.critical,
+            AllyElement::Chain => &self.allies.chain,
+            AllyElement::Coin => &self.allies.coin,
+            AllyElement::Aura => &self.allies.aura,
+        };
+        match overrides {
+            Some(overrides) => overrides.or(&self.default),
+            None => self.default.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub mode: GameMode,
+    /// Current wave, starting from 1.
+    pub level: usize,
+    /// Waves the player has fully cleared this run.
+    pub waves_cleared: usize,
+    pub lives: usize,
+    /// Enemies killed this run.
+    pub kills: usize,
+    /// Damage and kills of the run by the element of the allies that dealt them. Allies of two
+    /// elements count toward both, sold and merged ones included.
+    pub element_stats: BTreeMap<AllyElement, AllyStats>,
+    /// Updates run so far, at 60 per in-game second.
+    pub ticks_elapsed: usize,
+    /// Lives lost since the current wave started.
+    pub lives_lost_this_wave: usize,
+    /// Updates run since the current wave started, for [`FrenzyConfig`].
+    pub wave_ticks: usize,
+    pub game_state: GameState,
+    pub board: Board,
+    pub cursor: (usize, usize),
+    pub selected: Option<(usize, usize)>,
+    pub coin: usize,
+    /// Coins earned this run, regardless of how many were spent.
+    pub coins_earned: usize,
+    /// Scrap collected from selling merged allies this run, see [`Game::scrap_value`]. Unlike
+    /// coins it buys nothing on the board.
+    pub scrap: usize,
+    /// Buy allies on their own whenever coins allow, see [`Game::auto_buy_threshold`].
+    pub auto_buy: bool,
+    /// Coins auto-buy keeps in reserve; it only buys while a purchase leaves at least this many.
+    pub auto_buy_threshold: usize,
+    /// Merge bought allies into a matching one on the board instead of placing them, see
+    /// [`Game::auto_merge_partner`].
+    pub auto_merge_on_buy: bool,
+    /// Leave the dragged ally selected when dropping it fails to merge, instead of deselecting
+    /// it like every other drop does.
+    pub keep_selection_on_failed_merge: bool,
+    pub config: Option<ConfigFile>,
+    /// Day the run started on, in days since 1970-01-01.
+    pub day: u64,
+    /// Source of every random roll in the run, so a seeded game plays out the same every time,
+    /// on every platform and rand version.
+    pub rng: ChaCha8Rng,
+    /// Seed `rng` started from, kept so the run can be replayed.
+    pub seed: u64,
+    /// Damage multiplier each ally cell gets from its neighbors this frame, see
+    /// [`Game::synergy_links`].
+    pub synergy: Vec<Vec<f32>>,
+    /// Events of the latest [`Game::update`].
+    pub events: Vec<GameEvent>,
+    /// The latest merge, while it can still be undone.
+    pub last_merge_undo: Option<MergeUndo>,
+    /// The latest move to an empty cell, while it can still be undone.
+    pub last_move: Option<MoveUndo>,
+    /// [`Enemy::id`] every ally attacks first while it's in range.
+    pub priority_target: Option<u64>,
+    /// [`Enemy::id`] given to the next enemy queued to spawn.
+    next_enemy_id: u64,
+    /// Attacks still flying toward their targets.
+    pub pending_hits: Vec<PendingHit>,
+    /// The ally that dealt the most damage of those sold or destroyed, for [`Game::mvp`].
+    departed_mvp: Option<Ally>,
+}
+
+impl Game {
+    pub fn new() -> Game {
+        let seed = ChaCha8Rng::from_os_rng().random();
+        Game {
+            mode: GameMode::Campaign,
+            level: 1,
+            waves_cleared: 0,
+            lives: STARTING_LIVES,
+            kills: 0,
+            element_stats: BTreeMap::new(),
+            ticks_elapsed: 0,
+            lives_lost_this_wave: 0,
+            wave_ticks: 0,
+            cursor: (0, 0),
+            selected: None,
+            coin: 100,
+            coins_earned: 0,
+            scrap: 0,
+            auto_buy: false,
+            auto_buy_threshold: 0,
+            auto_merge_on_buy: false,
+            keep_selection_on_failed_merge: false,
+            game_state: GameState::Init,
+            board: Board {
+                ally_grid: vec![vec![None; 7]; 3],
+                enemies: Vec::new(),
+                enemy_ready2spawn: Vec::new(),
+            },
+            config: None,
+            day: daily::today(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            seed,
+            synergy: Vec::new(),
+            events: Vec::new(),
+            last_merge_undo: None,
+            last_move: None,
+            priority_target: None,
+            pending_hits: Vec::new(),
+            next_enemy_id: 0,
+            departed_mvp: None,
+        }
+    }
+
+    /// Constructs a [`Game`] whose random rolls all come from `seed`.
+    pub fn new_seeded(seed: u64) -> Game {
+        Game {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            seed,
+            ..Game::new()
+        }
+    }
+
+    pub fn with_mode(mode: GameMode) -> Game {
+        let game = match mode {
+            GameMode::Daily => Game::new_seeded(daily::daily_seed()),
+            _ => Game::new(),
+        };
+        Game { mode, ..game }
+    }
+
+    /// Highest level merges can reach.
+    pub fn max_level(&self) -> usize {
+        self.config
+            .as_ref()
+            .and_then(|config| config.max_level)
+            .unwrap_or(DEFAULT_MAX_LEVEL)
+    }
+
+    pub fn merge_rule(&self) -> MergeRule {
+        self.config
+            .as_ref()
+            .map(|config| config.merge_rule)
+            .unwrap_or_default()
+    }
+
+    /// Whether `ally` has gone without a target long enough to be shown as idle.
+    pub fn is_idle(&self, ally: &Ally) -> bool {
+        let seconds = self
+            .config
+            .as_ref()
+            .and_then(|config| config.idle_seconds)
+            .unwrap_or(DEFAULT_IDLE_SECONDS);
+        seconds > 0.0 && ally.idle_ticks as f32 >= seconds * 60.0
+    }
+
+    /// Whether `ally` is at the level cap and can't be leveled up further.
+    pub fn is_maxed(&self, ally: &Ally) -> bool {
+        ally.level >= self.max_level()
+    }
+
+    /// Key of the high-score table this run is ranked in.
+    pub fn high_score_table(&self) -> String {
+        self.mode.high_score_table(self.day)
+    }
+
+    /// A running [`Game`] around an existing board, e.g. to benchmark or test `update` against
+    /// a hand-built set of allies and enemies. Enemy ids continue after the highest one already
+    /// on it.
+    pub fn with_board(board: Board) -> Game {
+        let next_enemy_id = board
             .enemies
             .iter()
-            .enumerate()
-            .filter_map(|(idx, enemy)| {
-                let enemy_pos = Game::enemy_grid_position(enemy.clone());
-                let dx = ally_position.0 - enemy_pos.0;
-                let dy = ally_position.1 - enemy_pos.1;
-                let dist = (dx * dx + dy * dy).sqrt();
-                if dist <= ally_range as f32 {
-                    Some((idx, dist))
-                } else {
-                    None
-                }
-            })
-            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-            .map(|(idx, _)| idx);
+            .chain(board.enemy_ready2spawn.iter().map(|(enemy, _)| enemy))
+            .map(|enemy| enemy.id + 1)
+            .max()
+            .unwrap_or_default();
+        Game {
+            board,
+            game_state: GameState::Running,
+            next_enemy_id,
+            ..Game::new()
+        }
+    }
 
-        // Prepare damage value (with critical hit if applicable)
-        let mut damage = ally_atk;
-        if first_element == AllyElement::Critical || second_element == Some(AllyElement::Critical) {
-            damage = (damage as f32 * 2.0) as usize;
+    pub fn load_config(&self) -> ConfigFile {
+        use std::fs;
+
+        let config_file = fs::read_to_string("config.toml");
+        match config_file {
+            Ok(content) => self.parse_config(&content),
+            Err(err) => {
+                info!(%err, "config.toml not readable, using default config");
+                self.default_config_file()
+            }
         }
-        if let Some(enemy_idx) = nearest_enemy_idx {
-            let enemy = &mut self.board.enemies[enemy_idx];
+    }
 
-            // Apply debuffs (first and second element, exclude AOE)
-            match first_element {
-                AllyElement::Slow => {
-                    enemy.slow_list.push(Debuff {
-                        value: 1,
-                        cooldown: 1.0,
-                    });
-                }
-                AllyElement::Dot => {
-                    enemy.dot_list.push(Debuff {
-                        value: 2,
-                        cooldown: 2.0,
-                    });
+    /// The config in `content`, with unsafe values fixed up, or the default config if it can't
+    /// be parsed.
+    pub fn parse_config(&self, content: &str) -> ConfigFile {
+        match ConfigFile::from_toml(content) {
+            Ok(mut config) => {
+                for problem in config.clamp_to_safe() {
+                    warn!(%problem, "unsafe value in config.toml");
                 }
-                _ => {}
+                config
             }
-            if let Some(second) = &second_element {
-                match second {
-                    AllyElement::Slow => {
-                        enemy.slow_list.push(Debuff {
-                            value: 1,
-                            cooldown: 1.0,
-                        });
-                    }
-                    AllyElement::Dot => {
-                        enemy.dot_list.push(Debuff {
-                            value: 2,
-                            cooldown: 2.0,
-                        });
-                    }
-                    _ => {}
-                }
+            Err(err) => {
+                warn!(%err, "invalid config.toml, using default config");
+                self.default_config_file()
             }
+        }
+    }
+
+    /// The config used when `config.toml` is missing or broken.
+    pub fn default_config_file(&self) -> ConfigFile {
+        let default_ally_config = AllyConfig {
+            atk: Some(10),
+            range: Some(2),
+            aoe_range: Some(0),
+            level: Some(1),
+            attacks_per_second: Some(1.0),
+            atk_speed: None,
+            attack_cooldown: Some(0.0),
+            levelup_ratio: Some(1.5),
+            special_value: Some(2.0),
+            stun_chance: Some(0.0),
+            stun_duration: Some(0.0),
+            aoe_center: Some(AoeCenter::Nearest),
+            slow_value: Some(1),
+            slow_duration: Some(1.0),
+            dot_value: Some(2),
+            dot_duration: Some(2.0),
+        };
+
+        ConfigFile {
+            default: default_ally_config,
+            allies: AlliesConfig::default(),
+            endless: EndlessConfig::default(),
+            score: ScoreConfig::default(),
+            bounty: BountyConfig::default(),
+            leak_cost: LeakCostConfig::default(),
+            regen: RegenConfig::default(),
+            slow: SlowConfig::default(),
+            wave_bonus: WaveBonusConfig::default(),
+            reward_falloff: RewardFalloffConfig::default(),
+            frenzy: FrenzyConfig::default(),
+            armor_type: ArmorTypeConfig::default(),
+            merge_rule: MergeRule::default(),
+            chain: ChainConfig::default(),
+            lifesteal_per_kill: 0,
+            free_starting_allies: 0,
+            max_level: None,
+            idle_seconds: None,
+            projectile_speed: None,
+            aoe_cone_degrees: None,
+            branch_split: None,
+            max_enemies: None,
+            enemy_overflow: EnemyOverflow::default(),
+            speed_unit: SpeedUnit::default(),
+            ally_selection_mode: AllySelectionMode::default(),
+            merge_snap: false,
+            chosen_ally_cost: None,
+            ally_hp: None,
+            reflect_fraction: None,
+            spawn_delay_min: None,
+            spawn_delay_max: None,
+            synergies: Vec::new(),
+            effectiveness: Vec::new(),
+            starting_ally: Vec::new(),
+            wave: Vec::new(),
+        }
+    }
+
+    pub fn init_game(&mut self) {
+        // Everyone plays the same daily challenge, whatever their config.toml says
+        let config = match self.mode {
+            GameMode::Daily => self.default_config_file(),
+            _ => self.load_config(),
+        };
+        self.init_game_with(config);
+    }
+
+    /// Starts the run with `config` instead of the one in `config.toml`.
+    pub fn init_game_with(&mut self, config: ConfigFile) {
+        self.config = Some(config);
+        match self.mode {
+            GameMode::Campaign | GameMode::Daily | GameMode::Tutorial => self.enemy_spawn(),
+            GameMode::Endless => self.endless_wave_spawn(),
+            // Enemies only come when spawned by hand
+            GameMode::Sandbox => {}
+        }
+        if self.mode != GameMode::Sandbox {
+            self.events
+                .push(GameEvent::WaveStarted { wave: self.level });
+        }
+        self.place_starting_allies();
+        self.place_free_allies();
+        self.game_state = GameState::Running;
+        if self.mode == GameMode::Tutorial {
+            self.place_tutorial_allies();
+            self.game_state = GameState::Pause;
+        }
+    }
+
+    /// Gives the tutorial a pair of matching allies, so there is something to merge whatever the
+    /// player buys.
+    fn place_tutorial_allies(&mut self) {
+        for col in [2, 4] {
+            if self.board.ally_grid[1][col].is_none() {
+                self.board.ally_grid[1][col] = Some(self.new_ally(AllyElement::Basic));
+            }
+        }
+    }
+
+    pub fn update(&mut self) {
+        // Nothing advances while paused, not even cooldowns and debuff timers
+        if matches!(self.game_state, GameState::End | GameState::Pause) {
+            return;
+        }
+        self.events.clear();
+        self.ticks_elapsed += 1;
+        self.wave_ticks += 1;
+        if let Some(undo) = self.last_merge_undo.as_mut() {
+            undo.time_left -= 1.0 / 60.0;
+            if undo.time_left <= 0.0 {
+                self.last_merge_undo = None;
+            }
+        }
+        if let Some(undo) = self.last_move.as_mut() {
+            undo.time_left -= 1.0 / 60.0;
+            if undo.time_left <= 0.0 {
+                self.last_move = None;
+            }
+        }
+        // at 60 FPS, called every frame
+        self.ally_update();
+        self.pending_hits_update();
+        self.enemy_update();
+        self.auto_buy_update();
+        if self.state_checklose() {
+            self.game_state = GameState::End;
+            self.events.push(GameEvent::RunEnded { victory: false });
+        } else if self.state_checkwin() {
+            self.waves_cleared += 1;
+            self.events
+                .push(GameEvent::WaveCleared { wave: self.level });
+            self.pay_wave_bonus();
+            self.game_state = GameState::End;
+            self.events.push(GameEvent::RunEnded { victory: true });
+        } else if self.mode == GameMode::Endless && self.is_wave_cleared() {
+            self.waves_cleared += 1;
+            self.events
+                .push(GameEvent::WaveCleared { wave: self.level });
+            self.pay_wave_bonus();
+            self.level += 1;
+            info!(wave = self.level, "next wave");
+            self.wave_ticks = 0;
+            self.events
+                .push(GameEvent::WaveStarted { wave: self.level });
+            self.endless_wave_spawn();
+        }
+    }
+
+    /// The run's score so far: `waves_cleared * per_wave + kills * per_kill +
+    /// coins_earned * per_coin + seconds survived * per_second`.
+    pub fn score(&self) -> u64 {
+        let default = ScoreConfig::default();
+        let weights = self
+            .config
+            .as_ref()
+            .map_or(&default, |config| &config.score);
+        self.waves_cleared as u64 * weights.per_wave
+            + self.kills as u64 * weights.per_kill
+            + self.coins_earned as u64 * weights.per_coin
+            + self.elapsed_seconds() as u64 * weights.per_second
+    }
+
+    /// Pays the bonus of [`WaveBonusConfig`] for the wave just cleared and starts counting lives
+    /// lost afresh for the next one.
+    fn pay_wave_bonus(&mut self) {
+        let default = WaveBonusConfig::default();
+        let bonus_config = self
+            .config
+            .as_ref()
+            .map_or(&default, |config| &config.wave_bonus);
+        let flawless = self.lives_lost_this_wave == 0;
+        let bonus = bonus_config.bonus(self.lives, flawless);
+        self.lives_lost_this_wave = 0;
+        if bonus > 0 {
+            self.coin += bonus;
+            self.coins_earned += bonus;
+            info!(bonus, flawless, "wave clear bonus");
+        }
+    }
+
+    /// What enemy speeds are measured in.
+    pub fn speed_unit(&self) -> SpeedUnit {
+        self.config
+            .as_ref()
+            .map(|config| config.speed_unit)
+            .unwrap_or_default()
+    }
+
+    /// Whether the path forks into a [`PathBranch::Upper`] and a [`PathBranch::Lower`] branch.
+    pub fn is_path_forked(&self) -> bool {
+        self.config
+            .as_ref()
+            .is_some_and(|config| config.branch_split.is_some())
+    }
+
+    /// Board cells `(row, column)` the path runs through, each with how far along it enemies
+    /// reach the cell, from `0.0` at the entrance to `1.0` at the exit. Cells on both branches
+    /// of a forked path take the nearer of the two.
+    pub fn path_cells(&self) -> BTreeMap<(usize, usize), f32> {
+        let branches = if self.is_path_forked() {
+            vec![Some(PathBranch::Upper), Some(PathBranch::Lower)]
+        } else {
+            vec![None]
+        };
+        let mut cells = BTreeMap::new();
+        for branch in branches {
+            let length = Enemy {
+                branch,
+                ..Default::default()
+            }
+            .path_length();
+            for step in 0..length as usize {
+                let cell = Game::enemy_grid_cell(&Enemy {
+                    position: step as f32,
+                    branch,
+                    ..Default::default()
+                });
+                let progress = step as f32 / length;
+                cells
+                    .entry(cell)
+                    .and_modify(|p: &mut f32| *p = p.min(progress))
+                    .or_insert(progress);
+            }
+        }
+        // Both branches leave through the corner they meet at
+        if self.is_path_forked() {
+            cells.insert((BOARD_HEIGHT - 1, BOARD_WIDTH - 1), 1.0);
+        }
+        cells
+    }
+
+    /// Multiplier on kill rewards at the current coin count, see [`RewardFalloffConfig`].
+    pub fn reward_multiplier(&self) -> f32 {
+        self.config
+            .as_ref()
+            .map_or(1.0, |config| config.reward_falloff.multiplier(self.coin))
+    }
+
+    /// Multiplier on enemy speed this far into the wave, see [`FrenzyConfig`].
+    pub fn frenzy_multiplier(&self) -> f32 {
+        self.config.as_ref().map_or(1.0, |config| {
+            config.frenzy.multiplier(self.wave_ticks as f32 / 60.0)
+        })
+    }
+
+    /// How far along the path the most advanced enemy is, from `0.0` at the entrance to `1.0` at
+    /// the exit. `0.0` with no enemy on the field.
+    pub fn path_progress_max(&self) -> f32 {
+        self.board
+            .enemies
+            .iter()
+            .map(|enemy| enemy.position / enemy.path_length())
+            .fold(0.0, f32::max)
+            .clamp(0.0, 1.0)
+    }
+
+    /// In-game seconds the run has lasted, not counting time spent paused.
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.ticks_elapsed as f32 / 60.0
+    }
+
+    /// Whether the run ended with the player still standing.
+    pub fn is_victory(&self) -> bool {
+        matches!(self.game_state, GameState::End) && self.lives > 0
+    }
+
+    fn ally_update(&mut self) {
+        self.synergy_update();
+        self.aura_update();
+
+        // Collect positions of allies that are ready to attack after updating cooldowns
+        let mut ready_to_attack = Vec::new();
+
+        for (i, row) in self.board.ally_grid.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                if let Some(ally) = cell {
+                    ally.idle_ticks = ally.idle_ticks.saturating_add(1);
+                    // Decrease attack_cooldown if above zero
+                    if ally.attack_cooldown > 0.0 {
+                        ally.attack_cooldown -= 1.0 / 60.0;
+                        if ally.attack_cooldown < 0.0 {
+                            ally.attack_cooldown = 0.0;
+                        }
+                    }
+                    // If cooldown is zero or less, mark for attack
+                    if ally.attack_cooldown <= 0.0 {
+                        ready_to_attack.push((i, j));
+                    }
+                }
+            }
+        }
+
+        let mut cooldowns = Vec::new();
+        for &(i, j) in &ready_to_attack {
+            if let Some(ally) = self.board.ally_grid[i][j].as_ref() {
+                cooldowns.push((i, j, ally.cooldown()));
+            }
+        }
+
+        for (i, j, cooldown) in cooldowns {
+            let Some(ally) = self.board.ally_grid[i][j].as_ref() else {
+                continue;
+            };
+            // A Coin ally of two elements earns and still attacks with its other element
+            let earns = ally.has_element(AllyElement::Coin);
+            let attacks =
+                !ally.has_element(AllyElement::Aura) && (!earns || ally.second_element.is_some());
+            if earns {
+                self.ally_generate_coins((i, j));
+            }
+            if attacks {
+                self.ally_ready2attack((i, j));
+            }
+            if let Some(ally) = self.board.ally_grid[i][j].as_mut() {
+                ally.attack_cooldown = cooldown;
+            }
+        }
+    }
+
+    /// Slows every enemy within range of an Aura ally by the ally's `special_value`, stacking
+    /// like any other slow. The slow lasts a single tick and is put back every tick, so it ends
+    /// as soon as the enemy leaves the aura or the ally leaves the board.
+    fn aura_update(&mut self) {
+        for (i, row) in self.board.ally_grid.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                let Some(ally) = cell.as_mut() else {
+                    continue;
+                };
+                let value = ally.special_value.max(0.0).round() as usize;
+                if !ally.has_element(AllyElement::Aura) || value == 0 {
+                    continue;
+                }
+                for enemy in self.board.enemies.iter_mut() {
+                    if Game::distance_from_ally((i, j), enemy) <= ally.range as f32 {
+                        enemy.slow_list.push(Debuff {
+                            value,
+                            cooldown: 1.0 / 60.0,
+                        });
+                        // Slowing counts as being busy, it has no use for a target
+                        ally.idle_ticks = 0;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pays out the income of the Coin ally at `pos`: its `special_value` in coins.
+    fn ally_generate_coins(&mut self, pos: (usize, usize)) {
+        let Some(ally) = self.board.ally_grid[pos.0][pos.1].as_mut() else {
+            return;
+        };
+        // Earning counts as being busy, it has no use for a target
+        ally.idle_ticks = 0;
+        let income = ally.special_value.max(0.0).round() as usize;
+        self.coin += income;
+        self.coins_earned += income;
+    }
+
+    /// Pairs of orthogonally adjacent allies that share a configured synergy.
+    pub fn synergy_links(&self) -> Vec<SynergyLink> {
+        let Some(config) = self.config.as_ref() else {
+            return Vec::new();
+        };
+        let elements = |ally: &Ally| [Some(ally.element), ally.second_element];
+        let multiplier = |a: &Ally, b: &Ally| {
+            config
+                .synergies
+                .iter()
+                .filter(|synergy| {
+                    let (x, y) = synergy.elements;
+                    let (x, y) = (Some(x), Some(y));
+                    (elements(a).contains(&x) && elements(b).contains(&y))
+                        || (elements(a).contains(&y) && elements(b).contains(&x))
+                })
+                .map(|synergy| synergy.damage_multiplier)
+                .product::<f32>()
+        };
+
+        let grid = &self.board.ally_grid;
+        let mut links = Vec::new();
+        for (i, row) in grid.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                let Some(ally) = cell else {
+                    continue;
+                };
+                for (ni, nj) in [(i, j + 1), (i + 1, j)] {
+                    let Some(Some(neighbor)) = grid.get(ni).and_then(|r| r.get(nj)) else {
+                        continue;
+                    };
+                    let m = multiplier(ally, neighbor);
+                    if m != 1.0 {
+                        links.push(SynergyLink {
+                            from: (i, j),
+                            to: (ni, nj),
+                            multiplier: m,
+                        });
+                    }
+                }
+            }
+        }
+        links
+    }
+
+    /// Recomputes [`Game::synergy`] from the current placement.
+    fn synergy_update(&mut self) {
+        let mut synergy = self
+            .board
+            .ally_grid
+            .iter()
+            .map(|row| vec![1.0; row.len()])
+            .collect::<Vec<_>>();
+        for link in self.synergy_links() {
+            let ((i, j), (ni, nj)) = (link.from, link.to);
+            synergy[i][j] *= link.multiplier;
+            synergy[ni][nj] *= link.multiplier;
+        }
+        self.synergy = synergy;
+    }
+
+    /// The synergy multiplier of the ally at `pos` this frame.
+    fn synergy_at(&self, (i, j): (usize, usize)) -> f32 {
+        self.synergy
+            .get(i)
+            .and_then(|row| row.get(j))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    fn ally_ready2attack(&mut self, pos: (usize, usize)) {
+        self.ally_damage(pos);
+    }
+
+    /// Attacks the enemy in range of the ally at grid cell `_pos` that its [`TargetingMode`]
+    /// picks. An instant hit that kills its target carries the damage it had left over on to the
+    /// next target in range, up to [`MAX_KILL_FOLLOW_UPS`] times, so the ally doesn't idle a whole
+    /// cooldown with enemies left to hit.
+    fn ally_damage(&mut self, _pos: (usize, usize)) {
+        let (i, j) = _pos;
+        let ally_position = (j as f32 + 1.0, i as f32 + 1.0);
+
+        let mut ally_range = 1;
+        let mut ally_atk = 0;
+        let mut first_element = AllyElement::Basic;
+        let mut second_element = None;
+        let targeting: TargetingMode;
+        let splash: Option<(AoeCenter, usize)>;
+
+        if let Some(ally) = self.board.ally_grid[i][j].as_ref() {
+            ally_range = ally.range;
+            ally_atk = ally.atk;
+            first_element = ally.element.clone();
+            second_element = ally.second_element.clone();
+            targeting = ally.targeting;
+            splash = ally
+                .has_element(AllyElement::Aoe)
+                .then_some((ally.aoe_center, ally.aoe_range));
+        } else {
+            return;
+        }
+
+        // Prepare damage value (with critical hit if applicable)
+        let mut damage = ally_atk;
+        if first_element == AllyElement::Critical || second_element == Some(AllyElement::Critical) {
+            damage = (damage as f32 * 2.0) as usize;
+        }
+        damage = (damage as f32 * self.synergy_at(_pos)) as usize;
+
+        let elements = [Some(first_element), second_element];
+        for _ in 0..=MAX_KILL_FOLLOW_UPS {
+            let Some(enemy_idx) = self.target_in_range(
+                ally_position,
+                ally_range,
+                targeting,
+                damage,
+                elements,
+                splash,
+            ) else {
+                break;
+            };
+            let enemy = &self.board.enemies[enemy_idx];
+            let hp = enemy.hp;
+            let full_hit =
+                Game::hit_damage(enemy, damage, self.effectiveness_against(elements, enemy));
+            self.fire(_pos, enemy_idx, damage);
+            // Projectiles only land later, so there is no telling yet whether they kill
+            if self.projectile_speed().is_some()
+                || self.board.enemies[enemy_idx].hp > 0
+                || full_hit <= hp
+            {
+                break;
+            }
+            damage = damage * (full_hit - hp) / full_hit;
+            if damage == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Sends the attack of the ally at `_pos` at enemy `enemy_idx`. It lands right away, or once
+    /// it has flown there if the config sets a `projectile_speed`.
+    fn fire(&mut self, _pos: (usize, usize), enemy_idx: usize, damage: usize) {
+        let enemy = &self.board.enemies[enemy_idx];
+        let target = Game::enemy_grid_cell(enemy);
+        self.events.push(GameEvent::Attack { ally: _pos, target });
+        match self.projectile_speed() {
+            Some(speed) => {
+                let flight = (Game::distance_from_ally(_pos, enemy) / speed * 60.0).ceil() as usize;
+                self.pending_hits.push(PendingHit {
+                    target_id: enemy.id,
+                    ally: _pos,
+                    damage,
+                    land_tick: self.ticks_elapsed + flight.max(1),
+                });
+            }
+            None => self.strike(_pos, enemy_idx, damage),
+        }
+    }
+
+    /// Lands the pending hits that arrive this tick. A hit misses if its target died or got
+    /// out of the range of the ally that fired it, or if that ally is no longer on the board.
+    fn pending_hits_update(&mut self) {
+        let tick = self.ticks_elapsed;
+        let (landing, flying): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_hits)
+            .into_iter()
+            .partition(|hit| hit.land_tick <= tick);
+        self.pending_hits = flying;
+        for hit in landing {
+            let (i, j) = hit.ally;
+            let Some(range) = self.board.ally_grid[i][j].as_ref().map(|a| a.range) else {
+                continue;
+            };
+            let Some(enemy_idx) = self
+                .board
+                .enemies
+                .iter()
+                .position(|e| e.id == hit.target_id && e.hp > 0)
+            else {
+                continue;
+            };
+            if Game::distance_from_ally(hit.ally, &self.board.enemies[enemy_idx]) > range as f32 {
+                continue;
+            }
+            self.strike(hit.ally, enemy_idx, hit.damage);
+        }
+    }
+
+    /// Drops the attacks still flying from grid cell `pos`, once the ally that fired them has left
+    /// it or been merged, so they don't land for whichever ally is there now.
+    fn drop_pending_hits(&mut self, pos: (usize, usize)) {
+        self.pending_hits.retain(|hit| hit.ally != pos);
+    }
+
+    /// Cells per second projectiles fly at, `None` if attacks land instantly.
+    fn projectile_speed(&self) -> Option<f32> {
+        self.config
+            .as_ref()
+            .and_then(|config| config.projectile_speed)
+            .filter(|speed| speed.is_finite() && *speed > 0.0)
+    }
+
+    /// Whether board position `target` lies within the cone of `degrees` opening from the ally at
+    /// `pos` towards `facing`.
+    fn is_in_cone(
+        pos: (usize, usize),
+        facing: Direction,
+        degrees: f32,
+        target: (f32, f32),
+    ) -> bool {
+        let dx = target.0 - (pos.1 as f32 + 1.0);
+        let dy = target.1 - (pos.0 as f32 + 1.0);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return true;
+        }
+        let (fx, fy) = facing.vector();
+        let cos = (dx * fx + dy * fy) / length;
+        cos >= (degrees.clamp(0.0, 360.0) / 2.0).to_radians().cos()
+    }
+
+    /// Distance between the ally at grid position `pos` and `enemy`, in cells.
+    fn distance_from_ally(pos: (usize, usize), enemy: &Enemy) -> f32 {
+        let (x, y) = Game::enemy_grid_position(enemy);
+        let dx = x - (pos.1 as f32 + 1.0);
+        let dy = y - (pos.0 as f32 + 1.0);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    /// Hits enemy `enemy_idx` with the attack of the ally at `_pos`, splashing around it if the
+    /// ally is an Aoe one and bouncing on to other enemies if it's a Chain one.
+    fn strike(&mut self, _pos: (usize, usize), enemy_idx: usize, damage: usize) {
+        let (i, j) = _pos;
+        let Some(ally) = self.board.ally_grid[i][j].as_ref() else {
+            return;
+        };
+        let is_aoe = ally.has_element(AllyElement::Aoe);
+        let is_chain = ally.has_element(AllyElement::Chain);
+        if is_aoe {
+            self.ally_AOE_strike(_pos, enemy_idx, damage);
+        } else {
+            self.ally_single_strike(_pos, enemy_idx, damage);
+        }
+        if is_chain {
+            self.ally_chain_bounces(_pos, enemy_idx, damage);
+        }
+    }
+
+    /// Bounces a Chain attack on from enemy `enemy_idx`, hit for `damage`: each bounce hits the
+    /// nearest enemy not hit yet within the bounce radius, for `decay` times the previous hit.
+    fn ally_chain_bounces(&mut self, _pos: (usize, usize), enemy_idx: usize, damage: usize) {
+        let chain = self
+            .config
+            .as_ref()
+            .map(|config| config.chain.clone())
+            .unwrap_or_default();
+        let mut hit = vec![self.board.enemies[enemy_idx].id];
+        let mut current = enemy_idx;
+        let mut damage = damage as f32;
+        for _ in 0..chain.bounces {
+            damage *= chain.decay.clamp(0.0, 1.0);
+            if damage < 1.0 {
+                break;
+            }
+            let (x, y) = Game::enemy_grid_position(&self.board.enemies[current]);
+            let next = self
+                .board
+                .enemies
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| e.hp > 0 && !hit.contains(&e.id))
+                .map(|(idx, e)| {
+                    let (ex, ey) = Game::enemy_grid_position(e);
+                    (idx, ((ex - x).powi(2) + (ey - y).powi(2)).sqrt())
+                })
+                .filter(|&(_, dist)| dist <= chain.radius)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let Some((next, _)) = next else {
+                break;
+            };
+            self.events.push(GameEvent::Bounce {
+                from: Game::enemy_grid_cell(&self.board.enemies[current]),
+                to: Game::enemy_grid_cell(&self.board.enemies[next]),
+            });
+            self.ally_single_strike(_pos, next, damage as usize);
+            hit.push(self.board.enemies[next].id);
+            current = next;
+        }
+    }
+
+    fn ally_single_strike(&mut self, _pos: (usize, usize), enemy_idx: usize, damage: usize) {
+        let (i, j) = _pos;
+        let (first_element, second_element, stun, shred, name) =
+            match self.board.ally_grid[i][j].as_ref() {
+                Some(ally) => (
+                    ally.element,
+                    ally.second_element,
+                    (ally.stun_chance, ally.stun_duration),
+                    ally.special_value.round() as usize,
+                    ally.name(),
+                ),
+                None => return,
+            };
+
+        let effectiveness = self.effectiveness_against(
+            [Some(first_element), second_element],
+            &self.board.enemies[enemy_idx],
+        );
+        let enemy = &mut self.board.enemies[enemy_idx];
+
+        Game::apply_debuffs(
+            self.config.as_ref(),
+            enemy,
+            [Some(first_element), second_element],
+            shred,
+        );
+
+        Game::try_stun(&mut self.rng, enemy, stun);
+
+        // Apply direct damage, with critical hit if applicable
+        let dealt = Game::apply_damage(enemy, damage, effectiveness);
+        let killed = dealt > 0 && enemy.hp == 0;
+        self.events.push(GameEvent::Hit {
+            cell: Game::enemy_grid_cell(enemy),
+            damage: dealt,
+            effectiveness,
+        });
+        let reflected = Game::reflected_damage(enemy, dealt);
+        if killed {
+            self.events.push(GameEvent::Killed {
+                ally: Some(name),
+                kind: enemy.kind,
+            });
+        }
+        self.credit_ally(_pos, dealt, killed as usize);
+        self.reflect_onto(_pos, reflected);
+    }
+
+    fn ally_AOE_strike(&mut self, _pos: (usize, usize), enemy_idx: usize, damage: usize) {
+        let (i, j) = _pos;
+        let (first_element, second_element, stun, shred, facing, name) =
+            match self.board.ally_grid[i][j].as_ref() {
+                Some(ally) => (
+                    ally.element,
+                    ally.second_element,
+                    (ally.stun_chance, ally.stun_duration),
+                    ally.special_value.round() as usize,
+                    ally.facing,
+                    ally.name(),
+                ),
+                None => return,
+            };
+        let cone_degrees = self
+            .config
+            .as_ref()
+            .and_then(|config| config.aoe_cone_degrees)
+            .unwrap_or(DEFAULT_AOE_CONE_DEGREES);
+
+        let enemy_pos = {
+            let enemy = &self.board.enemies[enemy_idx];
+            Game::enemy_grid_position(enemy)
+        };
+
+        // For all enemies within aoe_range of the target enemy, apply damage and debuffs
+        let aoe_range = if let Some(ally) = self.board.ally_grid[i][j].as_ref() {
+            ally.aoe_range
+        } else {
+            0
+        };
+
+        let elements = [Some(first_element), second_element];
+        let matchups = self
+            .board
+            .enemies
+            .iter()
+            .map(|enemy| self.effectiveness_against(elements, enemy))
+            .collect::<Vec<_>>();
+
+        let mut total_dealt = 0;
+        let mut kills = 0;
+        let mut reflected = 0;
+        for (idx, enemy) in self.board.enemies.iter_mut().enumerate() {
+            let pos = Game::enemy_grid_position(enemy);
+            let dx = enemy_pos.0 - pos.0;
+            let dy = enemy_pos.1 - pos.1;
+            let dist = (dx * dx + dy * dy).sqrt();
+            // The target itself is always hit, the splash only reaches into the facing cone
+            let in_cone = idx == enemy_idx
+                || facing.is_none_or(|facing| Game::is_in_cone(_pos, facing, cone_degrees, pos));
+            if dist <= aoe_range as f32 && in_cone {
+                Game::apply_debuffs(self.config.as_ref(), enemy, elements, shred);
+
+                Game::try_stun(&mut self.rng, enemy, stun);
+
+                // Apply damage
+                let effectiveness = matchups[idx];
+                let dealt = Game::apply_damage(enemy, damage, effectiveness);
+                self.events.push(GameEvent::Hit {
+                    cell: Game::enemy_grid_cell(enemy),
+                    damage: dealt,
+                    effectiveness,
+                });
+                total_dealt += dealt;
+                reflected += Game::reflected_damage(enemy, dealt);
+                if dealt > 0 && enemy.hp == 0 {
+                    kills += 1;
+                    self.events.push(GameEvent::Killed {
+                        ally: Some(name),
+                        kind: enemy.kind,
+                    });
+                }
+            }
+        }
+        self.credit_ally(_pos, total_dealt, kills);
+        self.reflect_onto(_pos, reflected);
+    }
+
+    /// Index of the enemy an ally at board position `ally_position` attacks: the priority target
+    /// if it's within `range`, else the one its `targeting` picks among those within it. `damage`
+    /// and `elements` describe its hit, for [`TargetingMode::SmartFinish`] to tell what it can
+    /// kill. `splash` is the [`AoeCenter`] and `aoe_range` of an Aoe ally, whose center other
+    /// than [`AoeCenter::Nearest`] takes over from its `targeting`.
+    fn target_in_range(
+        &self,
+        ally_position: (f32, f32),
+        range: usize,
+        targeting: TargetingMode,
+        damage: usize,
+        elements: [Option<AllyElement>; 2],
+        splash: Option<(AoeCenter, usize)>,
+    ) -> Option<usize> {
+        let in_range = self
+            .board
+            .enemies
+            .iter()
+            .enumerate()
+            // Enemies killed earlier in the tick are only cleared away at its end
+            .filter(|(_, enemy)| enemy.hp > 0)
+            .filter_map(|(idx, enemy)| {
+                let enemy_pos = Game::enemy_grid_position(enemy);
+                let dx = ally_position.0 - enemy_pos.0;
+                let dy = ally_position.1 - enemy_pos.1;
+                let dist = (dx * dx + dy * dy).sqrt();
+                if dist <= range as f32 {
+                    Some((idx, dist))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let priority = in_range
+            .iter()
+            .find(|&&(idx, _)| Some(self.board.enemies[idx].id) == self.priority_target);
+        let finishable = || {
+            in_range
+                .iter()
+                .filter(|&&(idx, _)| {
+                    let enemy = &self.board.enemies[idx];
+                    let effectiveness = self.effectiveness_against(elements, enemy);
+                    Game::hit_damage(enemy, damage, effectiveness) >= enemy.hp
+                })
+                .max_by(|a, b| {
+                    let hp = |idx: usize| self.board.enemies[idx].hp;
+                    hp(a.0).cmp(&hp(b.0)).then(b.1.partial_cmp(&a.1).unwrap())
+                })
+        };
+        let densest = |aoe_range: usize| {
+            let splashed = |idx: usize| {
+                let (x, y) = Game::enemy_grid_position(&self.board.enemies[idx]);
+                self.board
+                    .enemies
+                    .iter()
+                    .filter(|e| {
+                        let (ex, ey) = Game::enemy_grid_position(e);
+                        ((ex - x).powi(2) + (ey - y).powi(2)).sqrt() <= aoe_range as f32
+                    })
+                    .count()
+            };
+            in_range
+                .iter()
+                .max_by(|a, b| splashed(a.0).cmp(&splashed(b.0)).then(b.1.total_cmp(&a.1)))
+        };
+        let chosen = match (splash, targeting) {
+            (Some((AoeCenter::Furthest, _)), _) => {
+                in_range.iter().max_by(|a, b| a.1.total_cmp(&b.1))
+            }
+            (Some((AoeCenter::Densest, aoe_range)), _) => densest(aoe_range),
+            (_, TargetingMode::Closest) => None,
+            (_, TargetingMode::SmartFinish) => finishable(),
+        };
+        priority
+            .or(chosen)
+            .or_else(|| {
+                in_range
+                    .iter()
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            })
+            .map(|&(idx, _)| idx)
+    }
+
+    /// Switches the ally under the cursor to its next [`TargetingMode`].
+    pub fn cycle_targeting(&mut self) {
+        let (y, x) = self.cursor;
+        if let Some(ally) = self.board.ally_grid[y][x].as_mut() {
+            ally.targeting = ally.targeting.next();
+            info!(targeting = ally.targeting.name(), "targeting changed");
+        }
+    }
+
+    /// Turns the selected Aoe ally, or the one under the cursor, clockwise to face the next
+    /// direction. After facing left it splashes all around again.
+    pub fn rotate_ally(&mut self) {
+        let (y, x) = self.selected.unwrap_or(self.cursor);
+        let Some(ally) = self.board.ally_grid[y][x].as_mut() else {
+            return;
+        };
+        if !ally.has_element(AllyElement::Aoe) {
+            info!("only Aoe allies can be rotated");
+            return;
+        }
+        ally.facing = match ally.facing {
+            None => Some(Direction::Up),
+            Some(Direction::Up) => Some(Direction::Right),
+            Some(Direction::Right) => Some(Direction::Down),
+            Some(Direction::Down) => Some(Direction::Left),
+            Some(Direction::Left) => None,
+        };
+        info!(facing = ?ally.facing, "ally rotated");
+    }
+
+    /// Orders every ally to focus the enemy nearest to the cursor. Giving the order again on the
+    /// same enemy, or with no enemy on the board, clears it.
+    pub fn focus_fire(&mut self) {
+        let nearest = self.enemy_nearest_cursor().map(|enemy| enemy.id);
+        self.priority_target = match nearest {
+            Some(id) if self.priority_target != Some(id) => {
+                info!(id, "focus fire");
+                Some(id)
+            }
+            _ => {
+                info!("focus fire cleared");
+                None
+            }
+        };
+    }
+
+    fn enemy_nearest_cursor(&self) -> Option<&Enemy> {
+        let cursor_position = (self.cursor.1 as f32 + 1.0, self.cursor.0 as f32 + 1.0);
+        self.board
+            .enemies
+            .iter()
+            .map(|enemy| {
+                let enemy_pos = Game::enemy_grid_position(enemy);
+                let dx = cursor_position.0 - enemy_pos.0;
+                let dy = cursor_position.1 - enemy_pos.1;
+                (enemy, dx * dx + dy * dy)
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(enemy, _)| enemy)
+    }
+
+    /// The ally in board cell `cell`, `None` for empty ally cells and cells of the path.
+    pub fn ally_in_cell(&self, (row, col): (usize, usize)) -> Option<&Ally> {
+        let (i, j) = (row.checked_sub(1)?, col.checked_sub(1)?);
+        self.board.ally_grid.get(i)?.get(j)?.as_ref()
+    }
+
+    /// Enemies standing in board cell `cell`.
+    pub fn enemies_in_cell(&self, cell: (usize, usize)) -> impl Iterator<Item = &Enemy> {
+        self.board
+            .enemies
+            .iter()
+            .filter(move |enemy| Game::enemy_grid_cell(enemy) == cell)
+    }
+
+    /// Multiplier of an ally with `elements` against `enemy`, matchup and armor type included,
+    /// `1.0` without a config.
+    fn effectiveness_against(&self, elements: [Option<AllyElement>; 2], enemy: &Enemy) -> f32 {
+        self.config
+            .as_ref()
+            .map_or(1.0, |config| config.hit_multiplier(elements, enemy))
+    }
+
+    /// Deals a direct hit of `damage` to `enemy`, scaled by the matchup's `effectiveness` and
+    /// then reduced by its [`Enemy::effective_armor`]. Every ally attack goes through here;
+    /// damage over time bypasses both.
+    ///
+    /// Returns the HP actually taken off the enemy.
+    fn apply_damage(enemy: &mut Enemy, damage: usize, effectiveness: f32) -> usize {
+        let damage = Game::hit_damage(enemy, damage, effectiveness).min(enemy.hp);
+        enemy.hp -= damage;
+        damage
+    }
+
+    /// What a hit of `damage` at `effectiveness` would take off `enemy` through its armor,
+    /// overkill included.
+    fn hit_damage(enemy: &Enemy, damage: usize, effectiveness: f32) -> usize {
+        ((damage as f32 * effectiveness) as usize).saturating_sub(enemy.effective_armor())
+    }
+
+    /// Damage `enemy` sends back for a hit that took `dealt` HP off it.
+    fn reflected_damage(enemy: &Enemy, dealt: usize) -> usize {
+        (dealt as f32 * enemy.reflect.clamp(0.0, 1.0)).round() as usize
+    }
+
+    /// Takes `damage` sent back by reflectors off the ally at grid position `pos`, destroying
+    /// it at 0 HP. Its attacks still in flight miss, and undoing can't bring it back.
+    fn reflect_onto(&mut self, pos: (usize, usize), damage: usize) {
+        if damage == 0 {
+            return;
+        }
+        let Some(ally) = self.board.ally_grid[pos.0][pos.1].as_mut() else {
+            return;
+        };
+        ally.hp = ally.hp.saturating_sub(damage);
+        if ally.hp > 0 {
+            return;
+        }
+        let Some(destroyed) = self.board.ally_grid[pos.0][pos.1].take() else {
+            return;
+        };
+        let ally = destroyed.name();
+        self.remember_departed(destroyed);
+        self.drop_pending_hits(pos);
+        if self.selected == Some(pos) {
+            self.selected = None;
+        }
+        self.last_merge_undo = None;
+        self.last_move = None;
+        info!(?pos, ally, "ally destroyed by reflected damage");
+        self.events
+            .push(GameEvent::AllyDestroyed { cell: pos, ally });
+    }
+
+    /// Keeps `ally`, which is leaving the board, as [`Game::departed_mvp`] if it dealt more than
+    /// the one kept so far.
+    fn remember_departed(&mut self, ally: Ally) {
+        let record = |ally: &Ally| (ally.stats.damage_dealt, ally.stats.kills);
+        if self
+            .departed_mvp
+            .as_ref()
+            .is_none_or(|best| record(&ally) > record(best))
+        {
+            self.departed_mvp = Some(ally);
+        }
+    }
+
+    /// Attributes a hit to the ally at grid position `pos`.
+    fn credit_ally(&mut self, pos: (usize, usize), damage: usize, kills: usize) {
+        if let Some(ally) = self.board.ally_grid[pos.0][pos.1].as_mut() {
+            ally.idle_ticks = 0;
+            ally.stats.damage_dealt += damage;
+            ally.stats.kills += kills;
+            for element in [Some(ally.element), ally.second_element]
+                .into_iter()
+                .flatten()
+            {
+                let stats = self.element_stats.entry(element).or_default();
+                stats.damage_dealt += damage;
+                stats.kills += kills;
+            }
+        }
+    }
+
+    /// The ally that dealt the most damage this run, if any dealt damage at all. Allies sold or
+    /// destroyed along the way count with what they had dealt by then.
+    pub fn mvp(&self) -> Option<&Ally> {
+        self.board
+            .ally_grid
+            .iter()
+            .flatten()
+            .flatten()
+            .chain(self.departed_mvp.as_ref())
+            .filter(|ally| ally.stats.damage_dealt > 0)
+            .max_by_key(|ally| (ally.stats.damage_dealt, ally.stats.kills))
+    }
+
+    /// Applies the debuffs of an ally with `elements` to a hit enemy, as set for each element in
+    /// `config`. Dot hits also shred `shred` armor for as long as their damage over time lasts.
+    fn apply_debuffs(
+        config: Option<&ConfigFile>,
+        enemy: &mut Enemy,
+        elements: [Option<AllyElement>; 2],
+        shred: usize,
+    ) {
+        for element in elements.into_iter().flatten() {
+            let ally_config = config
+                .map(|config| config.ally_config(element))
+                .unwrap_or_default();
+            match element {
+                AllyElement::Slow => {
+                    enemy.slow_list.push(Debuff {
+                        value: ally_config.slow_value.unwrap_or(1),
+                        cooldown: ally_config.slow_duration.unwrap_or(1.0),
+                    });
+                }
+                AllyElement::Dot => {
+                    let cooldown = ally_config.dot_duration.unwrap_or(2.0);
+                    enemy.dot_list.push(Debuff {
+                        value: ally_config.dot_value.unwrap_or(2),
+                        cooldown,
+                    });
+                    // Dot hits also corrode armor
+                    enemy.armor_shred_list.push(Debuff {
+                        value: shred,
+                        cooldown,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Rolls an ally's `(stun_chance, stun_duration)` against a hit enemy.
+    fn try_stun(rng: &mut ChaCha8Rng, enemy: &mut Enemy, (chance, duration): (f32, f32)) {
+        if duration > 0.0 && chance > 0.0 && rng.random::<f32>() < chance {
+            enemy.stun_list.push(Debuff {
+                value: 1,
+                cooldown: duration,
+            });
+        }
+    }
+
+    fn enemy_update(&mut self) {
+        // Update spawn timers and spawn enemies if ready
+        for (_, timer) in &mut self.board.enemy_ready2spawn {
+            *timer = timer.saturating_sub(1);
+        }
+        // Spawn enemies whose timers reached 0, in id order, so enemies spawning on the same tick
+        // line up the same way whatever order they were queued in
+        let (mut spawned, waiting): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.board.enemy_ready2spawn)
+                .into_iter()
+                .partition(|(_, timer)| *timer == 0);
+        self.board.enemy_ready2spawn = waiting;
+        spawned.sort_by_key(|(enemy, _)| enemy.id);
+        // Past the enemy cap, the latest of them wait or are dropped
+        if let Some((cap, overflow)) = self
+            .config
+            .as_ref()
+            .and_then(|config| Some((config.max_enemies?, config.enemy_overflow)))
+        {
+            let room = cap.saturating_sub(self.board.enemies.len());
+            if spawned.len() > room {
+                let over = spawned.split_off(room);
+                match overflow {
+                    EnemyOverflow::Delay => self
+                        .board
+                        .enemy_ready2spawn
+                        .extend(over.into_iter().map(|(enemy, _)| (enemy, 1))),
+                    EnemyOverflow::Drop => {
+                        info!(
+                            dropped = over.len(),
+                            cap, "enemy cap reached, spawns dropped"
+                        )
+                    }
+                }
+            }
+        }
+        for (enemy, _) in spawned {
+            self.events
+                .push(GameEvent::EnemySpawned { kind: enemy.kind });
+            self.board.enemies.push(enemy);
+        }
+
+        let slow = self
+            .config
+            .as_ref()
+            .map(|config| config.slow.clone())
+            .unwrap_or_default();
+        let frenzy = self.frenzy_multiplier();
+        let speed_unit = self.speed_unit();
+        // Update all enemies
+        for enemy in self.board.enemies.iter_mut() {
+            // Apply DOT debuffs
+            let mut dot_damage = 0;
+            enemy.dot_list.retain_mut(|debuff| {
+                if debuff.cooldown > 0.0 {
+                    dot_damage += debuff.value;
+                    debuff.cooldown -= 1.0 / 60.0;
+                    debuff.cooldown > 0.0
+                } else {
+                    false
+                }
+            });
+            if dot_damage > 0 && enemy.hp > 0 {
+                enemy.hp = enemy.hp.saturating_sub(dot_damage);
+                if enemy.hp == 0 {
+                    self.events.push(GameEvent::Killed {
+                        ally: None,
+                        kind: enemy.kind,
+                    });
+                }
+            }
+
+            // Regenerate, carrying fractions of a point over to the next tick
+            if enemy.is_regenerating() {
+                enemy.regen_progress += enemy.hp_regen / 60.0;
+                let healed = enemy.regen_progress.floor();
+                enemy.regen_progress -= healed;
+                enemy.hp = (enemy.hp + healed as usize).min(enemy.max_hp);
+            } else {
+                enemy.regen_progress = 0.0;
+            }
+
+            // Apply slow debuffs
+            let mut slow_values = Vec::new();
+            enemy.slow_list.retain_mut(|debuff| {
+                if debuff.cooldown > 0.0 {
+                    slow_values.push(debuff.value);
+                    debuff.cooldown -= 1.0 / 60.0;
+                    debuff.cooldown > 0.0
+                } else {
+                    false
+                }
+            });
+            let slow_factor = slow.factor(slow_values);
+
+            // Apply stuns, which hold the enemy in place regardless of slows
+            let stunned = enemy.is_stunned();
+            enemy.stun_list.retain_mut(|debuff| {
+                debuff.cooldown -= 1.0 / 60.0;
+                debuff.cooldown > 0.0
+            });
+
+            // Expire armor shreds
+            enemy.armor_shred_list.retain_mut(|debuff| {
+                debuff.cooldown -= 1.0 / 60.0;
+                debuff.cooldown > 0.0
+            });
+
+            // Move enemy. Frenzy scales slowed speed too, wearing down heavy slows, but not stuns
+            let speed = speed_unit.cells_per_second(enemy.move_speed, enemy.path_length());
+            let mut move_amount = speed * slow_factor * frenzy * (1.0 / 60.0);
+            if stunned {
+                move_amount = 0.0;
+            }
+            enemy.position += move_amount;
+        }
+
+        // Remove dead enemies and pay out their bounties, scaled by the coins already held
+        let (dead_count, bounty) = self
+            .board
+            .enemies
+            .iter()
+            .filter(|enemy| enemy.hp == 0)
+            .fold((0, 0), |(count, bounty), enemy| {
+                (count + 1, bounty + enemy.bounty)
+            });
+        let bounty = (bounty as f32 * self.reward_multiplier()).round() as usize;
+        self.coin += bounty;
+        self.coins_earned += bounty;
+        self.board.enemies.retain(|enemy| enemy.hp > 0);
+        self.lifesteal(dead_count);
+
+        // Enemies that walked the whole path escape and cost the lives of their kind's leak cost
+        let default = LeakCostConfig::default();
+        let leak_cost = self
+            .config
+            .as_ref()
+            .map_or(&default, |config| &config.leak_cost);
+        let (leaked, cost) = self
+            .board
+            .enemies
+            .iter()
+            .filter(|enemy| enemy.position >= enemy.path_length())
+            .fold((0, 0), |(count, cost), enemy| {
+                (count + 1, cost + leak_cost.of(enemy.kind))
+            });
+        if leaked > 0 {
+            let lost = cost.min(self.lives);
+            self.lives_lost_this_wave += lost;
+            self.lives -= lost;
+            info!(leaked, lost, lives = self.lives, "enemy reached the exit");
+            self.events.push(GameEvent::LifeLost {
+                lost,
+                remaining: self.lives,
+            });
+            self.board
+                .enemies
+                .retain(|enemy| enemy.position < enemy.path_length());
+        }
+
+        // The focus fire order ends with its target
+        if let Some(id) = self.priority_target
+            && !self.board.enemies.iter().any(|enemy| enemy.id == id)
+        {
+            self.priority_target = None;
+        }
+    }
+
+    /// Counts `new_kills` and restores a life for every `lifesteal_per_kill` kills.
+    fn lifesteal(&mut self, new_kills: usize) {
+        let before = self.kills;
+        self.kills += new_kills;
+        let per_kill = self
+            .config
+            .as_ref()
+            .map_or(0, |config| config.lifesteal_per_kill);
+        if per_kill == 0 {
+            return;
+        }
+        let restored = (self.kills / per_kill - before / per_kill)
+            .min(STARTING_LIVES.saturating_sub(self.lives));
+        if restored > 0 {
+            self.lives += restored;
+            info!(restored, lives = self.lives, "lifesteal restored lives");
+        }
+    }
+
+    pub fn enemy_counts(&self) -> EnemyCounts {
+        let incoming = self.board.enemy_ready2spawn.iter().map(|(enemy, _)| enemy);
+        let mut by_kind = [0; EnemyKind::ALL.len()];
+        for enemy in self.board.enemies.iter().chain(incoming) {
+            let kind = EnemyKind::ALL.iter().position(|&k| k == enemy.kind);
+            by_kind[kind.unwrap_or_default()] += 1;
+        }
+        EnemyCounts {
+            on_field: self.board.enemies.len(),
+            incoming: self.board.enemy_ready2spawn.len(),
+            by_kind,
+        }
+    }
+
+    /// `(position, ticks until spawn)` of enemies spawning within [`SPAWN_TELEGRAPH_TICKS`].
+    pub fn pending_spawns(&self) -> Vec<(f32, usize)> {
+        self.board
+            .enemy_ready2spawn
+            .iter()
+            .filter(|(_, timer)| *timer <= SPAWN_TELEGRAPH_TICKS)
+            .map(|(enemy, timer)| (enemy.position, *timer))
+            .collect()
+    }
+
+    fn is_wave_cleared(&self) -> bool {
+        self.board.enemy_ready2spawn.is_empty() && self.board.enemies.is_empty()
+    }
+
+    // Endless runs never end in a win, sandbox runs never end at all
+    fn state_checkwin(&self) -> bool {
+        !matches!(self.mode, GameMode::Endless | GameMode::Sandbox) && self.is_wave_cleared()
+    }
+
+    fn state_checklose(&self) -> bool {
+        self.mode != GameMode::Sandbox && self.lives == 0
+    }
+
+    pub fn state_pause(&mut self) {
+        if matches!(self.game_state, GameState::Running) {
+            self.game_state = GameState::Pause;
+        }
+    }
+
+    pub fn state_resume(&mut self) {
+        if matches!(self.game_state, GameState::Pause) {
+            self.game_state = GameState::Running;
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        match self.game_state {
+            GameState::Running => self.state_pause(),
+            GameState::Pause => self.state_resume(),
+            _ => {}
+        }
+    }
+
+    /// Spends [`ALLY_COST`] on a random ally in an empty cell. Returns whether one was bought;
+    /// nothing is spent when the board is full or coins are short.
+    pub fn buy_ally(&mut self) -> bool {
+        self.buy(ALLY_COST, None)
+    }
+
+    /// Buys up to `count` allies, stopping at the first one that can't be bought. Returns how
+    /// many were.
+    pub fn buy_allies(&mut self, count: usize) -> usize {
+        (0..count).take_while(|_| self.buy_ally()).count()
+    }
+
+    /// Spends [`Game::chosen_ally_cost`] on an ally of `element` in an empty cell, like
+    /// [`Game::buy_ally`].
+    pub fn buy_ally_of(&mut self, element: AllyElement) -> bool {
+        self.buy(self.chosen_ally_cost(), Some(element))
+    }
+
+    /// Buys up to `count` allies of `element`, like [`Game::buy_allies`].
+    pub fn buy_allies_of(&mut self, element: AllyElement, count: usize) -> usize {
+        (0..count).take_while(|_| self.buy_ally_of(element)).count()
+    }
+
+    /// Spends [`Game::chosen_ally_cost`] on an ally of `element` in grid cell `pos`. Returns
+    /// whether it was built; nothing is spent if the cell is taken or coins are short.
+    pub fn build_ally_at(&mut self, pos: (usize, usize), element: AllyElement) -> bool {
+        if self.board.ally_grid[pos.0][pos.1].is_some() {
+            info!(?pos, "cell taken!");
+            return false;
+        }
+        let cost = self.chosen_ally_cost();
+        if self.coin < cost {
+            info!(required = cost, current = self.coin, "coin not enough!");
+            return false;
+        }
+        self.last_merge_undo = None;
+        self.last_move = None;
+        self.coin -= cost;
+        self.board.ally_grid[pos.0][pos.1] = Some(self.new_ally(element));
+        info!(?pos, ?element, "built ally");
+        true
+    }
+
+    /// How bought allies get their element, see [`AllySelectionMode`].
+    pub fn ally_selection_mode(&self) -> AllySelectionMode {
+        self.config
+            .as_ref()
+            .map_or_else(AllySelectionMode::default, |config| {
+                config.ally_selection_mode
+            })
+    }
+
+    /// HP new allies are placed with.
+    pub fn ally_hp(&self) -> usize {
+        self.config
+            .as_ref()
+            .and_then(|config| config.ally_hp)
+            .unwrap_or(DEFAULT_ALLY_HP)
+    }
+
+    /// Share of a direct hit's damage reflectors send back.
+    pub fn reflect_fraction(&self) -> f32 {
+        self.config
+            .as_ref()
+            .and_then(|config| config.reflect_fraction)
+            .unwrap_or(DEFAULT_REFLECT_FRACTION)
+    }
+
+    /// Coins an ally of the player's choosing costs.
+    pub fn chosen_ally_cost(&self) -> usize {
+        self.config
+            .as_ref()
+            .and_then(|config| config.chosen_ally_cost)
+            .unwrap_or(DEFAULT_CHOSEN_ALLY_COST)
+    }
+
+    /// Spends `cost` on an ally of `element`, or a random one if `None`.
+    fn buy(&mut self, cost: usize, element: Option<AllyElement>) -> bool {
+        if self.board_is_full() {
+            info!("board full!");
+            return false;
+        }
+        self.last_merge_undo = None;
+        self.last_move = None;
+        if self.coin >= cost {
+            self.coin -= cost;
+            match element {
+                Some(element) => self.spawn_ally_of(element),
+                None => self.ally_spawn(),
+            }
+            true
+        } else {
+            info!(required = cost, current = self.coin, "coin not enough!");
+            false
+        }
+    }
+
+    /// Buys an ally if auto-buy is on and the purchase keeps [`Game::auto_buy_threshold`] coins.
+    /// Holds off while a merge or move can still be undone, since buying would cancel the undo.
+    fn auto_buy_update(&mut self) {
+        let can_afford = self.coin >= self.auto_buy_threshold + ALLY_COST;
+        if self.auto_buy
+            && can_afford
+            && self.last_merge_undo.is_none()
+            && self.last_move.is_none()
+            && !self.board_is_full()
+            && self.buy_ally()
+        {
+            self.events.push(GameEvent::AutoBought);
+        }
+    }
+
+    /// Whether every cell of the ally grid is taken.
+    pub fn board_is_full(&self) -> bool {
+        self.board.ally_grid.iter().flatten().all(Option::is_some)
+    }
+
+    /// Coins refunded for selling `ally`: half the price of an ally per level.
+    pub fn sell_value(ally: &Ally) -> usize {
+        ALLY_COST / 2 * ally.level
+    }
+
+    /// Scrap salvaged from selling `ally`: one per merge that went into it, so level 1 allies
+    /// yield none.
+    pub fn scrap_value(ally: &Ally) -> usize {
+        ally.level.saturating_sub(1)
+    }
+
+    /// Sells the ally at grid position `pos`, returning the refund, or `None` if the cell is
+    /// empty. Also salvages its [`Game::scrap_value`].
+    pub fn sell_ally(&mut self, pos: (usize, usize)) -> Option<usize> {
+        let ally = self.board.ally_grid[pos.0][pos.1].take()?;
+        self.drop_pending_hits(pos);
+        self.last_merge_undo = None;
+        self.last_move = None;
+        if self.selected == Some(pos) {
+            self.selected = None;
+        }
+        let refund = Game::sell_value(&ally);
+        let scrap = Game::scrap_value(&ally);
+        self.coin += refund;
+        self.scrap += scrap;
+        info!(
+            name = ally.name(),
+            level = ally.level,
+            refund,
+            scrap,
+            "sold ally"
+        );
+        self.remember_departed(ally);
+        Some(refund)
+    }
+
+    /// Allies [`Game::sell_all_of_element`] would sell for `element`.
+    pub fn allies_of_element(&self, element: AllyElement) -> impl Iterator<Item = &Ally> {
+        self.board
+            .ally_grid
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(move |a| a.element == element && a.second_element.is_none())
+    }
+
+    /// Sells every single-element ally whose element is `element`, refunding
+    /// [`Game::sell_value`] and salvaging [`Game::scrap_value`] for each. Dual-element allies are
+    /// kept, even if one of their elements matches. Returns how many allies were sold.
+    pub fn sell_all_of_element(&mut self, element: AllyElement) -> usize {
+        self.last_merge_undo = None;
+        self.last_move = None;
+        let mut sold = 0;
+        let mut refund = 0;
+        let mut scrap = 0;
+        let mut departed = Vec::new();
+        for (i, row) in self.board.ally_grid.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                let Some(ally) =
+                    cell.take_if(|a| a.element == element && a.second_element.is_none())
+                else {
+                    continue;
+                };
+                sold += 1;
+                refund += Game::sell_value(&ally);
+                scrap += Game::scrap_value(&ally);
+                if self.selected == Some((i, j)) {
+                    self.selected = None;
+                }
+                departed.push(((i, j), ally));
+            }
+        }
+        for (pos, ally) in departed {
+            self.drop_pending_hits(pos);
+            self.remember_departed(ally);
+        }
+        self.coin += refund;
+        self.scrap += scrap;
+        info!(?element, sold, refund, scrap, "sold allies");
+        sold
+    }
+
+    // Generate a level 1 ally on a random empty grid
+    fn ally_spawn(&mut self) {
+        if let Some(cell) = self.random_empty_cell() {
+            let element = *AllyElement::ALL.choose(&mut self.rng).unwrap();
+            self.place_bought_ally(cell, element);
+        }
+    }
+
+    /// Places a level 1 ally of `element` on a random empty cell, if there is one.
+    fn spawn_ally_of(&mut self, element: AllyElement) {
+        if let Some(cell) = self.random_empty_cell() {
+            self.place_bought_ally(cell, element);
+        }
+    }
+
+    fn random_empty_cell(&mut self) -> Option<(usize, usize)> {
+        let mut empty_cells = Vec::new();
+        for (i, row) in self.board.ally_grid.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if cell.is_none() {
+                    empty_cells.push((i, j));
+                }
+            }
+        }
+        empty_cells.choose(&mut self.rng).copied()
+    }
+
+    /// Puts a bought ally of `element` in grid cell `(i, j)`, or merges it into a matching ally
+    /// if [`Game::auto_merge_on_buy`] is on.
+    fn place_bought_ally(&mut self, (i, j): (usize, usize), element: AllyElement) {
+        let ally = self.new_ally(element);
+        // The cell and element are rolled either way, so the setting doesn't shift the rolls
+        // of the rest of the run
+        if self.auto_merge_on_buy
+            && let Some((mi, mj)) = self.auto_merge_partner(&ally)
+        {
+            let partner = self.board.ally_grid[mi][mj].clone().unwrap();
+            if let Ok(merged) = self.ally_merge(partner.clone(), ally.clone()) {
+                // Undoing puts the bought ally where it would have landed
+                self.last_merge_undo = Some(MergeUndo {
+                    from: ((i, j), ally),
+                    onto: ((mi, mj), partner),
+                    time_left: MERGE_UNDO_SECONDS,
+                });
+                self.events.push(GameEvent::Merged {
+                    cell: (mi, mj),
+                    level: merged.level,
+                });
+                info!(
+                    name = merged.name(),
+                    level = merged.level,
+                    "bought ally merged"
+                );
+                self.board.ally_grid[mi][mj] = Some(merged);
+                self.drop_pending_hits((mi, mj));
+                return;
+            }
+        }
+        self.board.ally_grid[i][j] = Some(ally);
+    }
+
+    /// First ally on the board, row by row, that a freshly bought `ally` merges into: one of the
+    /// same single element and level that isn't maxed.
+    pub fn auto_merge_partner(&self, ally: &Ally) -> Option<(usize, usize)> {
+        self.board
+            .ally_grid
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| row.iter().enumerate().map(move |(j, cell)| ((i, j), cell)))
+            .find(|(_, cell)| {
+                cell.as_ref().is_some_and(|other| {
+                    other.element == ally.element
+                        && other.second_element.is_none()
+                        && ally.second_element.is_none()
+                        && other.level == ally.level
+                        && !self.is_maxed(other)
+                })
+            })
+            .map(|(pos, _)| pos)
+    }
+
+    /// A fresh ally of `element` with its configured base stats.
+    fn new_ally(&self, element: AllyElement) -> Ally {
+        // Get config (fall back to default if not loaded)
+        let config = self
+            .config
+            .as_ref()
+            .map(|c| c.clone())
+            .unwrap_or_else(|| self.load_config());
+        let ally_config = config.ally_config(element);
+
+        Ally {
+            element,
+            second_element: None,
+            atk: ally_config.atk.unwrap_or(10),
+            range: ally_config.range.unwrap_or(1),
+            aoe_range: ally_config.aoe_range.unwrap_or(0),
+            level: ally_config.level.unwrap_or(1),
+            attacks_per_second: ally_config.attacks_per_second().unwrap_or(1.0),
+            attack_cooldown: ally_config.attack_cooldown.unwrap_or(0.0),
+            levelup_ratio: ally_config.levelup_ratio.unwrap_or(1.5),
+            special_value: ally_config.special_value.unwrap_or(1.5),
+            stun_chance: ally_config.stun_chance.unwrap_or(0.0),
+            stun_duration: ally_config.stun_duration.unwrap_or(0.0),
+            stats: AllyStats::default(),
+            targeting: TargetingMode::default(),
+            idle_ticks: 0,
+            facing: None,
+            aoe_center: ally_config.aoe_center.unwrap_or_default(),
+            hp: self.ally_hp(),
+            max_hp: self.ally_hp(),
+        }
+    }
+
+    /// Places the `[[starting_ally]]` entries of the config. Entries outside the grid, on an
+    /// already taken cell or with an impossible element pair are skipped with a warning.
+    fn place_starting_allies(&mut self) {
+        let entries = self
+            .config
+            .as_ref()
+            .map(|config| config.starting_ally.clone())
+            .unwrap_or_default();
+        for entry in entries {
+            let (row, col) = (entry.row, entry.col);
+            match self.board.ally_grid.get(row).and_then(|r| r.get(col)) {
+                None => {
+                    warn!(row, col, "starting ally is outside the grid, skipped");
+                    continue;
+                }
+                Some(Some(_)) => {
+                    warn!(row, col, "starting ally cell is already taken, skipped");
+                    continue;
+                }
+                Some(None) => {}
+            }
+            let Some(ally) = self.starting_ally(&entry) else {
+                warn!(?entry, "starting ally can't be built, skipped");
+                continue;
+            };
+            self.board.ally_grid[row][col] = Some(ally);
+        }
+    }
+
+    /// Places `free_starting_allies` random allies like bought ones, without charging for them,
+    /// in the cells `[[starting_ally]]` entries left empty.
+    fn place_free_allies(&mut self) {
+        let count = self
+            .config
+            .as_ref()
+            .map_or(0, |config| config.free_starting_allies);
+        // Each gets a cell of its own, even with merge on buy
+        let auto_merge_on_buy = std::mem::take(&mut self.auto_merge_on_buy);
+        let mut placed = 0;
+        while placed < count && !self.board_is_full() {
+            self.ally_spawn();
+            placed += 1;
+        }
+        self.auto_merge_on_buy = auto_merge_on_buy;
+        if placed > 0 {
+            info!(placed, "free starting allies placed");
+        }
+    }
+
+    /// Builds a starting ally through the same merges a player would do, so its stats match.
+    fn starting_ally(&mut self, entry: &StartingAllyConfig) -> Option<Ally> {
+        let mut ally = self.new_ally(entry.element);
+        if let Some(second) = entry.second_element {
+            if second == entry.element {
+                return None;
+            }
+            ally = self.ally_merge(ally, self.new_ally(second)).ok()?;
+        }
+        while ally.level < entry.level {
+            ally = self.ally_merge(ally.clone(), ally).ok()?;
+        }
+        Some(ally)
+    }
+
+    //if drop a save level on a allay they will levelup
+    // Merge two allies at the given positions (i1, j1) and (i2, j2)
+    pub fn ally_merge(&mut self, ally1: Ally, ally2: Ally) -> Result<Ally, MergeError> {
+        let same_elements =
+            ally1.element == ally2.element && ally1.second_element == ally2.second_element;
+        if ally1.level != ally2.level {
+            return match self.merge_rule() {
+                MergeRule::Absorb if same_elements => self.ally_absorb(ally1, ally2),
+                _ => Err(MergeError::LevelMismatch),
+            };
+        }
+
+        // To compare AllyElement and Option<AllyElement>, derive PartialEq for AllyElement and Option<AllyElement>
+        // (Already derived via #[derive(Debug,Clone)] for AllyElement, but need PartialEq)
+        // Let's add PartialEq to AllyElement and Option<AllyElement> in the struct definition (not shown here).
+
+        if same_elements {
+            if self.is_maxed(&ally1) {
+                return Err(MergeError::MaxLevel);
+            }
+            // Combine both inputs symmetrically so the result doesn't depend on which ally was
+            // dropped onto which, and keep the second element of dual allies.
+            let levelup_ratio = (ally1.levelup_ratio + ally2.levelup_ratio) / 2.0;
+            let scale = |v: usize| ((v as f32) * levelup_ratio) as usize;
+            Ok(Ally {
+                element: ally1.element,
+                second_element: ally1.second_element,
+                atk: scale(std::cmp::max(ally1.atk, ally2.atk)),
+                range: scale(std::cmp::max(ally1.range, ally2.range)),
+                aoe_range: scale(std::cmp::max(ally1.aoe_range, ally2.aoe_range)),
+                level: ally1.level + 1,
+                attacks_per_second: (ally1.attacks_per_second + ally2.attacks_per_second) / 2.0
+                    * levelup_ratio,
+                attack_cooldown: 0.0,
+                levelup_ratio,
+                special_value: (ally1.special_value + ally2.special_value) / 2.0 * levelup_ratio,
+                stun_chance: (ally1.stun_chance + ally2.stun_chance) / 2.0,
+                stun_duration: (ally1.stun_duration + ally2.stun_duration) / 2.0,
+                stats: ally1.stats.combine(&ally2.stats),
+                targeting: ally1.targeting,
+                idle_ticks: 0,
+                facing: ally1.facing,
+                aoe_center: ally1.aoe_center,
+                hp: ally1.max_hp.max(ally2.max_hp),
+                max_hp: ally1.max_hp.max(ally2.max_hp),
+            })
+        } else if ally1.second_element.is_none() && ally2.second_element.is_none() {
+            // Merge two no second element allies (no upgrade)
+            let (e0, e1) = if ally1.element < ally2.element {
+                (ally1.element.clone(), Some(ally2.element.clone()))
+            } else {
+                (ally2.element.clone(), Some(ally1.element.clone()))
+            };
+            Ok(Ally {
+                element: e0,
+                second_element: e1,
+                atk: std::cmp::max(ally1.atk, ally2.atk),
+                range: std::cmp::max(ally1.range, ally2.range),
+                aoe_range: std::cmp::max(ally1.aoe_range, ally2.aoe_range),
+                level: ally1.level,
+                attacks_per_second: (ally1.attacks_per_second + ally2.attacks_per_second) / 2.0,
+                attack_cooldown: 0.0,
+                levelup_ratio: (ally1.levelup_ratio + ally2.levelup_ratio) / 2.0,
+                special_value: (ally1.special_value + ally2.special_value) / 2.0,
+                stun_chance: ally1.stun_chance.max(ally2.stun_chance),
+                stun_duration: ally1.stun_duration.max(ally2.stun_duration),
+                stats: ally1.stats.combine(&ally2.stats),
+                targeting: ally1.targeting,
+                idle_ticks: 0,
+                facing: ally1.facing,
+                // Whichever of the two splashes brings its aim along
+                aoe_center: if ally1.has_element(AllyElement::Aoe) {
+                    ally1.aoe_center
+                } else {
+                    ally2.aoe_center
+                },
+                hp: ally1.max_hp.max(ally2.max_hp),
+                max_hp: ally1.max_hp.max(ally2.max_hp),
+            })
+        } else if ally1.second_element.is_some() && ally2.second_element.is_some() {
+            Err(MergeError::IncompatibleElements)
+        } else {
+            Err(MergeError::AlreadyDualElement)
+        }
+    }
+
+    /// The higher-level of two same-element allies absorbing the other under
+    /// [`MergeRule::Absorb`]. Its stats grow by `lower level / higher level` of a level-up.
+    fn ally_absorb(&self, ally1: Ally, ally2: Ally) -> Result<Ally, MergeError> {
+        let (high, low) = if ally1.level >= ally2.level {
+            (ally1, ally2)
+        } else {
+            (ally2, ally1)
+        };
+        if self.is_maxed(&high) {
+            return Err(MergeError::MaxLevel);
+        }
+        let share = low.level as f32 / high.level as f32;
+        let ratio = 1.0 + (high.levelup_ratio - 1.0) * share;
+        let scale = |v: usize| ((v as f32) * ratio) as usize;
+        Ok(Ally {
+            atk: scale(high.atk),
+            range: scale(high.range),
+            aoe_range: scale(high.aoe_range),
+            attacks_per_second: high.attacks_per_second * ratio,
+            attack_cooldown: 0.0,
+            special_value: high.special_value * ratio,
+            stats: high.stats.combine(&low.stats),
+            idle_ticks: 0,
+            hp: high.max_hp,
+            ..high
+        })
+    }
+
+    //handle cursor movement
+    pub fn cursor_move(&mut self, direction: Direction) {
+        match direction {
+            Direction::Up => {
+                if self.cursor.0 == 0 {
+                    self.cursor.0 = 2;
+                } else {
+                    self.cursor.0 -= 1;
+                }
+            }
+            Direction::Down => {
+                if self.cursor.0 == 2 {
+                    self.cursor.0 = 0;
+                } else {
+                    self.cursor.0 += 1;
+                }
+            }
+            Direction::Left => {
+                if self.cursor.1 == 0 {
+                    self.cursor.1 = 6;
+                } else {
+                    self.cursor.1 -= 1;
+                }
+            }
+            Direction::Right => {
+                if self.cursor.1 == 6 {
+                    self.cursor.1 = 0;
+                } else {
+                    self.cursor.1 += 1;
+                }
+            }
+        }
+    }
+
+    //select a ally if there is a ally at cursor
+    pub fn cursor_select(&mut self) {
+        if self.selected.is_some() {
+            self.cursor_drop();
+            return;
+        }
+
+        let (i, j) = self.cursor;
+        if let Some(Some(_)) = self.board.ally_grid.get(i).and_then(|row| row.get(j)) {
+            self.selected = Some((i, j));
+        } else {
+            self.selected = None;
+        }
+    }
+
+    // Drop the selected ally on an empty grid or merge with an ally at the cursor
+    fn cursor_drop(&mut self) {
+        if let Some((sel_i, sel_j)) = self.selected {
+            let (cur_i, cur_j) = self.cursor;
+
+            if (sel_i, sel_j) == (cur_i, cur_j) {
+                return;
+            }
+            let ally1 = self.board.ally_grid[sel_i][sel_j].take();
+
+            if let Some(ally1) = ally1 {
+                if let Some(Some(ally2)) = self
+                    .board
+                    .ally_grid
+                    .get(cur_i)
+                    .and_then(|row| row.get(cur_j))
+                {
+                    let ally2 = ally2.clone();
+                    if let Ok(merged) = self.ally_merge(ally1.clone(), ally2.clone()) {
+                        self.place_drop_merge((sel_i, sel_j), ally1, (cur_i, cur_j), ally2, merged);
+                    } else {
+                        // Merge failed, return ally1 to its original position
+                        self.board.ally_grid[sel_i][sel_j] = Some(ally1);
+                        if !self.keep_selection_on_failed_merge {
+                            self.selected = None;
+                        }
+                    }
+                } else if let Some((onto, ally2, merged)) =
+                    self.merge_snap_partner(&ally1, (sel_i, sel_j))
+                {
+                    // Dropped next to a matching ally, merge into it instead of moving
+                    self.place_drop_merge((sel_i, sel_j), ally1, onto, ally2, merged);
+                } else {
+                    // No ally at cursor, move selected ally to cursor position
+                    self.last_merge_undo = None;
+                    self.last_move = Some(MoveUndo {
+                        from: (sel_i, sel_j),
+                        to: (cur_i, cur_j),
+                        time_left: MOVE_UNDO_SECONDS,
+                    });
+                    self.board.ally_grid[cur_i][cur_j] = Some(ally1);
+                    self.drop_pending_hits((sel_i, sel_j));
+                    self.selected = None;
+                }
+            } else {
+                // No ally at selected position, clear selection
+                self.selected = None;
+            }
+        }
+    }
+
+    /// Puts `merged`, made of `ally1` dropped from grid cell `from` onto `ally2` in grid cell
+    /// `onto`, in place of `ally2`, keeping the merge undoable.
+    fn place_drop_merge(
+        &mut self,
+        from: (usize, usize),
+        ally1: Ally,
+        onto: (usize, usize),
+        ally2: Ally,
+        merged: Ally,
+    ) {
+        self.last_merge_undo = Some(MergeUndo {
+            from: (from, ally1),
+            onto: (onto, ally2),
+            time_left: MERGE_UNDO_SECONDS,
+        });
+        self.last_move = None;
+        self.events.push(GameEvent::Merged {
+            cell: onto,
+            level: merged.level,
+        });
+        self.board.ally_grid[onto.0][onto.1] = Some(merged);
+        self.drop_pending_hits(from);
+        self.drop_pending_hits(onto);
+        self.selected = None;
+    }
+
+    /// With `merge_snap` on, the first ally next to the cursor, looking up, down, left then
+    /// right, that `ally` dropped from grid cell `from` levels up: its grid cell, itself and the
+    /// merge. Allies of other elements are left alone, as merging into a dual ally is a choice.
+    fn merge_snap_partner(
+        &mut self,
+        ally: &Ally,
+        from: (usize, usize),
+    ) -> Option<((usize, usize), Ally, Ally)> {
+        if !self.config.as_ref().is_some_and(|config| config.merge_snap) {
+            return None;
+        }
+        let (cur_i, cur_j) = self.cursor;
+        for direction in [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ] {
+            // Step on the board, whose interior is the ally grid
+            let (row, col) = direction.step_on_board((cur_i + 1, cur_j + 1));
+            if row == 0 || col == 0 || row == BOARD_HEIGHT - 1 || col == BOARD_WIDTH - 1 {
+                continue;
+            }
+            let cell = (row - 1, col - 1);
+            if cell == from || cell == self.cursor {
+                continue;
+            }
+            let Some(other) = self.board.ally_grid[cell.0][cell.1].clone() else {
+                continue;
+            };
+            if other.element != ally.element || other.second_element != ally.second_element {
+                continue;
+            }
+            if let Ok(merged) = self.ally_merge(ally.clone(), other.clone()) {
+                return Some((cell, other, merged));
+            }
+        }
+        None
+    }
+
+    /// Splits the latest merge back into the two allies it consumed, if it's still within
+    /// [`MERGE_UNDO_SECONDS`]. Returns whether anything was undone.
+    pub fn undo_merge(&mut self) -> bool {
+        let Some(MergeUndo {
+            from: ((from_i, from_j), from),
+            onto: ((onto_i, onto_j), onto),
+            ..
+        }) = self.last_merge_undo.take()
+        else {
+            return false;
+        };
+        self.board.ally_grid[from_i][from_j] = Some(from);
+        self.board.ally_grid[onto_i][onto_j] = Some(onto);
+        self.drop_pending_hits((onto_i, onto_j));
+        self.selected = None;
+        info!("merge undone");
+        true
+    }
+
+    /// Moves the latest moved ally back to where it came from, if it's still within
+    /// [`MOVE_UNDO_SECONDS`], still on the cell it was moved to and its old cell is still empty.
+    /// Returns whether anything was undone.
+    pub fn undo_move(&mut self) -> bool {
+        let Some(MoveUndo { from, to, .. }) = self.last_move.take() else {
+            return false;
+        };
+        if self.board.ally_grid[from.0][from.1].is_some() {
+            return false;
+        }
+        let Some(ally) = self.board.ally_grid[to.0][to.1].take() else {
+            return false;
+        };
+        self.board.ally_grid[from.0][from.1] = Some(ally);
+        self.drop_pending_hits(to);
+        self.selected = None;
+        info!("move undone");
+        true
+    }
+
+    /// Maps an enemy's path position to world coordinates.
+    ///
+    /// World space uses the cells of the rendered board as `(x, y)` = `(column, row)`: the board
+    /// is `BOARD_WIDTH` x `BOARD_HEIGHT` with the ally grid in its interior, so ally `(i, j)` sits
+    /// at `(j + 1, i + 1)`. Enemies walk the perimeter clockwise from the top-left corner, one
+    /// cell per unit of `position`, and the corners are reached at positions 0, 8, 12 and 20:
+    ///
+    /// | position | edge   | coordinates   |
+    /// |----------|--------|---------------|
+    /// | 0..8     | top    | `(p, 0)`      |
+    /// | 8..12    | right  | `(8, p - 8)`  |
+    /// | 12..20   | bottom | `(20 - p, 4)` |
+    /// | 20..24   | left   | `(0, 24 - p)` |
+    ///
+    /// Positions past `PATH_LENGTH` are off the board and map back to the start.
+    ///
+    /// On a forking path the [`PathBranch::Upper`] branch is the first half of the loop, and the
+    /// [`PathBranch::Lower`] one walks the other half the other way round, so both end at the
+    /// bottom-right corner:
+    ///
+    /// | position | edge   | coordinates   |
+    /// |----------|--------|---------------|
+    /// | 0..4     | left   | `(0, p)`      |
+    /// | 4..12    | bottom | `(p - 4, 4)`  |
+    ///
+    /// Positions past `BRANCH_LENGTH` map back to the start as well.
+    pub fn enemy_grid_position(ene: &Enemy) -> (f32, f32) {
+        let p = ene.position;
+        if p >= ene.path_length() {
+            return (0.0, 0.0);
+        }
+        if ene.branch == Some(PathBranch::Lower) {
+            return if p < 4.0 { (0.0, p) } else { (p - 4.0, 4.0) };
+        }
+        if p < 8.0 {
+            (p, 0.0)
+        } else if p < 12.0 {
+            // right
+            (8.0, p - 8.0)
+        } else if p < 20.0 {
+            // bottom
+            (20.0 - p, 4.0)
+        } else if p < PATH_LENGTH {
+            // left
+            (0.0, PATH_LENGTH - p)
+        } else {
+            // out of bounds
+            (0.0, 0.0)
+        }
+    }
+
+    /// The board cell `(row, column)` an enemy is drawn in, i.e. the cell nearest to its
+    /// [`Game::enemy_grid_position`].
+    pub fn enemy_grid_cell(ene: &Enemy) -> (usize, usize) {
+        let (x, y) = Game::enemy_grid_position(ene);
+        (y.round() as usize, x.round() as usize)
+    }
+
+    fn endless_wave_spawn(&mut self) {
+        let wave = match &self.config {
+            Some(config) => config.endless.wave(self.level),
+            None => EndlessConfig::default().wave(self.level),
+        };
+        for (enemy, spawn_time) in wave {
+            self.queue_enemy(enemy, spawn_time);
+        }
+    }
+
+    /// Gives `enemy` the next id and its kind's regeneration, and queues it to spawn in
+    /// `spawn_time` ticks.
+    fn queue_enemy(&mut self, enemy: Enemy, spawn_time: usize) {
+        let id = self.next_enemy_id;
+        self.next_enemy_id += 1;
+        let hp_regen = self
+            .config
+            .as_ref()
+            .map_or(0.0, |config| config.regen.of(enemy.kind));
+        let split = self.config.as_ref().and_then(|config| config.branch_split);
+        let branch = split.map(|split| {
+            if self.rng.random::<f32>() < split {
+                PathBranch::Upper
+            } else {
+                PathBranch::Lower
+            }
+        });
+        let mut enemy = Enemy {
+            id,
+            max_hp: enemy.hp,
+            hp_regen,
+            branch,
+            ..enemy
+        };
+        // A branch is shorter than the whole path, so a sandbox spawn can't start past its end
+        enemy.position = enemy.position.min(enemy.path_length() - 1.0);
+        self.board.enemy_ready2spawn.push((enemy, spawn_time));
+    }
+
+    /// A campaign enemy of `kind`, at the entrance.
+    fn enemy_of_kind(&self, kind: EnemyKind) -> Enemy {
+        let bounty = self.config.as_ref().map_or_else(
+            || BountyConfig::default().of(kind),
+            |config| config.bounty.of(kind),
+        );
+        let enemy = match kind {
+            EnemyKind::Tank => Enemy {
+                hp: 200,
+                move_speed: 0.6,
+                kind,
+                armor_type: kind.armor_type(),
+                armor: 5,
+                bounty,
+                ..Default::default()
+            },
+            EnemyKind::Runner => Enemy {
+                hp: 60,
+                move_speed: 1.6,
+                kind,
+                armor_type: kind.armor_type(),
+                bounty,
+                ..Default::default()
+            },
+            EnemyKind::Grunt => Enemy {
+                hp: 100,
+                move_speed: 1.0,
+                kind,
+                armor_type: kind.armor_type(),
+                bounty,
+                ..Default::default()
+            },
+            EnemyKind::Reflector => Enemy {
+                hp: 120,
+                move_speed: 0.8,
+                kind,
+                armor_type: kind.armor_type(),
+                bounty,
+                reflect: self.reflect_fraction(),
+                ..Default::default()
+            },
+        };
+        // The speeds above are in cells, taking as long to walk the loop in either unit
+        match self.speed_unit() {
+            SpeedUnit::Cells => enemy,
+            SpeedUnit::Loops => Enemy {
+                move_speed: enemy.move_speed / PATH_LENGTH,
+                ..enemy
+            },
+        }
+    }
+
+    /// Spawns an enemy of `kind` at `position` on the next update, for sandbox mode.
+    pub fn debug_spawn_enemy(&mut self, kind: EnemyKind, position: f32) {
+        let enemy = Enemy {
+            position: position.max(0.0),
+            ..self.enemy_of_kind(kind)
+        };
+        info!(?kind, position, "sandbox spawn");
+        self.queue_enemy(enemy, 0);
+    }
+
+    /// Adds [`SANDBOX_COIN_GRANT`] coins, for sandbox mode. They don't count as earned.
+    pub fn grant_coins(&mut self) {
+        self.coin += SANDBOX_COIN_GRANT;
+    }
+
+    fn enemy_spawn(&mut self) {
+        let (delay_min, delay_max) = self
+            .config
+            .as_ref()
+            .map_or((0, DEFAULT_SPAWN_DELAY_MAX), |config| {
+                config.spawn_delay_range()
+            });
+        // Enemies of the configured groups, or 10 with a tank and a runner in every five
+        let groups = self
+            .config
+            .as_ref()
+            .map(|config| config.wave.clone())
+            .unwrap_or_default();
+        let enemies = if groups.is_empty() {
+            (0..10)
+                .map(|k| match k % 5 {
+                    4 => EnemyKind::Tank,
+                    2 => EnemyKind::Runner,
+                    _ => EnemyKind::Grunt,
+                })
+                .map(|kind| self.enemy_of_kind(kind))
+                .collect::<Vec<_>>()
+        } else {
+            groups
+                .iter()
+                .flat_map(|group| self.wave_group(group))
+                .collect()
+        };
+        // Each with a random spawn time within the configured delays
+        for enemy in enemies {
+            let spawn_time = self.rng.random_range(delay_min..=delay_max);
+            self.queue_enemy(enemy, spawn_time);
+        }
+    }
+
+    /// The enemies of a `[[wave]]` group. Groups [`ConfigFile::validate`] would flag spawn
+    /// nothing, or fall back to the kind's HP and speed.
+    fn wave_group(&self, group: &WaveConfig) -> Vec<Enemy> {
+        let Some(kind) = EnemyKind::from_name(&group.kind) else {
+            return Vec::new();
+        };
+        let base = self.enemy_of_kind(kind);
+        let valid = |v: &f32| v.is_finite() && *v > 0.0;
+        let enemy = Enemy {
+            hp: group
+                .hp
+                .filter(valid)
+                .map_or(base.hp, |hp| hp as usize)
+                .max(1),
+            move_speed: group.speed.filter(valid).unwrap_or(base.move_speed),
+            ..base
+        };
+        vec![enemy; group.count]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn arb_element() -> impl Strategy<Value = AllyElement> {
+        prop_oneof![
+            Just(AllyElement::Basic),
+            Just(AllyElement::Slow),
+            Just(AllyElement::Aoe),
+            Just(AllyElement::Dot),
+            Just(AllyElement::Critical),
+            Just(AllyElement::Chain),
+            Just(AllyElement::Coin),
+            Just(AllyElement::Aura),
+        ]
+    }
+
+    /// Element pairs as they appear on the board: the second element, if any, is distinct from
+    /// and sorted after the first.
+    fn arb_elements() -> impl Strategy<Value = (AllyElement, Option<AllyElement>)> {
+        (arb_element(), proptest::option::of(arb_element())).prop_map(|(e0, e1)| match e1 {
+            Some(e1) if e1 != e0 => (std::cmp::min(e0, e1), Some(std::cmp::max(e0, e1))),
+            _ => (e0, None),
+        })
+    }
+
+    fn arb_ally() -> impl Strategy<Value = Ally> {
+        (
+            arb_elements(),
+            1usize..100,
+            1usize..5,
+            0usize..3,
+            1usize..5,
+            0.1f32..3.0,
+            1.0f32..2.0,
+            0.0f32..5.0,
+        )
+            .prop_map(
+                |(
+                    (element, second_element),
+                    atk,
+                    range,
+                    aoe_range,
+                    level,
+                    attacks_per_second,
+                    levelup_ratio,
+                    special_value,
+                )| Ally {
+                    element,
+                    second_element,
+                    atk,
+                    range,
+                    aoe_range,
+                    level,
+                    attacks_per_second,
+                    attack_cooldown: 0.0,
+                    levelup_ratio,
+                    special_value,
+                    ..Default::default()
+                },
+            )
+    }
+
+    #[test]
+    fn shipped_config_parses() {
+        let config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        let slow = config.ally_config(AllyElement::Slow);
+        assert_eq!(slow.atk, Some(7));
+        assert_eq!(slow.attacks_per_second(), Some(1.25));
+        // inherited from [general]
+        assert_eq!(slow.range, Some(2));
+        assert_eq!(config.ally_config(AllyElement::Aoe).aoe_range, Some(1));
+        assert_eq!(config.ally_config(AllyElement::Basic).atk, Some(10));
+    }
+
+    #[test]
+    fn malformed_config_is_an_error() {
+        assert!(ConfigFile::from_toml("[general").is_err());
+        assert!(ConfigFile::from_toml("[general]\natk = -1").is_err());
+        assert!(ConfigFile::from_toml("[allies.slow]\natk = 7").is_err());
+    }
+
+    #[test]
+    fn endless_waves_scale_with_wave_number() {
+        let config = EndlessConfig::default();
+        let first = config.wave(1);
+        let tenth = config.wave(10);
+        assert_eq!(first.len(), config.base_count);
+        assert!(tenth.len() > first.len());
+        assert!(tenth[0].0.hp > first[0].0.hp);
+        assert!(tenth[0].0.move_speed > first[0].0.move_speed);
+    }
+
+    #[test]
+    fn endless_mode_advances_waves_instead_of_winning() {
+        let mut game = Game::with_mode(GameMode::Endless);
+        game.config = Some(game.default_config_file());
+        game.game_state = GameState::Running;
+        game.update();
+        assert!(matches!(game.game_state, GameState::Running));
+        assert_eq!(game.level, 2);
+        assert_eq!(game.waves_cleared, 1);
+        assert!(!game.board.enemy_ready2spawn.is_empty());
+    }
+
+    #[test]
+    fn cleared_waves_pay_a_bonus_for_lives_kept() {
+        let bonus_after_leaks = |leaks: usize| {
+            let mut game = Game::with_mode(GameMode::Endless);
+            game.config = Some(game.default_config_file());
+            game.game_state = GameState::Running;
+            let leaking = Enemy {
+                hp: 100,
+                ..enemy_at(PATH_LENGTH)
+            };
+            game.board.enemies = vec![leaking; leaks];
+            let coin = game.coin;
+            game.update();
+            assert_eq!(game.level, 2);
+            assert_eq!(game.lives_lost_this_wave, 0);
+            game.coin - coin
+        };
+        assert_eq!(bonus_after_leaks(0), STARTING_LIVES * 2 + 50);
+        assert_eq!(bonus_after_leaks(1), (STARTING_LIVES - 1) * 2);
+    }
+
+    #[test]
+    fn leaked_enemies_cost_lives_until_game_over() {
+        let mut game = Game::with_mode(GameMode::Endless);
+        game.lives = 2;
+        game.board.enemies = vec![enemy_at(PATH_LENGTH), enemy_at(PATH_LENGTH)];
+        game.board.enemies.iter_mut().for_each(|e| e.hp = 100);
+        game.update();
+        assert_eq!(game.lives, 0);
+        assert!(game.board.enemies.is_empty());
+        assert!(matches!(game.game_state, GameState::End));
+        assert!(!game.is_victory());
+    }
+
+    #[test]
+    fn a_leaking_boss_costs_its_configured_lives() {
+        let mut game = Game::with_mode(GameMode::Endless);
+        let mut config = game.default_config_file();
+        config.leak_cost.tank = 4;
+        game.config = Some(config);
+        game.board.enemies = vec![
+            Enemy {
+                kind: EnemyKind::Tank,
+                hp: 100,
+                ..enemy_at(PATH_LENGTH)
+            },
+            Enemy {
+                hp: 100,
+                ..enemy_at(PATH_LENGTH)
+            },
+        ];
+        game.update();
+        assert_eq!(game.lives, STARTING_LIVES - 5);
+        assert!(game.events.contains(&GameEvent::LifeLost {
+            lost: 5,
+            remaining: STARTING_LIVES - 5,
+        }));
+
+        // A cost past the lives left ends the run at 0
+        game.lives = 2;
+        game.board.enemies = vec![Enemy {
+            kind: EnemyKind::Tank,
+            hp: 100,
+            ..enemy_at(PATH_LENGTH)
+        }];
+        game.update();
+        assert_eq!(game.lives, 0);
+        assert!(matches!(game.game_state, GameState::End));
+    }
+
+    #[test]
+    fn nothing_ticks_while_paused() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Dot,
+            atk: 1,
+            range: 2,
+            level: 1,
+            attacks_per_second: 1.0,
+            ..Default::default()
+        });
+        game.board.enemies = vec![Enemy {
+            hp: 1000,
+            move_speed: 1.0,
+            position: 1.0,
+            ..Default::default()
+        }];
+        game.board.enemy_ready2spawn = vec![(enemy_at(0.0), 500)];
+        game.game_state = GameState::Running;
+        // Leave an attack cooldown, slows and DoTs mid-flight
+        for _ in 0..10 {
+            game.update();
+        }
+        game.board.enemies[0].slow_list.push(Debuff {
+            value: 1,
+            cooldown: 1.0,
+        });
+        assert!(!game.board.enemies[0].dot_list.is_empty());
+        assert!(game.board.ally_grid[0][0].as_ref().unwrap().attack_cooldown > 0.0);
+
+        game.toggle_pause();
+        let board = game.board.clone();
+        let (coin, lives) = (game.coin, game.lives);
+        for _ in 0..600 {
+            game.update();
+        }
+        assert_eq!(game.board, board);
+        assert_eq!((game.coin, game.lives), (coin, lives));
+        assert_eq!(game.ticks_elapsed, 10);
+
+        game.toggle_pause();
+        game.update();
+        assert_ne!(game.board, board);
+    }
+
+    #[test]
+    fn only_imminent_spawns_are_pending() {
+        let mut game = Game::with_board(Board {
+            ally_grid: vec![vec![None; 7]; 3],
+            enemies: Vec::new(),
+            enemy_ready2spawn: vec![
+                (enemy_at(0.0), SPAWN_TELEGRAPH_TICKS + 5),
+                (enemy_at(3.0), 10),
+            ],
+        });
+        assert_eq!(game.pending_spawns(), vec![(3.0, 10)]);
+        for _ in 0..5 {
+            game.update();
+        }
+        assert_eq!(
+            game.pending_spawns(),
+            vec![(0.0, SPAWN_TELEGRAPH_TICKS), (3.0, 5)]
+        );
+    }
+
+    #[test]
+    fn allies_retarget_to_the_priority_enemy_in_range() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            atk: 10,
+            range: 2,
+            level: 1,
+            ..Default::default()
+        });
+        game.board.enemies = [(1, 1.0), (2, 2.0)]
+            .map(|(id, position)| Enemy {
+                hp: 100,
+                position,
+                id,
+                ..Default::default()
+            })
+            .to_vec();
+        game.game_state = GameState::Running;
+        game.priority_target = Some(2);
+        game.update();
+        let hp = game.board.enemies.iter().map(|e| e.hp).collect::<Vec<_>>();
+        assert_eq!(hp, vec![100, 90]);
+
+        game.board.enemies[1].hp = 0;
+        game.enemy_update();
+        assert_eq!(game.priority_target, None);
+    }
+
+    #[test]
+    fn enemy_counts_split_field_incoming_and_kinds() {
+        let mut game = Game::new();
+        game.board.enemies = vec![
+            enemy_at(1.0),
+            Enemy {
+                kind: EnemyKind::Tank,
+                ..enemy_at(2.0)
+            },
+        ];
+        game.board.enemy_ready2spawn = vec![
+            (enemy_at(0.0), 10),
+            (
+                Enemy {
+                    kind: EnemyKind::Runner,
+                    ..enemy_at(0.0)
+                },
+                20,
+            ),
+            (
+                Enemy {
+                    kind: EnemyKind::Tank,
+                    ..enemy_at(0.0)
+                },
+                30,
+            ),
+        ];
+        assert_eq!(
+            game.enemy_counts(),
+            EnemyCounts {
+                on_field: 2,
+                incoming: 3,
+                by_kind: [2, 2, 1, 0],
+            }
+        );
+    }
+
+    #[test]
+    fn spawned_enemies_have_unique_ids() {
+        let mut game = Game::new();
+        game.enemy_spawn();
+        game.endless_wave_spawn();
+        let ids = game
+            .board
+            .enemy_ready2spawn
+            .iter()
+            .map(|(enemy, _)| enemy.id)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(ids.len(), game.board.enemy_ready2spawn.len());
+    }
+
+    #[test]
+    fn loaded_boards_keep_enemy_ids_apart() {
+        let enemy = Enemy {
+            id: 41,
+            ..Default::default()
+        };
+        let content = toml::to_string(&enemy).unwrap();
+        let enemy: Enemy = toml::from_str(&content).unwrap();
+        let mut game = Game::with_board(Board {
+            enemies: vec![enemy],
+            ..Default::default()
+        });
+        game.queue_enemy(Enemy::default(), 0);
+        assert_eq!(game.board.enemy_ready2spawn[0].0.id, 42);
+    }
+
+    #[test]
+    fn smart_finish_picks_the_enemy_it_can_kill() {
+        let hp_after_one_hit = |targeting| {
+            let mut game = Game::new();
+            game.board.ally_grid[0][0] = Some(Ally {
+                atk: 10,
+                range: 2,
+                level: 1,
+                targeting,
+                ..Default::default()
+            });
+            // The closer enemy survives the hit, the farther one doesn't
+            game.board.enemies = vec![
+                Enemy {
+                    hp: 100,
+                    position: 1.0,
+                    ..Default::default()
+                },
+                Enemy {
+                    hp: 8,
+                    position: 2.0,
+                    ..Default::default()
+                },
+            ];
+            game.ally_update();
+            game.board.enemies.iter().map(|e| e.hp).collect::<Vec<_>>()
+        };
+        assert_eq!(hp_after_one_hit(TargetingMode::Closest), vec![90, 8]);
+        // What the kill had left over goes on to the closer one
+        assert_eq!(hp_after_one_hit(TargetingMode::SmartFinish), vec![98, 0]);
+    }
+
+    #[test]
+    fn a_killing_hit_carries_over_to_the_next_enemy() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            atk: 30,
+            range: 2,
+            level: 1,
+            ..Default::default()
+        });
+        game.board.enemies = [1.0, 2.0, 0.0]
+            .into_iter()
+            .map(|position| Enemy {
+                hp: 10,
+                position,
+                ..Default::default()
+            })
+            .collect();
+        game.ally_update();
+        let hp = game.board.enemies.iter().map(|e| e.hp).collect::<Vec<_>>();
+        assert_eq!(hp, [0, 0, 0]);
+
+        // A hit that only just kills has nothing left to carry over
+        game.board.enemies = vec![enemy_at(1.0), enemy_at(2.0)];
+        game.board.enemies[0].hp = 30;
+        game.board.enemies[1].hp = 10;
+        game.board.ally_grid[0][0].as_mut().unwrap().attack_cooldown = 0.0;
+        game.ally_update();
+        let hp = game.board.enemies.iter().map(|e| e.hp).collect::<Vec<_>>();
+        assert_eq!(hp, [0, 10]);
+    }
+
+    #[test]
+    fn focus_fire_targets_the_enemy_nearest_to_the_cursor() {
+        let mut game = Game::new();
+        game.board.enemies = [(1, 1.0), (2, 7.0)]
+            .map(|(id, position)| Enemy {
+                position,
+                id,
+                ..Default::default()
+            })
+            .to_vec();
+        game.cursor = (0, 6);
+        game.focus_fire();
+        assert_eq!(game.priority_target, Some(2));
+        game.focus_fire();
+        assert_eq!(game.priority_target, None);
+    }
+
+    #[test]
+    fn stun_holds_enemy_until_it_expires() {
+        let mut enemy = enemy_at(0.0);
+        enemy.hp = 100;
+        enemy.move_speed = 1.0;
+        enemy.stun_list.push(Debuff {
+            value: 1,
+            cooldown: 0.5,
+        });
+        let mut game = Game::with_board(Board {
+            ally_grid: vec![vec![None; 7]; 3],
+            enemies: vec![enemy],
+            enemy_ready2spawn: Vec::new(),
+        });
+
+        // 0.5s of stun at 60 ticks per second
+        for _ in 0..25 {
+            game.update();
+        }
+        assert_eq!(game.board.enemies[0].position, 0.0);
+        assert!(game.board.enemies[0].is_stunned());
+
+        for _ in 0..10 {
+            game.update();
+        }
+        assert!(!game.board.enemies[0].is_stunned());
+        assert!(game.board.enemies[0].position > 0.0);
+    }
+
+    #[test]
+    fn guaranteed_stun_is_applied_on_hit() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            atk: 1,
+            range: 2,
+            level: 1,
+            stun_chance: 1.0,
+            stun_duration: 0.2,
+            ..Default::default()
+        });
+        game.board.enemies = vec![Enemy {
+            hp: 100,
+            move_speed: 1.0,
+            position: 1.0,
+            ..Default::default()
+        }];
+        game.game_state = GameState::Running;
+        game.update();
+        assert!(game.board.enemies[0].is_stunned());
+    }
+
+    #[test]
+    fn shredded_tank_takes_more_damage() {
+        let tank = Enemy {
+            hp: 200,
+            kind: EnemyKind::Tank,
+            armor: 5,
+            position: 1.0,
+            ..Default::default()
+        };
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Critical,
+            atk: 5,
+            range: 2,
+            level: 1,
+            ..Default::default()
+        });
+        game.board.enemies = vec![tank.clone()];
+        game.game_state = GameState::Running;
+        game.update();
+        let unshredded_hp = game.board.enemies[0].hp;
+
+        let mut shredded = tank;
+        shredded.armor_shred_list.push(Debuff {
+            value: 3,
+            cooldown: 1.0,
+        });
+        assert_eq!(shredded.effective_armor(), 2);
+        game.board.enemies = vec![shredded];
+        game.board.ally_grid[0][0].as_mut().unwrap().attack_cooldown = 0.0;
+        game.update();
+
+        assert_eq!(unshredded_hp, 200 - (10 - 5));
+        assert_eq!(game.board.enemies[0].hp, 200 - (10 - 2));
+    }
+
+    #[test]
+    fn dot_hits_shred_armor_by_special_value() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Dot,
+            atk: 1,
+            range: 2,
+            level: 1,
+            special_value: 2.0,
+            ..Default::default()
+        });
+        game.board.enemies = vec![Enemy {
+            hp: 100,
+            kind: EnemyKind::Tank,
+            armor: 5,
+            position: 1.0,
+            ..Default::default()
+        }];
+        game.game_state = GameState::Running;
+        game.update();
+        assert_eq!(game.board.enemies[0].effective_armor(), 3);
+    }
+
+    #[test]
+    fn dot_duration_sets_how_long_dot_lasts() {
+        let ticks_until_dot_expires = |dot_duration: &str| {
+            let toml = include_str!("../config.toml").replace(
+                "[allies.Dot]\n",
+                &format!("[allies.Dot]\ndot_duration = {dot_duration}\n"),
+            );
+            let mut game = Game::new();
+            game.config = Some(ConfigFile::from_toml(&toml).unwrap());
+            let mut enemy = Enemy {
+                hp: 10_000,
+                ..Default::default()
+            };
+            Game::apply_debuffs(
+                game.config.as_ref(),
+                &mut enemy,
+                [Some(AllyElement::Dot), None],
+                0,
+            );
+            game.board.enemies = vec![enemy];
+            let mut ticks = 0_usize;
+            while !game.board.enemies[0].dot_list.is_empty() {
+                game.enemy_update();
+                ticks += 1;
+            }
+            ticks
+        };
+        // One tick either way for float rounding of the countdown
+        assert!(ticks_until_dot_expires("1.0").abs_diff(60) <= 1);
+        assert!(ticks_until_dot_expires("0.5").abs_diff(30) <= 1);
+    }
+
+    #[test]
+    fn lifesteal_restores_lives_up_to_the_start() {
+        let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        config.lifesteal_per_kill = 2;
+        let mut game = Game::new();
+        game.config = Some(config);
+        game.lives = STARTING_LIVES - 2;
+
+        game.lifesteal(1);
+        assert_eq!(game.lives, STARTING_LIVES - 2);
+        game.lifesteal(1);
+        assert_eq!(game.lives, STARTING_LIVES - 1);
+        game.lifesteal(10);
+        assert_eq!(game.lives, STARTING_LIVES);
+        assert_eq!(game.kills, 12);
+    }
+
+    #[test]
+    fn slow_stacks_have_diminishing_returns() {
+        let slow = SlowConfig::default();
+        let one = slow.factor([1]);
+        let two = slow.factor([1, 1]);
+        let five = slow.factor([1; 5]);
+        assert_eq!(one, 0.5);
+        assert!(two < one && five < two);
+        // Each extra stack takes less speed off than the one before
+        assert!(one - two < 1.0 - one);
+        assert!(slow.factor([1; 4]) - five < two - slow.factor([1; 3]));
+        assert!(five >= slow.floor);
+    }
+
+    #[test]
+    fn slows_never_go_below_the_floor() {
+        let slow = SlowConfig {
+            diminishing: 1.0,
+            floor: 0.3,
+        };
+        assert_eq!(slow.factor([1]), 0.5);
+        assert_eq!(slow.factor([1; 2]), 0.3);
+        assert_eq!(slow.factor([1; 5]), 0.3);
+        assert_eq!(slow.factor([]), 1.0);
+    }
+
+    #[test]
+    fn kills_pay_each_enemys_bounty() {
+        let coins_for = |enemy: Enemy| {
+            let mut game = Game::new();
+            game.board.enemies = vec![Enemy { hp: 0, ..enemy }];
+            let coin = game.coin;
+            game.enemy_update();
+            assert_eq!(game.coins_earned, game.coin - coin);
+            game.coin - coin
+        };
+        let boss = coins_for(Enemy {
+            kind: EnemyKind::Tank,
+            bounty: 100,
+            ..Default::default()
+        });
+        let grunt = coins_for(Enemy {
+            bounty: 10,
+            ..Default::default()
+        });
+        assert_eq!((boss, grunt), (100, 10));
+    }
+
+    #[test]
+    fn kill_rewards_fall_off_with_the_stockpile() {
+        let reward_at = |coin, enabled| {
+            let mut game = Game::new();
+            let mut config = game.default_config_file();
+            config.reward_falloff.enabled = enabled;
+            game.config = Some(config);
+            game.coin = coin;
+            game.board.enemies = vec![Enemy {
+                bounty: 10,
+                ..Default::default()
+            }];
+            game.enemy_update();
+            game.coin - coin
+        };
+        // Broke, in between and hoarding, with the default thresholds of 20 and 300
+        assert_eq!(reward_at(0, true), 15);
+        assert_eq!(reward_at(100, true), 10);
+        assert_eq!(reward_at(300, true), 5);
+        assert_eq!(reward_at(0, false), 10);
+        assert_eq!(reward_at(300, false), 10);
+    }
+
+    #[test]
+    fn enemies_speed_up_once_a_wave_drags_on() {
+        let step_at = |seconds: usize, enabled| {
+            let mut game = Game::new();
+            let mut config = game.default_config_file();
+            config.frenzy.enabled = enabled;
+            game.config = Some(config);
+            game.wave_ticks = seconds * 60;
+            game.board.enemies = vec![Enemy {
+                hp: 100,
+                move_speed: 1.0,
+                ..Default::default()
+            }];
+            game.enemy_update();
+            game.board.enemies[0].position * 60.0
+        };
+        // The default frenzy starts 60 seconds in and grows by 2% a second up to 3x
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        assert!(close(step_at(30, true), 1.0));
+        assert!(close(step_at(110, true), 2.0));
+        assert!(step_at(110, true) > step_at(70, true));
+        assert!(close(step_at(600, true), 3.0));
+        assert!(close(step_at(600, false), 1.0));
+    }
+
+    #[test]
+    fn enemy_cap_holds_under_a_flood_of_spawns() {
+        let flood = |overflow| {
+            let mut game = Game::new();
+            let mut config = game.default_config_file();
+            config.max_enemies = Some(5);
+            config.enemy_overflow = overflow;
+            game.config = Some(config);
+            game.board.enemy_ready2spawn = (0..40)
+                .map(|id| {
+                    let enemy = Enemy {
+                        id,
+                        hp: 100,
+                        move_speed: 6.0,
+                        ..Default::default()
+                    };
+                    (enemy, 0)
+                })
+                .collect();
+            game.enemy_update();
+            game
+        };
+        let ids = |game: &Game| game.board.enemies.iter().map(|e| e.id).collect::<Vec<_>>();
+
+        let game = flood(EnemyOverflow::Drop);
+        assert_eq!(ids(&game), [0, 1, 2, 3, 4]);
+        assert!(game.board.enemy_ready2spawn.is_empty());
+
+        let mut game = flood(EnemyOverflow::Delay);
+        assert_eq!(ids(&game), [0, 1, 2, 3, 4]);
+        assert_eq!(game.board.enemy_ready2spawn.len(), 35);
+        // The rest trickle in as the first ones leak, never past the cap
+        while !game.board.enemy_ready2spawn.is_empty() {
+            game.enemy_update();
+            assert!(game.board.enemies.len() <= 5);
+        }
+        assert_eq!(ids(&game), [35, 36, 37, 38, 39]);
+    }
+
+    #[test]
+    fn free_starting_allies_are_placed_without_spending_coins() {
+        let start = |free_starting_allies| {
+            let mut game = Game::new();
+            game.rng = ChaCha8Rng::seed_from_u64(7);
+            game.auto_merge_on_buy = true;
+            let mut config = game.default_config_file();
+            config.free_starting_allies = free_starting_allies;
+            game.init_game_with(config);
+            game
+        };
+        let allies = |game: &Game| game.board.ally_grid.iter().flatten().flatten().count();
+
+        let game = start(3);
+        assert_eq!(allies(&game), 3);
+        assert_eq!(game.coin, Game::new().coin);
+        assert!(
+            game.board
+                .ally_grid
+                .iter()
+                .flatten()
+                .flatten()
+                .all(|a| a.level == 1)
+        );
+        assert_eq!(game.board.ally_grid, start(3).board.ally_grid);
+        assert_eq!(allies(&start(100)), 21);
+        assert_eq!(allies(&start(0)), 0);
+    }
+
+    #[test]
+    fn lifesteal_is_off_by_default() {
+        let config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        assert_eq!(config.lifesteal_per_kill, 0);
+        let mut game = Game::new();
+        game.config = Some(config);
+        game.lives = 1;
+        game.lifesteal(100);
+        assert_eq!(game.lives, 1);
+    }
+
+    #[test]
+    fn score_weighs_waves_kills_and_coins() {
+        let mut game = Game::new();
+        game.waves_cleared = 3;
+        game.kills = 25;
+        game.coins_earned = 250;
+        game.ticks_elapsed = 90 * 60 + 30;
+        assert_eq!(game.score(), 3 * 1000 + 25 * 10 + 250 + 90);
+
+        let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        config.score = ScoreConfig {
+            per_wave: 7,
+            per_kill: 3,
+            per_coin: 0,
+            per_second: 2,
+        };
+        game.config = Some(config);
+        assert_eq!(game.score(), 3 * 7 + 25 * 3 + 90 * 2);
+    }
+
+    #[test]
+    fn damage_and_kills_are_credited_to_the_attacker() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            atk: 30,
+            range: 2,
+            level: 1,
+            ..Default::default()
+        });
+        game.board.ally_grid[2][6] = Some(Ally {
+            atk: 1,
+            range: 1,
+            level: 1,
+            ..Default::default()
+        });
+        game.board.enemies = vec![Enemy {
+            hp: 20,
+            position: 1.0,
+            ..Default::default()
+        }];
+        game.game_state = GameState::Running;
+        game.update();
+
+        let stats = &game.board.ally_grid[0][0].as_ref().unwrap().stats;
+        assert_eq!(stats.damage_dealt, 20);
+        assert_eq!(stats.kills, 1);
+        assert_eq!(game.mvp(), game.board.ally_grid[0][0].as_ref());
+
+        // Selling it doesn't take the title away from it
+        let mvp = game.board.ally_grid[0][0].clone();
+        game.sell_ally((0, 0));
+        assert_eq!(game.mvp(), mvp.as_ref());
+    }
+
+    #[test]
+    fn max_level_allies_do_not_merge() {
+        let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        config.max_level = Some(3);
+        let mut game = Game::new();
+        game.config = Some(config);
+        let ally = |level| Ally {
+            level,
+            atk: 10,
+            levelup_ratio: 1.5,
+            ..Default::default()
+        };
+
+        assert_eq!(game.ally_merge(ally(2), ally(2)).unwrap().level, 3);
+        assert!(game.is_maxed(&ally(3)));
+        assert_eq!(game.ally_merge(ally(3), ally(3)), Err(MergeError::MaxLevel));
+    }
+
+    #[test]
+    fn mismatched_levels_merge_only_under_the_absorb_rule() {
+        let mut game = Game::new();
+        game.config = Some(game.default_config_file());
+        let ally = |element, level, atk| Ally {
+            element,
+            level,
+            atk,
+            levelup_ratio: 1.5,
+            ..Default::default()
+        };
+        let (high, low) = (
+            ally(AllyElement::Dot, 4, 100),
+            ally(AllyElement::Dot, 2, 10),
+        );
+
+        assert_eq!(
+            game.ally_merge(high.clone(), low.clone()),
+            Err(MergeError::LevelMismatch)
+        );
+
+        game.config.as_mut().unwrap().merge_rule = MergeRule::Absorb;
+        let absorbed = game.ally_merge(low.clone(), high.clone()).unwrap();
+        assert_eq!(absorbed, game.ally_merge(high, low).unwrap());
+        assert_eq!((absorbed.level, absorbed.atk), (4, 125));
+        assert_eq!(
+            game.ally_merge(absorbed, ally(AllyElement::Slow, 1, 10)),
+            Err(MergeError::LevelMismatch)
+        );
+    }
+
+    #[test]
+    fn inspect_cursor_reaches_every_cell_of_the_board() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            level: 2,
+            ..Default::default()
+        });
+        game.board.enemies = vec![enemy_at(1.0), enemy_at(1.1), enemy_at(6.0)];
+
+        let mut cell = (1, 1);
+        assert_eq!(game.ally_in_cell(cell).map(|a| a.level), Some(2));
+        cell = Direction::Up.step_on_board(cell);
+        assert_eq!(cell, (0, 1));
+        assert!(game.ally_in_cell(cell).is_none());
+        assert_eq!(game.enemies_in_cell(cell).count(), 2);
+        assert_eq!(Direction::Up.step_on_board(cell), cell);
+
+        for _ in 0..BOARD_WIDTH {
+            cell = Direction::Right.step_on_board(cell);
+        }
+        for _ in 0..BOARD_HEIGHT {
+            cell = Direction::Down.step_on_board(cell);
+        }
+        assert_eq!(cell, (BOARD_HEIGHT - 1, BOARD_WIDTH - 1));
+        assert!(game.ally_in_cell(cell).is_none());
+    }
+
+    #[test]
+    fn merge_errors_name_the_reason() {
+        let mut game = Game::new();
+        let ally = |element, second_element, level| Ally {
+            element,
+            second_element,
+            level,
+            levelup_ratio: 1.5,
+            ..Default::default()
+        };
+        let basic = ally(AllyElement::Basic, None, 1);
+        let slow_dot = ally(AllyElement::Slow, Some(AllyElement::Dot), 1);
+        let aoe_chain = ally(AllyElement::Aoe, Some(AllyElement::Chain), 1);
+
+        assert_eq!(
+            game.ally_merge(basic.clone(), ally(AllyElement::Basic, None, 2)),
+            Err(MergeError::LevelMismatch)
+        );
+        assert_eq!(
+            game.ally_merge(basic, slow_dot.clone()),
+            Err(MergeError::AlreadyDualElement)
+        );
+        assert_eq!(
+            game.ally_merge(slow_dot, aoe_chain),
+            Err(MergeError::IncompatibleElements)
+        );
+    }
+
+    #[test]
+    fn selling_by_element_skips_dual_allies() {
+        let mut game = Game::new();
+        let ally = |element, second_element, level| {
+            Some(Ally {
+                element,
+                second_element,
+                level,
+                ..Default::default()
+            })
+        };
+        game.board.ally_grid[0][0] = ally(AllyElement::Slow, None, 1);
+        game.board.ally_grid[1][3] = ally(AllyElement::Slow, None, 3);
+        game.board.ally_grid[2][6] = ally(AllyElement::Slow, Some(AllyElement::Dot), 1);
+        game.board.ally_grid[0][1] = ally(AllyElement::Dot, None, 1);
+        game.selected = Some((1, 3));
+        let coin = game.coin;
+
+        assert_eq!(game.sell_all_of_element(AllyElement::Slow), 2);
+        assert_eq!(game.coin, coin + ALLY_COST / 2 * (1 + 3));
+        assert_eq!(game.scrap, 2);
+        assert_eq!(game.selected, None);
+        assert!(game.board.ally_grid[0][0].is_none());
+        assert!(game.board.ally_grid[1][3].is_none());
+        assert!(game.board.ally_grid[2][6].is_some());
+        assert!(game.board.ally_grid[0][1].is_some());
+    }
+
+    #[test]
+    fn synergy_partners_hit_harder_only_while_adjacent() {
+        let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        config.synergies = vec![SynergyConfig {
+            elements: (AllyElement::Dot, AllyElement::Critical),
+            damage_multiplier: 1.5,
+        }];
+        let mut game = Game::new();
+        game.config = Some(config);
+        game.game_state = GameState::Running;
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Basic,
+            second_element: Some(AllyElement::Critical),
+            atk: 10,
+            range: 2,
+            level: 1,
+            attacks_per_second: 0.01,
+            ..Default::default()
+        });
+        let partner = Some(Ally {
+            element: AllyElement::Dot,
+            level: 1,
+            ..Default::default()
+        });
+        let hit = |game: &mut Game| {
+            game.board.enemies = vec![Enemy {
+                hp: 1000,
+                position: 1.0,
+                ..Default::default()
+            }];
+            if let Some(ally) = game.board.ally_grid[0][0].as_mut() {
+                ally.attack_cooldown = 0.0;
+            }
+            game.update();
+            1000 - game.board.enemies[0].hp
+        };
+
+        assert_eq!(hit(&mut game), 20);
+        game.board.ally_grid[1][0] = partner.clone();
+        assert_eq!(hit(&mut game), 30);
+        assert_eq!(
+            game.synergy_links(),
+            vec![SynergyLink {
+                from: (0, 0),
+                to: (1, 0),
+                multiplier: 1.5,
+            }]
+        );
+        game.board.ally_grid[1][0] = None;
+        game.board.ally_grid[2][0] = partner;
+        assert_eq!(hit(&mut game), 20);
+    }
+
+    fn matchup_game() -> Game {
+        let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        config.effectiveness = vec![
+            EffectivenessConfig {
+                element: AllyElement::Dot,
+                kind: EnemyKind::Tank,
+                multiplier: 1.5,
+            },
+            EffectivenessConfig {
+                element: AllyElement::Dot,
+                kind: EnemyKind::Runner,
+                multiplier: 0.5,
+            },
+        ];
+        let mut game = Game::new();
+        game.config = Some(config);
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Dot,
+            second_element: Some(AllyElement::Critical),
+            atk: 10,
+            range: 2,
+            level: 1,
+            ..Default::default()
+        });
+        game
+    }
+
+    #[test]
+    fn effectiveness_composes_with_crit_and_armor() {
+        let mut game = matchup_game();
+        game.board.enemies = vec![Enemy {
+            hp: 100,
+            kind: EnemyKind::Tank,
+            armor: 5,
+            position: 1.0,
+            ..Default::default()
+        }];
+        game.ally_damage((0, 0));
+        // 10 atk, doubled by crit, times 1.5 against tanks, minus 5 armor
+        assert_eq!(game.board.enemies[0].hp, 100 - 25);
+        assert_eq!(
+            game.events,
+            vec![
+                GameEvent::Attack {
+                    ally: (0, 0),
+                    target: (0, 1),
+                },
+                GameEvent::Hit {
+                    cell: (0, 1),
+                    damage: 25,
+                    effectiveness: 1.5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn allies_without_a_target_in_range_do_not_attack() {
+        let mut game = matchup_game();
+        game.board.enemies = vec![Enemy {
+            hp: 100,
+            position: PATH_LENGTH / 2.0,
+            ..Default::default()
+        }];
+        game.ally_damage((0, 0));
+        assert!(game.events.is_empty());
+    }
+
+    #[test]
+    fn densest_aoe_center_splashes_the_most_enemies() {
+        let hits = |aoe_center| {
+            let mut game = Game::new();
+            game.board.ally_grid[0][0] = Some(Ally {
+                element: AllyElement::Aoe,
+                atk: 10,
+                range: 3,
+                aoe_range: 1,
+                level: 1,
+                aoe_center,
+                ..Default::default()
+            });
+            // A lone enemy right next to the ally and a pack a little further down the path
+            game.board.enemies = [1.0, 3.0, 3.5, 4.0]
+                .into_iter()
+                .map(|position| Enemy {
+                    hp: 100,
+                    position,
+                    ..Default::default()
+                })
+                .collect();
+            game.ally_damage((0, 0));
+            game.events
+                .iter()
+                .filter(|e| matches!(e, GameEvent::Hit { .. }))
+                .count()
+        };
+        assert_eq!(hits(AoeCenter::Nearest), 1);
+        assert_eq!(hits(AoeCenter::Densest), 3);
+        assert_eq!(hits(AoeCenter::Furthest), 3);
+    }
+
+    #[test]
+    fn readiness_rises_as_the_cooldown_runs_out() {
+        let ally = |attack_cooldown, attacks_per_second| Ally {
+            attack_cooldown,
+            attacks_per_second,
+            ..Default::default()
+        };
+        assert_eq!(ally(2.0, 0.5).readiness(), 0.0);
+        assert_eq!(ally(0.5, 0.5).readiness(), 0.75);
+        assert_eq!(ally(0.0, 0.5).readiness(), 1.0);
+        assert_eq!(ally(1.0, 0.0).readiness(), 1.0);
+    }
+
+    #[test]
+    fn allies_out_of_reach_turn_idle() {
+        let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        config.idle_seconds = Some(1.0);
+        let mut game = Game::new();
+        game.config = Some(config);
+        game.board.ally_grid[0][0] = Some(Ally {
+            atk: 10,
+            range: 2,
+            attacks_per_second: 2.0,
+            level: 1,
+            ..Default::default()
+        });
+        game.board.enemies = vec![Enemy {
+            hp: 1_000,
+            position: PATH_LENGTH / 2.0,
+            ..Default::default()
+        }];
+        for _ in 0..60 {
+            game.ally_update();
+        }
+        assert!(game.is_idle(game.board.ally_grid[0][0].as_ref().unwrap()));
+
+        game.board.enemies[0].position = 1.0;
+        game.ally_update();
+        assert!(!game.is_idle(game.board.ally_grid[0][0].as_ref().unwrap()));
+
+        game.config.as_mut().unwrap().idle_seconds = Some(0.0);
+        game.board.ally_grid[0][0].as_mut().unwrap().idle_ticks = u32::MAX;
+        assert!(!game.is_idle(game.board.ally_grid[0][0].as_ref().unwrap()));
+    }
+
+    #[test]
+    fn fast_enemies_outrun_slow_projectiles() {
+        let projectile_game = |move_speed| {
+            let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+            config.projectile_speed = Some(0.5);
+            let mut ally_grid = vec![vec![None; 7]; 3];
+            ally_grid[0][0] = Some(Ally {
+                atk: 10,
+                range: 1,
+                // fires once, then cools down for longer than the test runs
+                attacks_per_second: 0.1,
+                level: 1,
+                ..Default::default()
+            });
+            let mut enemy = enemy_at(1.0);
+            enemy.hp = 100;
+            enemy.move_speed = move_speed;
+            let mut game = Game::with_board(Board {
+                ally_grid,
+                enemies: vec![enemy],
+                enemy_ready2spawn: Vec::new(),
+            });
+            game.config = Some(config);
+            game
+        };
+
+        // One cell away at half a cell per second, the hit lands after two seconds
+        let mut game = projectile_game(0.0);
+        game.update();
+        assert_eq!(game.pending_hits.len(), 1);
+        assert_eq!(game.board.enemies[0].hp, 100);
+        for _ in 0..120 {
+            game.update();
+        }
+        assert!(game.pending_hits.is_empty());
+        assert_eq!(game.board.enemies[0].hp, 90);
+
+        let mut game = projectile_game(3.0);
+        for _ in 0..121 {
+            game.update();
+        }
+        assert!(game.pending_hits.is_empty());
+        assert_eq!(game.board.enemies[0].hp, 100);
+
+        // Selling the ally takes its attack in flight with it, even if another takes its place
+        let mut game = projectile_game(0.0);
+        game.update();
+        let ally = game.board.ally_grid[0][0].clone();
+        game.sell_ally((0, 0));
+        assert!(game.pending_hits.is_empty());
+        game.board.ally_grid[0][0] = ally;
+        for _ in 0..120 {
+            game.update();
+        }
+        assert_eq!(game.board.enemies[0].hp, 100);
+    }
+
+    #[test]
+    fn chain_attacks_bounce_with_decaying_damage() {
+        let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        config.chain = ChainConfig {
+            bounces: 2,
+            decay: 0.5,
+            radius: 1.5,
+        };
+        let mut game = Game::new();
+        game.config = Some(config);
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Chain,
+            atk: 40,
+            range: 2,
+            level: 1,
+            ..Default::default()
+        });
+        game.board.enemies = (0..4)
+            .map(|i| Enemy {
+                hp: 100,
+                id: i,
+                position: 1.0 + i as f32,
+                ..Default::default()
+            })
+            .collect();
+        game.ally_damage((0, 0));
+        let hp = game.board.enemies.iter().map(|e| e.hp).collect::<Vec<_>>();
+        assert_eq!(hp, vec![60, 80, 90, 100]);
+        let bounces = game
+            .events
+            .iter()
+            .filter(|e| matches!(e, GameEvent::Bounce { .. }))
+            .count();
+        assert_eq!(bounces, 2);
+    }
+
+    #[test]
+    fn facing_aoe_skips_enemies_behind_the_ally() {
+        let mut game = Game::new();
+        game.config = Some(game.default_config_file());
+        // Board column 2 of row 1, right below the top edge of the path
+        game.board.ally_grid[0][1] = Some(Ally {
+            element: AllyElement::Aoe,
+            atk: 10,
+            range: 3,
+            aoe_range: 2,
+            level: 1,
+            facing: Some(Direction::Right),
+            ..Default::default()
+        });
+        // Target ahead on the top edge, one enemy further ahead and one behind the ally
+        game.board.enemies = [(2.0, 0), (4.0, 1), (0.0, 2)]
+            .map(|(position, id)| Enemy {
+                hp: 100,
+                id,
+                position,
+                ..Default::default()
+            })
+            .to_vec();
+        game.strike((0, 1), 0, 10);
+        let hp = game.board.enemies.iter().map(|e| e.hp).collect::<Vec<_>>();
+        assert_eq!(hp, vec![90, 90, 100]);
+
+        // Facing nowhere in particular, the same splash reaches behind
+        game.board.ally_grid[0][1].as_mut().unwrap().facing = None;
+        game.strike((0, 1), 0, 10);
+        let hp = game.board.enemies.iter().map(|e| e.hp).collect::<Vec<_>>();
+        assert_eq!(hp, vec![80, 80, 90]);
+    }
+
+    #[test]
+    fn coin_allies_earn_instead_of_attacking() {
+        let mut game = Game::with_board(Board {
+            ally_grid: vec![vec![None; 7]; 3],
+            enemies: vec![Enemy {
+                hp: 100,
+                position: 1.0,
+                ..Default::default()
+            }],
+            enemy_ready2spawn: Vec::new(),
+        });
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Coin,
+            atk: 10,
+            range: 2,
+            attacks_per_second: 1.0,
+            special_value: 5.0,
+            level: 1,
+            ..Default::default()
+        });
+        let coin = game.coin;
+        // pays once on placement, then every second
+        for _ in 0..150 {
+            game.ally_update();
+        }
+        assert_eq!(game.coin, coin + 15);
+        assert_eq!(game.coins_earned, 15);
+        assert_eq!(game.board.enemies[0].hp, 100);
+    }
+
+    #[test]
+    fn dual_coin_allies_earn_and_attack() {
+        let mut game = Game::with_board(Board {
+            ally_grid: vec![vec![None; 7]; 3],
+            enemies: vec![Enemy {
+                hp: 100,
+                position: 1.0,
+                ..Default::default()
+            }],
+            enemy_ready2spawn: Vec::new(),
+        });
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Basic,
+            second_element: Some(AllyElement::Coin),
+            atk: 10,
+            range: 2,
+            attacks_per_second: 1.0,
+            special_value: 5.0,
+            level: 1,
+            ..Default::default()
+        });
+        let coin = game.coin;
+        game.ally_update();
+        assert_eq!(game.coin, coin + 5);
+        assert!(game.board.enemies[0].hp < 100);
+    }
+
+    #[test]
+    fn heavy_armor_takes_more_from_splash_than_single_hits() {
+        let mut game = Game::new();
+        game.config = Some(game.default_config_file());
+        let heavy = Enemy {
+            hp: 100,
+            armor_type: ArmorType::Heavy,
+            ..Default::default()
+        };
+        let damage_taken = |game: &mut Game, element| {
+            game.board.ally_grid[0][0] = Some(Ally {
+                element,
+                level: 1,
+                ..Default::default()
+            });
+            game.board.enemies = vec![heavy.clone()];
+            game.strike((0, 0), 0, 40);
+            100 - game.board.enemies[0].hp
+        };
+        let single = damage_taken(&mut game, AllyElement::Basic);
+        let splash = damage_taken(&mut game, AllyElement::Aoe);
+        assert_eq!(single, 40);
+        assert_eq!(splash, 50);
+    }
+
+    #[test]
+    fn aura_slows_enemies_in_range_while_placed() {
+        let mut game = Game::with_board(Board {
+            ally_grid: vec![vec![None; 7]; 3],
+            enemies: vec![Enemy {
+                hp: 100,
+                move_speed: 1.0,
+                ..Default::default()
+            }],
+            enemy_ready2spawn: Vec::new(),
+        });
+        game.board.ally_grid[0][0] = Some(Ally {
+            element: AllyElement::Aura,
+            atk: 10,
+            range: 3,
+            attacks_per_second: 1.0,
+            special_value: 1.0,
+            level: 1,
+            ..Default::default()
+        });
+        let step = |game: &mut Game| {
+            let before = game.board.enemies[0].position;
+            game.update();
+            game.board.enemies[0].position - before
+        };
+
+        // One halving from the aura, and no damage since it never attacks
+        assert!((step(&mut game) - 0.5 / 60.0).abs() < 1e-6);
+        assert_eq!(game.board.enemies[0].hp, 100);
+
+        game.board.ally_grid[0][0] = None;
+        assert!((step(&mut game) - 1.0 / 60.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn resisted_and_neutral_matchups() {
+        let mut game = matchup_game();
+        game.board.enemies = vec![Enemy {
+            hp: 100,
+            kind: EnemyKind::Runner,
+            position: 1.0,
+            ..Default::default()
+        }];
+        game.ally_damage((0, 0));
+        assert_eq!(game.board.enemies[0].hp, 100 - 10);
+
+        game.board.enemies = vec![Enemy {
+            hp: 100,
+            position: 1.0,
+            ..Default::default()
+        }];
+        game.ally_damage((0, 0));
+        assert_eq!(game.board.enemies[0].hp, 100 - 20);
+    }
+
+    fn merge_undo_game() -> (Game, Ally, Ally) {
+        let mut game = Game::new();
+        let ally = |atk| Ally {
+            element: AllyElement::Slow,
+            atk,
+            level: 1,
+            levelup_ratio: 1.5,
+            ..Default::default()
+        };
+        let (a, b) = (ally(10), ally(12));
+        game.board.ally_grid[0][0] = Some(a.clone());
+        game.board.ally_grid[1][1] = Some(b.clone());
+        // Keep the wave going so updates keep ticking
+        game.board.enemy_ready2spawn = vec![(enemy_at(0.0), 10_000)];
+        game.game_state = GameState::Running;
+        game.selected = Some((0, 0));
+        game.cursor = (1, 1);
+        game.cursor_select();
+        assert_eq!(game.board.ally_grid[1][1].as_ref().unwrap().level, 2);
+        (game, a, b)
+    }
+
+    #[test]
+    fn undo_restores_both_merged_allies() {
+        let (mut game, a, b) = merge_undo_game();
+        game.update();
+        assert!(game.undo_merge());
+        assert_eq!(game.board.ally_grid[0][0], Some(a));
+        assert_eq!(game.board.ally_grid[1][1], Some(b));
+        assert!(!game.undo_merge());
+    }
+
+    #[test]
+    fn merge_undo_expires() {
+        let (mut game, _, _) = merge_undo_game();
+        for _ in 0..(MERGE_UNDO_SECONDS * 60.0) as usize + 1 {
+            game.update();
+        }
+        assert!(!game.undo_merge());
+
+        let (mut game, _, _) = merge_undo_game();
+        game.buy_ally();
+        assert!(!game.undo_merge());
+    }
+
+    #[test]
+    fn undo_puts_a_moved_ally_back() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            level: 1,
+            ..Default::default()
+        });
+        game.board.enemy_ready2spawn = vec![(enemy_at(0.0), 10_000)];
+        game.game_state = GameState::Running;
+        game.cursor_select();
+        game.cursor = (2, 3);
+        game.cursor_select();
+        assert!(game.board.ally_grid[0][0].is_none());
+        assert!(game.last_merge_undo.is_none());
+
+        game.update();
+        assert!(game.undo_move());
+        assert!(game.board.ally_grid[0][0].is_some());
+        assert!(game.board.ally_grid[2][3].is_none());
+        assert!(!game.undo_move());
+
+        // Any other change to the board drops the pending undo
+        game.cursor = (0, 0);
+        game.cursor_select();
+        game.cursor = (1, 1);
+        game.cursor_select();
+        game.sell_ally((1, 1));
+        assert!(!game.undo_move());
+        assert!(game.board.ally_grid[0][0].is_none());
+    }
+
+    #[test]
+    fn failed_merge_keeps_the_selection_only_when_asked() {
+        let drop_on_mismatch = |keep_selection| {
+            let mut game = Game::new();
+            game.keep_selection_on_failed_merge = keep_selection;
+            let ally = |level| Ally {
+                level,
+                ..Default::default()
+            };
+            game.board.ally_grid[0][0] = Some(ally(1));
+            game.board.ally_grid[0][1] = Some(ally(2));
+            game.cursor_select();
+            game.cursor = (0, 1);
+            game.cursor_select();
+            assert_eq!(game.board.ally_grid[0][0], Some(ally(1)));
+            assert_eq!(game.board.ally_grid[0][1], Some(ally(2)));
+            game.selected
+        };
+
+        assert_eq!(drop_on_mismatch(false), None);
+        assert_eq!(drop_on_mismatch(true), Some((0, 0)));
+    }
+
+    #[test]
+    fn merge_snap_merges_into_a_neighbor_of_the_drop() {
+        let drop_next_to_partner = |merge_snap| {
+            let mut game = Game::new();
+            let mut config = game.default_config_file();
+            config.merge_snap = merge_snap;
+            game.config = Some(config);
+            let ally = Ally {
+                level: 1,
+                levelup_ratio: 1.5,
+                ..Default::default()
+            };
+            game.board.ally_grid[0][0] = Some(ally.clone());
+            game.board.ally_grid[1][2] = Some(ally);
+            game.cursor_select();
+            game.cursor = (1, 1);
+            game.cursor_select();
+            assert_eq!(game.selected, None);
+            assert!(game.board.ally_grid[0][0].is_none());
+            let level = |cell: Option<&Ally>| cell.map(|a| a.level);
+            (
+                level(game.board.ally_grid[1][1].as_ref()),
+                level(game.board.ally_grid[1][2].as_ref()),
+            )
+        };
+        assert_eq!(drop_next_to_partner(true), (None, Some(2)));
+        assert_eq!(drop_next_to_partner(false), (Some(1), Some(1)));
+    }
+
+    #[test]
+    fn auto_merge_on_buy_levels_up_a_matching_ally() {
+        // Same board and seed each time, so every purchase rolls the same ally
+        let buy = |occupant: Ally, auto_merge_on_buy| {
+            let mut game = Game::new();
+            game.rng = ChaCha8Rng::seed_from_u64(7);
+            game.board.ally_grid[2][6] = Some(occupant);
+            game.auto_merge_on_buy = auto_merge_on_buy;
+            game.coin = ALLY_COST;
+            assert!(game.buy_ally());
+            game
+        };
+        let stranger = Ally {
+            level: 99,
+            ..Default::default()
+        };
+        let placed = buy(stranger.clone(), false);
+        let bought = placed
+            .board
+            .ally_grid
+            .iter()
+            .flatten()
+            .flatten()
+            .find(|a| a.level != 99)
+            .unwrap()
+            .clone();
+        assert_eq!(
+            buy(stranger, true)
+                .board
+                .ally_grid
+                .iter()
+                .flatten()
+                .flatten()
+                .count(),
+            2
+        );
+
+        let merged = buy(bought.clone(), true);
+        let allies: Vec<_> = merged.board.ally_grid.iter().flatten().flatten().collect();
+        assert_eq!(allies.len(), 1);
+        assert_eq!(allies[0].level, bought.level + 1);
+        assert!(merged.board.ally_grid[2][6].is_some());
+        // The rolls don't depend on the setting
+        assert_eq!(merged.rng, placed.rng);
+    }
+
+    #[test]
+    fn buying_on_a_full_board_spends_nothing() {
+        let mut game = Game::new();
+        for cell in game.board.ally_grid.iter_mut().flatten() {
+            *cell = Some(Ally::default());
+        }
+        assert!(game.board_is_full());
+        let coin = game.coin;
+        assert!(!game.buy_ally());
+        assert_eq!(game.coin, coin);
+
+        game.board.ally_grid[1][1] = None;
+        assert!(!game.board_is_full());
+        assert!(game.buy_ally());
+        assert_eq!(game.coin, coin - ALLY_COST);
+    }
+
+    #[test]
+    fn buying_several_stops_when_coins_run_out() {
+        let mut game = Game::new();
+        game.coin = 3 * ALLY_COST + 5;
+        assert_eq!(game.buy_allies(5), 3);
+        assert_eq!(game.coin, 5);
+        assert_eq!(game.board.ally_grid.iter().flatten().flatten().count(), 3);
+    }
+
+    #[test]
+    fn chosen_allies_are_of_the_element_asked_for_at_their_price() {
+        let mut game = Game::new();
+        assert_eq!(game.ally_selection_mode(), AllySelectionMode::Random);
+        let mut config = game.default_config_file();
+        config.ally_selection_mode = AllySelectionMode::Choose;
+        config.chosen_ally_cost = Some(25);
+        game.config = Some(config);
+        game.coin = 60;
+        assert_eq!(game.buy_allies_of(AllyElement::Chain, 3), 2);
+        assert_eq!(game.coin, 10);
+        let bought = game.board.ally_grid.iter().flatten().flatten();
+        assert!(bought.map(|a| a.element).eq([AllyElement::Chain; 2]));
+    }
+
+    #[test]
+    fn speeds_in_loops_take_as_long_on_any_path_length() {
+        // Ticks until an enemy of `move_speed` on `branch` walks out
+        let ticks_to_exit = |speed_unit, branch| {
+            let mut game = Game::new();
+            let mut config = game.default_config_file();
+            config.speed_unit = speed_unit;
+            game.init_game_with(config);
+            game.board.enemy_ready2spawn.clear();
+            game.board.enemies = vec![Enemy {
+                hp: 100,
+                move_speed: 0.25,
+                branch,
+                ..enemy_at(0.0)
+            }];
+            game.game_state = GameState::Running;
+            (1..=10_000usize)
+                .find(|_| {
+                    game.update();
+                    game.board.enemies.is_empty()
+                })
+                .unwrap()
+        };
+        let full_loop = ticks_to_exit(SpeedUnit::Loops, None);
+        let branch = ticks_to_exit(SpeedUnit::Loops, Some(PathBranch::Upper));
+        assert!((239..=241).contains(&full_loop));
+        assert!(full_loop.abs_diff(branch) <= 1);
+
+        // In cells, the path half as long takes half the time
+        let full_loop = ticks_to_exit(SpeedUnit::Cells, None);
+        let branch = ticks_to_exit(SpeedUnit::Cells, Some(PathBranch::Upper));
+        assert!(full_loop.abs_diff(2 * branch) <= 2);
+    }
+
+    #[test]
+    fn path_cells_cover_the_perimeter_in_order() {
+        let mut game = Game::new();
+        let cells = game.path_cells();
+        assert_eq!(cells.len(), PATH_LENGTH as usize);
+        assert_eq!(cells[&(0, 0)], 0.0);
+        assert_eq!(cells[&(0, 8)], 8.0 / PATH_LENGTH);
+        assert_eq!(cells[&(1, 0)], 23.0 / PATH_LENGTH);
+        assert!(cells.keys().all(|&(row, col)| row == 0
+            || col == 0
+            || row == BOARD_HEIGHT - 1
+            || col == BOARD_WIDTH - 1));
+
+        // Each branch is measured on its own, eight steps in along either
+        let mut config = game.default_config_file();
+        config.branch_split = Some(0.5);
+        game.init_game_with(config);
+        let cells = game.path_cells();
+        assert_eq!(cells.len(), PATH_LENGTH as usize);
+        assert_eq!(cells[&(4, 4)], cells[&(0, 8)]);
+        assert_eq!(cells[&(4, 8)], 1.0);
+    }
+
+    #[test]
+    fn attacking_a_reflector_hurts_the_attacker() {
+        let mut game = Game::new();
+        let mut config = game.default_config_file();
+        config.reflect_fraction = Some(0.5);
+        game.init_game_with(config);
+        game.board.enemy_ready2spawn.clear();
+        game.board.ally_grid[2][6] = Some(Ally {
+            element: AllyElement::Basic,
+            atk: 40,
+            range: 2,
+            level: 1,
+            attacks_per_second: 1.0,
+            hp: 30,
+            max_hp: 30,
+            ..Default::default()
+        });
+        game.board.enemies = vec![Enemy {
+            hp: 1000,
+            position: PATH_LENGTH / 2.0,
+            ..game.enemy_of_kind(EnemyKind::Reflector)
+        }];
+
+        game.ally_damage((2, 6));
+        assert_eq!(game.board.enemies[0].hp, 960);
+        assert_eq!(game.board.ally_grid[2][6].as_ref().unwrap().hp, 10);
+
+        // The next hit sends back more than it has left
+        game.ally_damage((2, 6));
+        assert!(game.board.ally_grid[2][6].is_none());
+        assert!(game.events.contains(&GameEvent::AllyDestroyed {
+            cell: (2, 6),
+            ally: "Tung Tung Tung Sahur",
+        }));
+
+        // Other kinds reflect nothing
+        game.board.ally_grid[2][6] = Some(game.new_ally(AllyElement::Basic));
+        game.board.enemies = vec![Enemy {
+            hp: 1000,
+            position: PATH_LENGTH / 2.0,
+            ..game.enemy_of_kind(EnemyKind::Grunt)
+        }];
+        game.ally_damage((2, 6));
+        let ally = game.board.ally_grid[2][6].as_ref().unwrap();
+        assert_eq!((ally.hp, ally.max_hp), (DEFAULT_ALLY_HP, DEFAULT_ALLY_HP));
+    }
+
+    #[test]
+    fn building_places_the_chosen_ally_on_the_chosen_cell() {
+        let mut game = Game::new();
+        game.coin = DEFAULT_CHOSEN_ALLY_COST + 5;
+        assert!(game.build_ally_at((1, 3), AllyElement::Slow));
+        assert_eq!(game.coin, 5);
+        let built = game.board.ally_grid[1][3].as_ref().unwrap();
+        assert_eq!((built.element, built.level), (AllyElement::Slow, 1));
+        assert_eq!(game.board.ally_grid.iter().flatten().flatten().count(), 1);
+
+        // Taken cells and short coins spend nothing
+        game.coin = 100;
+        assert!(!game.build_ally_at((1, 3), AllyElement::Dot));
+        assert_eq!(game.coin, 100);
+        game.coin = 1;
+        assert!(!game.build_ally_at((0, 0), AllyElement::Dot));
+        assert!(game.board.ally_grid[0][0].is_none());
+    }
+
+    #[test]
+    fn spawn_delays_follow_the_config() {
+        let spawn_times = |min, max| {
+            let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+            config.spawn_delay_min = Some(min);
+            config.spawn_delay_max = Some(max);
+            let mut game = Game::new_seeded(7);
+            game.config = Some(config);
+            game.enemy_spawn();
+            let mut times = game
+                .board
+                .enemy_ready2spawn
+                .iter()
+                .map(|&(_, time)| time)
+                .collect::<Vec<_>>();
+            times.sort_unstable();
+            times.dedup();
+            times
+        };
+        assert_eq!(spawn_times(30, 30), vec![30]);
+        let spread = spawn_times(0, 600);
+        assert!(spread.len() > 5);
+        assert!(spread.iter().all(|&t| t <= 600));
+    }
+
+    #[test]
+    fn spawn_delay_min_above_max_is_rejected() {
+        let err = ConfigFile::from_toml(
+            "spawn_delay_min = 50\nspawn_delay_max = 10\n[general]\natk = 10",
+        );
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn zero_atk_speed_is_flagged_and_clamped() {
+        let mut config =
+            ConfigFile::from_toml("[general]\natk_speed = 0.0\n[allies.slow]\nrange = 0").unwrap();
+        let problems = config.validate().unwrap_err();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("general.atk_speed"));
+        assert!(problems[1].contains("allies.slow.range"));
+
+        assert_eq!(config.clamp_to_safe(), problems);
+        assert!(config.validate().is_ok());
+        let slow = config.ally_config(AllyElement::Slow);
+        assert_eq!(slow.atk_speed, Some(MIN_ATK_SPEED));
+        assert_eq!(slow.range, Some(1));
+    }
+
+    #[test]
+    fn attack_rates_read_either_way_and_faster_fires_more() {
+        let config = ConfigFile::from_toml(
+            "[general]\natk_speed = 0.5\n[allies.slow]\nattacks_per_second = 4.0",
+        )
+        .unwrap();
+        assert_eq!(
+            config.ally_config(AllyElement::Basic).attacks_per_second(),
+            Some(2.0)
+        );
+        assert_eq!(
+            config.ally_config(AllyElement::Slow).attacks_per_second(),
+            Some(4.0)
+        );
+        assert_eq!(config.ally_config(AllyElement::Slow).atk_speed, None);
+
+        // A huge legacy cooldown can't slow allies past the slowest rate
+        let mut config = ConfigFile::from_toml("[general]\natk_speed = 1000.0").unwrap();
+        assert_eq!(
+            config.ally_config(AllyElement::Basic).attacks_per_second(),
+            Some(MIN_ATTACKS_PER_SECOND)
+        );
+        assert_eq!(config.clamp_to_safe().len(), 1);
+        assert_eq!(
+            config.ally_config(AllyElement::Basic).atk_speed,
+            Some(MAX_ATK_SPEED)
+        );
+
+        let attacks_in_a_second = |attacks_per_second| {
+            let mut game = Game::new();
+            game.board.ally_grid[0][0] = Some(Ally {
+                atk: 1,
+                range: 2,
+                level: 1,
+                attacks_per_second,
+                ..Default::default()
+            });
+            game.board.enemies = vec![Enemy {
+                hp: 1_000,
+                position: 1.0,
+                ..Default::default()
+            }];
+            for _ in 0..60 {
+                game.ally_update();
+            }
+            1_000 - game.board.enemies[0].hp
+        };
+        assert_eq!(attacks_in_a_second(2.0), 2);
+        assert_eq!(attacks_in_a_second(4.0), 4);
+    }
+
+    #[test]
+    fn broken_wave_groups_are_reported_and_skipped() {
+        let mut config = ConfigFile::from_toml(
+            "[general]\n\
+             [[wave]]\nkind = \"Boss\"\ncount = 1\n\
+             [[wave]]\nkind = \"tank\"\ncount = 2\nhp = inf\n\
+             [[wave]]\nkind = \"Runner\"\ncount = 0",
+        )
+        .unwrap();
+        let problems = config.validate().unwrap_err();
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("\"Boss\" is unknown"));
+        assert!(problems[1].contains("wave.hp of tank is inf"));
+        assert!(problems[2].contains("wave.count of Runner is 0"));
+
+        // Spawning from the unchecked groups skips and clamps the same way
+        let mut game = Game::new();
+        game.config = Some(config.clone());
+        game.enemy_spawn();
+        let tank_hp = game.enemy_of_kind(EnemyKind::Tank).hp;
+        let spawned = game
+            .board
+            .enemy_ready2spawn
+            .iter()
+            .map(|(enemy, _)| (enemy.kind, enemy.hp))
+            .collect::<Vec<_>>();
+        assert_eq!(spawned, [(EnemyKind::Tank, tank_hp); 2]);
+
+        config.clamp_to_safe();
+        assert_eq!(config.wave.len(), 1);
+        assert_eq!(config.wave[0].hp, None);
+    }
+
+    #[test]
+    fn shipped_config_is_valid() {
+        let config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn sandbox_spawns_by_hand_and_never_ends() {
+        let mut game = Game::with_mode(GameMode::Sandbox);
+        game.init_game();
+        assert!(game.board.enemy_ready2spawn.is_empty());
+        game.update();
+        assert!(matches!(game.game_state, GameState::Running));
+
+        game.debug_spawn_enemy(EnemyKind::Tank, 12.0);
+        game.update();
+        let tank = &game.board.enemies[0];
+        assert_eq!(tank.kind, EnemyKind::Tank);
+        assert_eq!(tank.armor, 5);
+        assert!(tank.position >= 12.0 && tank.position < 12.1);
+
+        game.lives = 0;
+        game.update();
+        assert!(matches!(game.game_state, GameState::Running));
 
-            // Apply direct damage, with critical hit if applicable
+        let coin = game.coin;
+        game.grant_coins();
+        assert_eq!(game.coin, coin + SANDBOX_COIN_GRANT);
+
+        // Spawns past the end of a branch start on its last cell
+        let mut config = game.default_config_file();
+        config.branch_split = Some(0.5);
+        game.init_game_with(config);
+        game.debug_spawn_enemy(EnemyKind::Grunt, PATH_LENGTH - 1.0);
+        let (enemy, _) = game.board.enemy_ready2spawn.last().unwrap();
+        assert_eq!(enemy.position, BRANCH_LENGTH - 1.0);
+    }
+
+    #[test]
+    fn regeneration_heals_up_to_max_hp() {
+        let mut game = Game::new();
+        game.game_state = GameState::Running;
+        game.board.enemies = vec![Enemy {
+            hp: 50,
+            max_hp: 100,
+            hp_regen: 30.0,
+            ..Default::default()
+        }];
+        for _ in 0..60 {
+            game.update();
+        }
+        assert_eq!(game.board.enemies[0].hp, 80);
+        for _ in 0..120 {
+            game.update();
+        }
+        assert_eq!(game.board.enemies[0].hp, 100);
 
-            enemy.hp = enemy.hp.saturating_sub(damage);
+        // Half a point per second still adds up
+        game.board.enemies[0].hp = 90;
+        game.board.enemies[0].hp_regen = 0.5;
+        for _ in 0..121 {
+            game.update();
         }
+        assert_eq!(game.board.enemies[0].hp, 91);
     }
 
-    fn ally_AOE_damage(&mut self, _pos: (usize, usize)) {
-        let (i, j) = _pos;
-        let ally_position = (j as f32 + 1.0, i as f32 + 1.0);
+    #[test]
+    fn dot_outpaces_low_regeneration() {
+        let mut game = Game::new();
+        game.game_state = GameState::Running;
+        game.board.enemies = vec![Enemy {
+            hp: 100,
+            max_hp: 100,
+            hp_regen: 15.0,
+            dot_list: vec![Debuff {
+                value: 1,
+                cooldown: 1.0,
+            }],
+            ..Default::default()
+        }];
+        // Half a second of 1 damage a tick against a quarter point healed a tick
+        for _ in 0..30 {
+            game.update();
+        }
+        assert_eq!(game.board.enemies[0].hp, 100 - 30 + 7);
+    }
 
-        // Find the nearest enemy within range
-        let mut nearest_enemy_idx: Option<usize> = None;
-        let mut nearest_dist: f32 = f32::MAX;
-        let mut ally_range = 1;
-        let mut ally_atk = 0;
-        let mut first_element = AllyElement::Basic;
-        let mut second_element = None;
+    #[test]
+    fn path_progress_follows_the_furthest_enemy() {
+        let mut game = Game::new();
+        assert_eq!(game.path_progress_max(), 0.0);
+        game.board.enemies = vec![enemy_at(3.0), enemy_at(PATH_LENGTH * 0.75), enemy_at(1.0)];
+        assert_eq!(game.path_progress_max(), 0.75);
+    }
 
-        if let Some(ally) = self.board.ally_grid[i][j].as_ref() {
-            ally_range = ally.range;
-            ally_atk = ally.atk;
-            first_element = ally.element.clone();
-            second_element = ally.second_element.clone();
-        } else {
-            return;
+    #[test]
+    fn auto_buy_keeps_the_threshold_in_reserve() {
+        let mut config = ConfigFile::from_toml(include_str!("../config.toml")).unwrap();
+        // Coin allies it happens to buy would otherwise pay for more
+        config.allies.coin = Some(AllyConfig {
+            special_value: Some(0.0),
+            ..Default::default()
+        });
+        let mut game = Game::new();
+        game.config = Some(config);
+        game.board.enemy_ready2spawn = vec![(enemy_at(0.0), 10_000)];
+        game.game_state = GameState::Running;
+        game.auto_buy = true;
+        game.auto_buy_threshold = 25;
+        game.coin = 25 + 2 * ALLY_COST + 5;
+        for _ in 0..5 {
+            game.update();
         }
+        let allies = game.board.ally_grid.iter().flatten().flatten().count();
+        assert_eq!((allies, game.coin), (2, 30));
+    }
 
-        nearest_enemy_idx = self
-            .board
-            .enemies
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, enemy)| {
-                let enemy_pos = Game::enemy_grid_position(enemy.clone());
-                let dx = ally_position.0 - enemy_pos.0;
-                let dy = ally_position.1 - enemy_pos.1;
-                let dist = (dx * dx + dy * dy).sqrt();
-                if dist <= ally_range as f32 {
-                    Some((idx, dist))
-                } else {
-                    None
-                }
-            })
-            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-            .map(|(idx, _)| idx);
+    #[test]
+    fn selling_one_ally_refunds_it() {
+        let mut game = Game::new();
+        game.board.ally_grid[1][2] = Some(Ally {
+            level: 3,
+            ..Default::default()
+        });
+        game.selected = Some((1, 2));
+        let coin = game.coin;
+        assert_eq!(game.sell_ally((1, 2)), Some(ALLY_COST / 2 * 3));
+        assert_eq!(game.coin, coin + ALLY_COST / 2 * 3);
+        assert_eq!(game.scrap, 2);
+        assert_eq!(game.selected, None);
+        assert_eq!(game.sell_ally((1, 2)), None);
+        assert_eq!(game.scrap, 2);
+    }
 
-        if let Some(enemy_idx) = nearest_enemy_idx {
-            let enemy_pos = {
-                let enemy = &self.board.enemies[enemy_idx];
-                Game::enemy_grid_position(enemy.clone())
-            };
+    #[test]
+    fn starting_allies_are_placed_and_bad_entries_skipped() {
+        let config = ConfigFile::from_toml(
+            r#"
+            [general]
+            atk = 10
+            levelup_ratio = 2.0
 
-            // Prepare damage value (with critical hit if applicable)
-            let mut damage = ally_atk;
-            if first_element == AllyElement::Critical
-                || second_element == Some(AllyElement::Critical)
-            {
-                damage = (damage as f32 * 2.0) as usize;
-            }
+            [[starting_ally]]
+            element = "Slow"
+            level = 3
+            row = 1
+            col = 2
 
-            // For all enemies within aoe_range of the target enemy, apply damage and debuffs
-            let aoe_range = if let Some(ally) = self.board.ally_grid[i][j].as_ref() {
-                ally.aoe_range
-            } else {
-                0
-            };
+            [[starting_ally]]
+            element = "Dot"
+            second_element = "Critical"
+            row = 0
+            col = 0
 
-            for enemy in self.board.enemies.iter_mut() {
-                let pos = Game::enemy_grid_position(enemy.clone());
-                let dx = enemy_pos.0 - pos.0;
-                let dy = enemy_pos.1 - pos.1;
-                let dist = (dx * dx + dy * dy).sqrt();
-                if dist <= aoe_range as f32 {
-                    // Apply debuffs (first and second element, exclude AOE)
-                    match first_element {
-                        AllyElement::Slow => {
-                            enemy.slow_list.push(Debuff {
-                                value: 1,
-                                cooldown: 1.0,
-                            });
-                        }
-                        AllyElement::Dot => {
-                            enemy.dot_list.push(Debuff {
-                                value: 2,
-                                cooldown: 2.0,
-                            });
-                        }
-                        _ => {}
-                    }
-                    if let Some(second) = &second_element {
-                        match second {
-                            AllyElement::Slow => {
-                                enemy.slow_list.push(Debuff {
-                                    value: 1,
-                                    cooldown: 1.0,
-                                });
-                            }
-                            AllyElement::Dot => {
-                                enemy.dot_list.push(Debuff {
-                                    value: 2,
-                                    cooldown: 2.0,
-                                });
-                            }
-                            _ => {}
-                        }
-                    }
+            [[starting_ally]]
+            element = "Basic"
+            row = 3
+            col = 0
 
-                    // Apply damage
-                    enemy.hp = enemy.hp.saturating_sub(damage);
-                }
-            }
-        }
+            [[starting_ally]]
+            element = "Basic"
+            row = 1
+            col = 2
+
+            [[starting_ally]]
+            element = "Aoe"
+            second_element = "Aoe"
+            row = 2
+            col = 6
+            "#,
+        )
+        .unwrap();
+        let mut game = Game::new();
+        game.config = Some(config);
+        game.place_starting_allies();
+
+        let slow = game.board.ally_grid[1][2].as_ref().unwrap();
+        assert_eq!(
+            (slow.element, slow.level, slow.atk),
+            (AllyElement::Slow, 3, 40)
+        );
+        let dual = game.board.ally_grid[0][0].as_ref().unwrap();
+        assert_eq!(
+            (dual.element, dual.second_element),
+            (AllyElement::Dot, Some(AllyElement::Critical))
+        );
+        assert!(game.board.ally_grid[2][6].is_none());
+        assert_eq!(game.board.ally_grid.iter().flatten().flatten().count(), 2);
     }
 
-    fn enemy_update(&mut self) {
-        // Update spawn timers and spawn enemies if ready
-        let mut spawned = Vec::new();
-        for (idx, &mut (_, ref mut timer)) in self.board.enemy_ready2spawn.iter_mut().enumerate() {
-            if *timer > 0 {
-                *timer -= 1;
-            }
-            if *timer == 0 {
-                spawned.push(idx);
+    #[test]
+    fn merged_allies_keep_both_contributions() {
+        let mut game = Game::new();
+        let ally = |damage_dealt, kills| Ally {
+            level: 1,
+            levelup_ratio: 1.5,
+            stats: AllyStats {
+                damage_dealt,
+                kills,
+            },
+            ..Default::default()
+        };
+        let merged = game.ally_merge(ally(100, 3), ally(40, 1)).unwrap();
+        assert_eq!(
+            merged.stats,
+            AllyStats {
+                damage_dealt: 140,
+                kills: 4,
             }
-        }
-        // Spawn enemies whose timers reached 0
-        for &idx in spawned.iter().rev() {
-            let (enemy, _) = self.board.enemy_ready2spawn.remove(idx);
-            self.board.enemies.push(enemy);
-        }
+        );
+    }
 
-        // Update all enemies
-        for enemy in self.board.enemies.iter_mut() {
-            // Apply DOT debuffs
-            let mut dot_damage = 0;
-            enemy.dot_list.retain_mut(|debuff| {
-                if debuff.cooldown > 0.0 {
-                    dot_damage += debuff.value;
-                    debuff.cooldown -= 1.0 / 60.0;
-                    debuff.cooldown > 0.0
-                } else {
-                    false
-                }
-            });
-            if dot_damage > 0 {
-                enemy.hp = enemy.hp.saturating_sub(dot_damage);
+    #[test]
+    fn seeded_games_play_out_the_same() {
+        let run = |seed| {
+            let mut game = Game::new_seeded(seed);
+            game.config = Some(ConfigFile::from_toml(include_str!("../config.toml")).unwrap());
+            game.enemy_spawn();
+            game.coin = 1000;
+            for _ in 0..5 {
+                game.buy_ally();
             }
+            let spawns = game
+                .board
+                .enemy_ready2spawn
+                .iter()
+                .map(|(_, timer)| *timer)
+                .collect::<Vec<_>>();
+            (spawns, game.board.ally_grid)
+        };
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42).0, run(43).0);
+    }
 
-            // Apply slow debuffs
-            let mut slow_factor = 1.0;
-            enemy.slow_list.retain_mut(|debuff| {
-                if debuff.cooldown > 0.0 {
-                    slow_factor *= 0.5_f32.powi(debuff.value as i32);
-                    debuff.cooldown -= 1.0 / 60.0;
-                    debuff.cooldown > 0.0
-                } else {
-                    false
-                }
-            });
+    #[test]
+    fn key_events_are_narrated() {
+        let mut game = Game::new();
+        game.board.enemies = vec![Enemy {
+            hp: 10,
+            move_speed: 1.0,
+            ..enemy_at(PATH_LENGTH - 0.001)
+        }];
+        game.game_state = GameState::Running;
+        game.update();
+        let narration = game
+            .events
+            .iter()
+            .filter_map(GameEvent::narration)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            narration,
+            [
+                format!("Lost a life, {} remaining", STARTING_LIVES - 1),
+                "Victory, all waves cleared".to_string(),
+            ]
+        );
 
-            // Move enemy
-            let move_amount = enemy.move_speed * slow_factor * (1.0 / 60.0);
-            enemy.position += move_amount;
-        }
+        let ally = Ally {
+            level: 3,
+            levelup_ratio: 1.5,
+            ..Default::default()
+        };
+        game.board.ally_grid[1][4] = Some(ally.clone());
+        game.board.ally_grid[1][5] = Some(ally);
+        game.cursor = (1, 4);
+        game.cursor_select();
+        game.cursor = (1, 5);
+        game.cursor_select();
+        assert_eq!(
+            game.events.last().and_then(GameEvent::narration).as_deref(),
+            Some("Ally merged to level 4 at row 2 col 6")
+        );
+    }
 
-        // Remove dead enemies and add coins
-        let dead_count = self
-            .board
-            .enemies
+    #[test]
+    fn kills_and_leaks_make_the_combat_log() {
+        let mut game = Game::new();
+        // Next to the bottom-right corner, out of reach of the exit
+        game.board.ally_grid[2][6] = Some(Ally {
+            atk: 10,
+            range: 2,
+            ..Default::default()
+        });
+        game.board.enemies = vec![
+            Enemy {
+                hp: 5,
+                ..enemy_at(PATH_LENGTH / 2.0)
+            },
+            Enemy {
+                hp: 10,
+                move_speed: 1.0,
+                ..enemy_at(PATH_LENGTH - 0.001)
+            },
+        ];
+        game.game_state = GameState::Running;
+        game.update();
+        let log = game
+            .events
             .iter()
-            .filter(|enemy| enemy.hp == 0)
-            .count();
-        self.coin += dead_count * 10;
-        self.board.enemies.retain(|enemy| enemy.hp > 0);
+            .filter_map(GameEvent::combat_line)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            log,
+            [
+                "Tung Tung Tung Sahur killed a Grunt",
+                "Lost a life!",
+                "Wave 1 cleared",
+                "Victory!",
+            ]
+        );
     }
-    fn state_checkwin(&self) -> bool {
-        self.board.enemy_ready2spawn.is_empty() && self.board.enemies.is_empty()
+
+    #[test]
+    fn damage_accrues_to_the_element_that_dealt_it() {
+        let mut game = Game::new();
+        game.board.ally_grid[2][6] = Some(Ally {
+            element: AllyElement::Critical,
+            atk: 10,
+            range: 2,
+            ..Default::default()
+        });
+        game.board.enemies = vec![Enemy {
+            hp: 15,
+            ..enemy_at(PATH_LENGTH / 2.0)
+        }];
+        game.ally_update();
+        // A critical hit doubles the 10 attack, killing with 15 of it
+        assert_eq!(
+            game.element_stats.get(&AllyElement::Critical),
+            Some(&AllyStats {
+                damage_dealt: 15,
+                kills: 1,
+            })
+        );
+        assert_eq!(game.element_stats.get(&AllyElement::Basic), None);
     }
 
-    fn state_pause() {
-        todo!()
+    #[test]
+    fn seeded_runs_order_enemies_the_same() {
+        let run = |seed| {
+            let mut game = Game::new_seeded(seed);
+            game.config = Some(ConfigFile::from_toml(include_str!("../config.toml")).unwrap());
+            game.enemy_spawn();
+            game.game_state = GameState::Running;
+            (0..600)
+                .map(|_| {
+                    game.update();
+                    game.board.enemies.iter().map(|e| e.id).collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(42), run(42));
     }
 
-    fn state_resume() {
-        todo!()
+    #[test]
+    fn enemies_spawning_on_the_same_tick_line_up_by_id() {
+        let mut game = Game::new();
+        let enemy = |id| Enemy {
+            id,
+            hp: 10,
+            ..Default::default()
+        };
+        game.board.enemy_ready2spawn =
+            vec![(enemy(2), 1), (enemy(0), 1), (enemy(3), 5), (enemy(1), 1)];
+        game.game_state = GameState::Running;
+        game.update();
+        let ids = game.board.enemies.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids, [0, 1, 2]);
+        assert_eq!(game.board.enemy_ready2spawn.len(), 1);
     }
 
-    // Deduct coins and spawn an ally if possible
-    pub fn buy_ally(&mut self) {
-        if self.coin >= 10 {
-            self.coin -= 10;
-            self.ally_spawn();
-        } else {
-            info!(required = 10, current = self.coin, "coin not enough!");
-        }
+    #[test]
+    fn daily_runs_are_ranked_per_day() {
+        assert_eq!(GameMode::Daily.high_score_table(19_723), "daily-2024-01-01");
+        assert_ne!(
+            GameMode::Daily.high_score_table(19_723),
+            GameMode::Daily.high_score_table(19_724)
+        );
+        assert_eq!(GameMode::Endless.high_score_table(19_723), "endless");
+        assert!(GameMode::Daily.is_ranked());
+        assert!(!GameMode::Tutorial.is_ranked());
     }
 
-    // Generate a level 1 ally on a random empty grid
-    fn ally_spawn(&mut self) {
-        let mut empty_cells = Vec::new();
-        for (i, row) in self.board.ally_grid.iter().enumerate() {
-            for (j, cell) in row.iter().enumerate() {
-                if cell.is_none() {
-                    empty_cells.push((i, j));
-                }
-            }
-        }
-        if let Some(&(i, j)) = empty_cells.choose(&mut rand::rng()) {
-            // Randomly pick an AllyElement variant
-            let elements = [
-                AllyElement::Basic,
-                AllyElement::Slow,
-                AllyElement::Aoe,
-                AllyElement::Dot,
-                AllyElement::Critical,
-            ];
-            let element = elements.choose(&mut rand::rng()).unwrap().clone();
-
-            // Get config (fall back to default if not loaded)
-            let config = self
-                .config
-                .as_ref()
-                .map(|c| c.clone())
-                .unwrap_or_else(|| self.load_config());
-            let ally_config = match element {
-                AllyElement::Basic => config.basic.as_ref().unwrap_or(&config.default),
-                AllyElement::Slow => config.slow.as_ref().unwrap_or(&config.default),
-                AllyElement::Aoe => config.aoe.as_ref().unwrap_or(&config.default),
-                AllyElement::Dot => config.dot.as_ref().unwrap_or(&config.default),
-                AllyElement::Critical => config.critical.as_ref().unwrap_or(&config.default),
-            };
+    #[test]
+    fn daily_runs_ignore_the_local_config() {
+        let mut game = Game::with_mode(GameMode::Daily);
+        game.init_game();
+        let config = game.config.as_ref().unwrap();
+        assert!(config.synergies.is_empty());
+        assert!(config.effectiveness.is_empty());
+    }
 
-            let ally = Ally {
-                element,
-                second_element: None,
-                atk: ally_config.atk.unwrap_or(10),
-                range: ally_config.range.unwrap_or(1),
-                aoe_range: ally_config.aoe_range.unwrap_or(0),
-                level: ally_config.level.unwrap_or(1),
-                atk_speed: ally_config.atk_speed.unwrap_or(1.0),
-                attack_cooldown: ally_config.attack_cooldown.unwrap_or(0.0),
-                levelup_ratio: ally_config.levelup_ratio.unwrap_or(1.5),
-                special_value: ally_config.special_value.unwrap_or(1.5),
-            };
-            self.board.ally_grid[i][j] = Some(ally);
+    fn enemy_at(position: f32) -> Enemy {
+        Enemy {
+            position,
+            ..Default::default()
         }
     }
 
-    //if drop a save level on a allay they will levelup
-    // Merge two allies at the given positions (i1, j1) and (i2, j2)
-    pub fn ally_merge(&mut self, ally1: Ally, ally2: Ally) -> Option<Ally> {
-        // Check if levels are the same
-        if ally1.level != ally2.level {
-            return None;
+    #[test]
+    fn seeded_enemies_split_across_branches() {
+        let mut game = Game::new();
+        game.rng = ChaCha8Rng::seed_from_u64(42);
+        let mut config = game.default_config_file();
+        config.branch_split = Some(0.25);
+        game.config = Some(config);
+        for _ in 0..400 {
+            game.queue_enemy(enemy_at(0.0), 0);
         }
+        let upper = game
+            .board
+            .enemy_ready2spawn
+            .iter()
+            .filter(|(enemy, _)| enemy.branch == Some(PathBranch::Upper))
+            .count();
+        assert!(
+            (80..=120).contains(&upper),
+            "{upper} of 400 took the upper branch"
+        );
+        assert!(
+            game.board
+                .enemy_ready2spawn
+                .iter()
+                .all(|(enemy, _)| enemy.branch.is_some())
+        );
+    }
 
-        // To compare AllyElement and Option<AllyElement>, derive PartialEq for AllyElement and Option<AllyElement>
-        // (Already derived via #[derive(Debug,Clone)] for AllyElement, but need PartialEq)
-        // Let's add PartialEq to AllyElement and Option<AllyElement> in the struct definition (not shown here).
+    #[test]
+    fn branches_leave_at_the_bottom_right_corner() {
+        let on = |branch, position| Enemy {
+            hp: 100,
+            branch: Some(branch),
+            ..enemy_at(position)
+        };
+        assert_eq!(
+            Game::enemy_grid_position(&on(PathBranch::Upper, 4.0)),
+            (4.0, 0.0)
+        );
+        assert_eq!(
+            Game::enemy_grid_position(&on(PathBranch::Lower, 4.0)),
+            (0.0, 4.0)
+        );
+        assert_eq!(
+            Game::enemy_grid_position(&on(PathBranch::Upper, 11.5)),
+            (8.0, 3.5)
+        );
+        assert_eq!(
+            Game::enemy_grid_position(&on(PathBranch::Lower, 11.5)),
+            (7.5, 4.0)
+        );
 
-        if ally1.element == ally2.element && ally1.second_element == ally2.second_element {
-            Some(Ally {
-                element: ally1.element.clone(),
-                second_element: None,
-                atk: ((ally1.atk as f32) * ally1.levelup_ratio) as usize,
-                range: ((ally1.range as f32) * ally1.levelup_ratio) as usize,
-                aoe_range: ((ally1.aoe_range as f32) * ally1.levelup_ratio) as usize,
-                level: ally1.level + 1,
-                atk_speed: ally1.atk_speed * ally1.levelup_ratio,
-                attack_cooldown: 0.0,
-                levelup_ratio: ally1.levelup_ratio,
-                special_value: ally1.special_value * ally1.levelup_ratio,
-            })
-        } else if ally1.second_element.is_none() && ally2.second_element.is_none() {
-            // Merge two no second element allies (no upgrade)
-            let (e0, e1) = if ally1.element < ally2.element {
-                (ally1.element.clone(), Some(ally2.element.clone()))
-            } else {
-                (ally2.element.clone(), Some(ally1.element.clone()))
-            };
-            Some(Ally {
-                element: e0,
-                second_element: e1,
-                atk: std::cmp::max(ally1.atk, ally2.atk),
-                range: std::cmp::max(ally1.range, ally2.range),
-                aoe_range: std::cmp::max(ally1.aoe_range, ally2.aoe_range),
-                level: ally1.level,
-                atk_speed: (ally1.atk_speed + ally2.atk_speed) / 2.0,
-                attack_cooldown: 0.0,
-                levelup_ratio: (ally1.levelup_ratio + ally2.levelup_ratio) / 2.0,
-                special_value: (ally1.special_value + ally2.special_value) / 2.0,
-            })
-        } else {
-            None
-        }
+        let mut game = Game::new();
+        game.board.enemies = vec![on(PathBranch::Lower, BRANCH_LENGTH)];
+        game.board.enemy_ready2spawn = vec![(enemy_at(0.0), 10_000)];
+        game.game_state = GameState::Running;
+        game.update();
+        assert!(game.board.enemies.is_empty());
+        assert_eq!(game.lives, STARTING_LIVES - 1);
     }
 
-    //handle cursor movement
-    pub fn cursor_move(&mut self, direction: Direction) {
-        match direction {
-            Direction::Up => {
-                if self.cursor.0 == 0 {
-                    self.cursor.0 = 2;
-                } else {
-                    self.cursor.0 -= 1;
-                }
-            }
-            Direction::Down => {
-                if self.cursor.0 == 2 {
-                    self.cursor.0 = 0;
-                } else {
-                    self.cursor.0 += 1;
-                }
-            }
-            Direction::Left => {
-                if self.cursor.1 == 0 {
-                    self.cursor.1 = 6;
-                } else {
-                    self.cursor.1 -= 1;
-                }
-            }
-            Direction::Right => {
-                if self.cursor.1 == 6 {
-                    self.cursor.1 = 0;
-                } else {
-                    self.cursor.1 += 1;
-                }
-            }
-        }
+    #[test]
+    fn enemy_grid_position_at_corners() {
+        assert_eq!(Game::enemy_grid_position(&enemy_at(0.0)), (0.0, 0.0));
+        assert_eq!(Game::enemy_grid_position(&enemy_at(8.0)), (8.0, 0.0));
+        assert_eq!(Game::enemy_grid_position(&enemy_at(12.0)), (8.0, 4.0));
+        assert_eq!(Game::enemy_grid_position(&enemy_at(20.0)), (0.0, 4.0));
+        assert_eq!(Game::enemy_grid_position(&enemy_at(24.0)), (0.0, 0.0));
     }
 
-    //select a ally if there is a ally at cursor
-    pub fn cursor_select(&mut self) {
-        if self.selected.is_some() {
-            self.cursor_drop();
-            return;
-        }
+    #[test]
+    fn enemy_grid_position_along_edges() {
+        assert_eq!(Game::enemy_grid_position(&enemy_at(3.5)), (3.5, 0.0));
+        assert_eq!(Game::enemy_grid_position(&enemy_at(10.0)), (8.0, 2.0));
+        assert_eq!(Game::enemy_grid_position(&enemy_at(15.0)), (5.0, 4.0));
+        assert_eq!(Game::enemy_grid_position(&enemy_at(22.0)), (0.0, 2.0));
+    }
 
-        let (i, j) = self.cursor;
-        if let Some(Some(_)) = self.board.ally_grid.get(i).and_then(|row| row.get(j)) {
-            self.selected = Some((i, j));
-        } else {
-            self.selected = None;
+    #[test]
+    fn enemy_grid_position_is_continuous_at_joins() {
+        for join in [8.0, 12.0, 20.0, PATH_LENGTH] {
+            let (x0, y0) = Game::enemy_grid_position(&enemy_at(join - 0.001));
+            let (x1, y1) = Game::enemy_grid_position(&enemy_at(join));
+            assert!(
+                (x0 - x1).abs() < 0.01 && (y0 - y1).abs() < 0.01,
+                "jump at {join}"
+            );
         }
     }
 
-    // Drop the selected ally on an empty grid or merge with an ally at the cursor
-    fn cursor_drop(&mut self) {
-        if let Some((sel_i, sel_j)) = self.selected {
-            let (cur_i, cur_j) = self.cursor;
+    #[test]
+    fn enemy_grid_cell_walks_every_perimeter_cell_once() {
+        let cells = (0..PATH_LENGTH as usize)
+            .map(|p| Game::enemy_grid_cell(&enemy_at(p as f32)))
+            .collect::<Vec<_>>();
+        for (row, col) in &cells {
+            assert!(*row < BOARD_HEIGHT && *col < BOARD_WIDTH);
+            assert!(*row == 0 || *row == BOARD_HEIGHT - 1 || *col == 0 || *col == BOARD_WIDTH - 1);
+        }
+        let mut unique = cells.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), cells.len());
+        for pair in cells.windows(2) {
+            let (r0, c0) = pair[0];
+            let (r1, c1) = pair[1];
+            assert_eq!(r0.abs_diff(r1) + c0.abs_diff(c1), 1);
+        }
+    }
 
-            if (sel_i, sel_j) == (cur_i, cur_j) {
-                return;
-            }
-            let ally1 = self.board.ally_grid[sel_i][sel_j].take();
+    proptest! {
+        #[test]
+        fn merge_is_commutative(a in arb_ally(), b in arb_ally()) {
+            let mut game = Game::new();
+            prop_assert_eq!(game.ally_merge(a.clone(), b.clone()), game.ally_merge(b, a));
+        }
 
-            if let Some(ally1) = ally1 {
-                if let Some(Some(ally2)) = self
-                    .board
-                    .ally_grid
-                    .get(cur_i)
-                    .and_then(|row| row.get(cur_j))
-                {
-                    if let Some(merged) = self.ally_merge(ally1.clone(), ally2.clone()) {
-                        // Place merged ally at cursor, clear selected cell
-                        self.board.ally_grid[cur_i][cur_j] = Some(merged);
-                        self.selected = None;
-                    } else {
-                        // Merge failed, return ally1 to its original position
-                        self.board.ally_grid[sel_i][sel_j] = Some(ally1);
-                        // Optionally, keep selection or clear it
-                    }
-                } else {
-                    // No ally at cursor, move selected ally to cursor position
-                    self.board.ally_grid[cur_i][cur_j] = Some(ally1);
-                    self.selected = None;
-                }
-            } else {
-                // No ally at selected position, clear selection
-                self.selected = None;
-            }
+        #[test]
+        fn same_element_merge_raises_level_by_one(a in arb_ally(), b in arb_ally()) {
+            let b = Ally {
+                element: a.element,
+                second_element: a.second_element,
+                level: a.level,
+                ..b
+            };
+            let merged = Game::new().ally_merge(a.clone(), b).expect("same element allies merge");
+            prop_assert_eq!(merged.level, a.level + 1);
+            prop_assert_eq!(merged.element, a.element);
+            prop_assert_eq!(merged.second_element, a.second_element);
         }
-    }
 
-    fn enemy_grid_position(ene: Enemy) -> (f32, f32) {
-        let grid_position: (f32, f32);
-        if ene.position < 8.0 {
-            grid_position = (ene.position as f32, 0.0)
-        } else if ene.position < 12.0 {
-            grid_position = (8.0, ene.position as f32 - 8.0)
-        } else if ene.position < 20.0 {
-            // bottom
-            grid_position = (ene.position as f32 - 12.0, 12.0)
-        } else if ene.position < 24.0 {
-            // left
-            grid_position = (0.0, ene.position as f32 - 20.0)
-        } else {
-            // out of bounds
-            grid_position = (0.0, 0.0)
+        #[test]
+        fn level_mismatch_never_merges(a in arb_ally(), b in arb_ally()) {
+            prop_assume!(a.level != b.level);
+            prop_assert_eq!(Game::new().ally_merge(a, b), Err(MergeError::LevelMismatch));
         }
-        grid_position
-    }
 
-    fn enemy_spawn(&mut self) {
-        use rand::Rng;
-        let mut rng = thread_rng();
-        // Push 10 enemies with random spawn times (0..=100 ticks)
-        for _ in 0..10 {
-            let enemy = Enemy {
-                hp: 100,
-                move_speed: 1.0,
-                position: 0.0,
-                dot_list: Vec::new(),
-                slow_list: Vec::new(),
+        #[test]
+        fn distinct_dual_allies_never_merge(a in arb_ally(), b in arb_ally()) {
+            let b = Ally { level: a.level, ..b };
+            prop_assume!(a.second_element.is_some() || b.second_element.is_some());
+            prop_assume!((a.element, a.second_element) != (b.element, b.second_element));
+            let expected = if a.second_element.is_some() && b.second_element.is_some() {
+                MergeError::IncompatibleElements
+            } else {
+                MergeError::AlreadyDualElement
             };
-            let spawn_time = rng.gen_range(0..=1000);
-            self.board.enemy_ready2spawn.push((enemy, spawn_time));
+            prop_assert_eq!(Game::new().ally_merge(a, b), Err(expected));
+        }
+
+        #[test]
+        fn single_element_allies_combine_without_leveling(a in arb_ally(), b in arb_ally()) {
+            let a = Ally { second_element: None, ..a };
+            let b = Ally { second_element: None, level: a.level, ..b };
+            prop_assume!(a.element != b.element);
+            let merged = Game::new().ally_merge(a.clone(), b.clone()).expect("distinct elements combine");
+            prop_assert_eq!(merged.level, a.level);
+            prop_assert!(merged.second_element.is_some());
+            prop_assert!(Some(merged.element) < merged.second_element);
         }
     }
 }
@@ -0,0 +1,124 @@
+use crate::setup_logging::get_data_dir;
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// How many entries each table keeps.
+const TABLE_SIZE: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub score: u64,
+    pub waves_cleared: usize,
+    /// How long the run lasted, in in-game seconds.
+    #[serde(default)]
+    pub seconds_survived: u64,
+}
+
+/// Best runs, ranked separately per table (e.g. one table per [`crate::game::GameMode`]).
+///
+/// Stored as `highscores.toml` in the data directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    #[serde(default)]
+    tables: BTreeMap<String, Vec<HighScoreEntry>>,
+}
+
+impl HighScores {
+    pub fn path() -> PathBuf {
+        get_data_dir().join("highscores.toml")
+    }
+
+    /// Loads the saved high scores, starting from an empty table if there are none yet or the
+    /// file can't be read.
+    pub fn load() -> HighScores {
+        match std::fs::read_to_string(Self::path()) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|err| {
+                warn!(%err, "invalid high score file, starting over");
+                HighScores::default()
+            }),
+            Err(_) => HighScores::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Records a run in `table`, returning its rank (0 is best) if it made the table.
+    pub fn insert(&mut self, table: &str, entry: HighScoreEntry) -> Option<usize> {
+        let entries = self.tables.entry(table.to_string()).or_default();
+        let rank = entries.partition_point(|e| e.score >= entry.score);
+        if rank >= TABLE_SIZE {
+            return None;
+        }
+        entries.insert(rank, entry);
+        entries.truncate(TABLE_SIZE);
+        Some(rank)
+    }
+
+    /// Entries of `table`, best first.
+    pub fn table(&self, table: &str) -> &[HighScoreEntry] {
+        self.tables
+            .get(table)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn best(&self, table: &str) -> Option<&HighScoreEntry> {
+        self.table(table).first()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: u64) -> HighScoreEntry {
+        HighScoreEntry {
+            score,
+            waves_cleared: score as usize,
+            seconds_survived: score,
+        }
+    }
+
+    #[test]
+    fn tables_are_ranked_independently() {
+        let mut scores = HighScores::default();
+        assert_eq!(scores.insert("campaign", entry(3)), Some(0));
+        assert_eq!(scores.insert("endless", entry(1)), Some(0));
+        assert_eq!(scores.insert("campaign", entry(5)), Some(0));
+        assert_eq!(scores.insert("campaign", entry(4)), Some(1));
+        assert_eq!(scores.table("campaign"), &[entry(5), entry(4), entry(3)]);
+        assert_eq!(scores.best("endless"), Some(&entry(1)));
+        assert_eq!(scores.best("daily"), None);
+    }
+
+    #[test]
+    fn only_the_best_runs_are_kept() {
+        let mut scores = HighScores::default();
+        for score in 1..=TABLE_SIZE as u64 {
+            scores.insert("endless", entry(score));
+        }
+        assert_eq!(scores.insert("endless", entry(0)), None);
+        assert_eq!(scores.insert("endless", entry(100)), Some(0));
+        assert_eq!(scores.table("endless").len(), TABLE_SIZE);
+        assert_eq!(scores.table("endless").last(), Some(&entry(2)));
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut scores = HighScores::default();
+        scores.insert("campaign", entry(7));
+        scores.insert("endless", entry(2));
+        let content = toml::to_string(&scores).unwrap();
+        assert_eq!(toml::from_str::<HighScores>(&content).unwrap(), scores);
+    }
+}
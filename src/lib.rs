@@ -0,0 +1,17 @@
+pub mod app;
+pub mod assets;
+pub mod clipboard;
+pub mod color_cycle;
+pub mod daily;
+pub mod event;
+pub mod export;
+pub mod fx;
+pub mod game;
+pub mod highscore;
+pub mod replay;
+pub mod settings;
+pub mod setup_logging;
+pub mod strings;
+pub mod styling;
+pub mod tutorial;
+pub mod ui;
@@ -1,19 +1,88 @@
-use crate::app::App;
-
-pub mod app;
-pub mod color_cycle;
-pub mod event;
-pub mod fx;
-pub mod game;
-pub mod setup_logging;
-pub mod styling;
-pub mod ui;
+use color_eyre::eyre::{bail, eyre};
+use crossterm::{
+    event::{
+        DisableFocusChange, EnableFocusChange, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+};
+use nycu_gdc_game_jam_0th::app::App;
+use nycu_gdc_game_jam_0th::replay::Replay;
+use std::path::Path;
 
 fn main() -> color_eyre::Result<()> {
-    color_eyre::install()?;
-    crate::setup_logging::initialize_logging()?;
+    install_hooks()?;
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let [flag, rest @ ..] = args.as_slice()
+        && flag == "--verify-replay"
+    {
+        let [path] = rest else {
+            bail!("usage: --verify-replay <file>");
+        };
+        return verify_replay(Path::new(path));
+    }
+    nycu_gdc_game_jam_0th::setup_logging::initialize_logging()?;
     let terminal = ratatui::init();
-    let result = App::new().run(terminal);
+    // Asked before the app starts reading events, the answer would go to its event thread
+    let reports_key_releases = report_key_releases();
+    let mut app = App::new();
+    app.reports_key_releases = reports_key_releases;
+    let result = app.run(terminal);
+    if reports_key_releases {
+        stop_key_releases();
+    }
     ratatui::restore();
+    if let Some(seed) = app.copied_seed {
+        println!("seed of the last run: {seed}");
+    }
     result
 }
+
+/// Asks the terminal to report key releases, and focus changes in case a release gets lost,
+/// which holding an arrow key to repeat it needs. Returns whether the terminal will.
+fn report_key_releases() -> bool {
+    crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false)
+        && execute!(
+            std::io::stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES),
+            EnableFocusChange
+        )
+        .is_ok()
+}
+
+/// Undoes [`report_key_releases`], leaving the shell's keyboard as it was.
+fn stop_key_releases() {
+    let _ = execute!(
+        std::io::stdout(),
+        PopKeyboardEnhancementFlags,
+        DisableFocusChange
+    );
+}
+
+/// Plays the replay at `path` back without a terminal UI, failing with the first tick that
+/// doesn't match the recording.
+fn verify_replay(path: &Path) -> color_eyre::Result<()> {
+    let replay = Replay::load(path)?;
+    let game = replay
+        .verify()
+        .map_err(|divergence| eyre!("{divergence}"))?;
+    println!(
+        "replay matches: {} ticks, score {}",
+        replay.checksums.len(),
+        game.score()
+    );
+    Ok(())
+}
+
+/// Installs color_eyre's error and panic hooks. The panic hook restores the terminal before
+/// printing the report, so a crash doesn't leave the shell in raw mode with the report garbled.
+fn install_hooks() -> color_eyre::Result<()> {
+    let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::default().into_hooks();
+    eyre_hook.install()?;
+    std::panic::set_hook(Box::new(move |panic_info| {
+        stop_key_releases();
+        ratatui::restore();
+        eprintln!("{}", panic_hook.panic_report(panic_info));
+    }));
+    Ok(())
+}
@@ -0,0 +1,316 @@
+//! Recorded runs, played back to check that a seed and the player's inputs always lead to the
+//! same game.
+
+use crate::game::{AllyElement, EnemyKind, Game, GameMode};
+use crate::setup_logging::get_data_dir;
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Version of the [`Replay`] format, bumped whenever it changes.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// A player input that changes the game. Inputs are made between updates and applied with the
+/// cursor where it was at the time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplayAction {
+    Buy {
+        count: usize,
+    },
+    /// Allies of the player's choosing, see [`crate::game::AllySelectionMode::Choose`].
+    BuyOf {
+        element: AllyElement,
+        count: usize,
+    },
+    /// An ally built on a chosen grid cell, see [`Game::build_ally_at`].
+    Build {
+        cell: (usize, usize),
+        element: AllyElement,
+    },
+    ToggleSelection,
+    Sell {
+        cell: (usize, usize),
+    },
+    SellAllOf {
+        element: AllyElement,
+    },
+    Undo,
+    CycleTargeting,
+    RotateAlly,
+    FocusFire,
+    TogglePause,
+    /// The game let run by the tutorial.
+    Resume,
+    SandboxSpawn {
+        kind: EnemyKind,
+        position: f32,
+    },
+    GrantCoins,
+    /// Settings the run plays by, at its start and whenever they change.
+    Settings {
+        auto_buy: bool,
+        auto_buy_threshold: usize,
+        auto_merge_on_buy: bool,
+        #[serde(default)]
+        keep_selection_on_failed_merge: bool,
+    },
+}
+
+impl ReplayAction {
+    pub fn apply(&self, game: &mut Game) {
+        match *self {
+            ReplayAction::Buy { count } => {
+                game.buy_allies(count);
+            }
+            ReplayAction::BuyOf { element, count } => {
+                game.buy_allies_of(element, count);
+            }
+            ReplayAction::Build { cell, element } => {
+                game.build_ally_at(cell, element);
+            }
+            ReplayAction::ToggleSelection => game.cursor_select(),
+            ReplayAction::Sell { cell } => {
+                game.sell_ally(cell);
+            }
+            ReplayAction::SellAllOf { element } => {
+                game.sell_all_of_element(element);
+            }
+            ReplayAction::Undo => {
+                let _ = game.undo_merge() || game.undo_move();
+            }
+            ReplayAction::CycleTargeting => game.cycle_targeting(),
+            ReplayAction::RotateAlly => game.rotate_ally(),
+            ReplayAction::FocusFire => game.focus_fire(),
+            ReplayAction::TogglePause => game.toggle_pause(),
+            ReplayAction::Resume => game.state_resume(),
+            ReplayAction::SandboxSpawn { kind, position } => game.debug_spawn_enemy(kind, position),
+            ReplayAction::GrantCoins => game.grant_coins(),
+            ReplayAction::Settings {
+                auto_buy,
+                auto_buy_threshold,
+                auto_merge_on_buy,
+                keep_selection_on_failed_merge,
+            } => {
+                game.auto_buy = auto_buy;
+                game.auto_buy_threshold = auto_buy_threshold;
+                game.auto_merge_on_buy = auto_merge_on_buy;
+                game.keep_selection_on_failed_merge = keep_selection_on_failed_merge;
+            }
+        }
+    }
+}
+
+/// An action, with when and where it was made.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayInput {
+    /// [`Game::ticks_elapsed`] when it was made, so it's applied before the next update.
+    pub tick: usize,
+    pub cursor: (usize, usize),
+    pub action: ReplayAction,
+}
+
+/// A run as the seed, config and inputs it was played with, and the [`checksum`] of the game
+/// after each of its updates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub mode: GameMode,
+    pub seed: u64,
+    /// Contents of the `config.toml` the run was played with, `None` for the default config.
+    pub config: Option<String>,
+    pub inputs: Vec<ReplayInput>,
+    /// Checksum after each update that advanced the game, the first after tick 1.
+    pub checksums: Vec<u64>,
+}
+
+/// Where a replay first plays out differently than it was recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub tick: usize,
+    pub expected: u64,
+    /// `None` if the game stopped before reaching the tick.
+    pub actual: Option<u64>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.actual {
+            Some(actual) => write!(
+                f,
+                "replay diverged at tick {}: expected checksum {:016x}, got {actual:016x}",
+                self.tick, self.expected
+            ),
+            None => write!(f, "replay ended before tick {}", self.tick),
+        }
+    }
+}
+
+impl Replay {
+    /// An empty recording of `game`, which has to be fresh from [`Game::new_seeded`] or
+    /// [`Game::with_mode`], played with the config in `config`.
+    pub fn new(game: &Game, config: Option<String>) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            mode: game.mode,
+            seed: game.seed,
+            config,
+            inputs: Vec::new(),
+            checksums: Vec::new(),
+        }
+    }
+
+    /// Notes `action`, about to be applied to `game`.
+    pub fn record_input(&mut self, game: &Game, action: ReplayAction) {
+        self.inputs.push(ReplayInput {
+            tick: game.ticks_elapsed,
+            cursor: game.cursor,
+            action,
+        });
+    }
+
+    /// Notes the state of `game` after an update, if the update advanced it.
+    pub fn record_tick(&mut self, game: &Game) {
+        if game.ticks_elapsed > self.checksums.len() {
+            self.checksums.push(checksum(game));
+        }
+    }
+
+    /// A game started like the recorded one, before any input.
+    pub fn start(&self) -> Game {
+        let mut game = Game::new_seeded(self.seed);
+        game.mode = self.mode;
+        let config = match &self.config {
+            Some(content) => game.parse_config(content),
+            None => game.default_config_file(),
+        };
+        game.init_game_with(config);
+        game
+    }
+
+    /// Plays the run back, checking the game after every update against the recording.
+    pub fn verify(&self) -> Result<Game, Divergence> {
+        let mut game = self.start();
+        let mut inputs = self.inputs.iter().peekable();
+        for (i, &expected) in self.checksums.iter().enumerate() {
+            let tick = i + 1;
+            while let Some(input) = inputs.next_if(|input| input.tick < tick) {
+                game.cursor = input.cursor;
+                input.action.apply(&mut game);
+            }
+            game.update();
+            if game.ticks_elapsed < tick {
+                return Err(Divergence {
+                    tick,
+                    expected,
+                    actual: None,
+                });
+            }
+            let actual = checksum(&game);
+            if actual != expected {
+                return Err(Divergence {
+                    tick,
+                    expected,
+                    actual: Some(actual),
+                });
+            }
+        }
+        Ok(game)
+    }
+
+    pub fn load(path: &Path) -> Result<Replay> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the replay to `replays/<mode>-<seed>.json` in the data directory, returning the
+    /// path written.
+    pub fn save(&self) -> Result<PathBuf> {
+        let dir = get_data_dir().join("replays");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}-{}.json", self.mode.name(), self.seed));
+        std::fs::write(&path, serde_json::to_string(self)?)?;
+        Ok(path)
+    }
+}
+
+/// Stable hash of everything an update can change: the board and the run's counters. FNV-1a
+/// over the JSON of the state, so it's the same on every platform and Rust version.
+pub fn checksum(game: &Game) -> u64 {
+    #[derive(Serialize)]
+    struct State<'a> {
+        game: crate::export::GameSnapshot<'a>,
+        selected: Option<(usize, usize)>,
+        coins_earned: usize,
+        priority_target: Option<u64>,
+    }
+    let state = State {
+        game: crate::export::GameSnapshot::of(game),
+        selected: game.selected,
+        coins_earned: game.coins_earned,
+        priority_target: game.priority_target,
+    };
+    let bytes = serde_json::to_vec(&state).expect("game state always serializes");
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A short recorded campaign run: some buys and merges, a pause and a while of fighting.
+    fn recorded_run() -> Replay {
+        let mut game = Game::new_seeded(7);
+        let config = include_str!("../config.toml").to_string();
+        let mut replay = Replay::new(&game, Some(config.clone()));
+        let parsed = game.parse_config(&config);
+        game.init_game_with(parsed);
+        let act = |game: &mut Game, replay: &mut Replay, action: ReplayAction| {
+            replay.record_input(game, action.clone());
+            action.apply(game);
+        };
+        act(&mut game, &mut replay, ReplayAction::Buy { count: 4 });
+        for tick in 0..600 {
+            match tick {
+                100 => act(&mut game, &mut replay, ReplayAction::TogglePause),
+                101 => act(&mut game, &mut replay, ReplayAction::TogglePause),
+                200 => {
+                    game.cursor = (1, 3);
+                    act(&mut game, &mut replay, ReplayAction::ToggleSelection);
+                    game.cursor = (2, 5);
+                    act(&mut game, &mut replay, ReplayAction::ToggleSelection);
+                }
+                300 => act(&mut game, &mut replay, ReplayAction::Buy { count: 2 }),
+                _ => {}
+            }
+            game.update();
+            replay.record_tick(&game);
+        }
+        replay
+    }
+
+    #[test]
+    fn recorded_runs_replay_to_the_same_checksums() {
+        let replay = recorded_run();
+        assert!(replay.checksums.len() > 500);
+        let json = serde_json::to_string(&replay).unwrap();
+        let loaded: Replay = serde_json::from_str(&json).unwrap();
+        assert!(loaded.verify().is_ok());
+    }
+
+    #[test]
+    fn the_first_diverging_tick_is_reported() {
+        let mut replay = recorded_run();
+        replay.checksums[250] ^= 1;
+        replay.checksums[400] ^= 1;
+        let divergence = replay.verify().unwrap_err();
+        assert_eq!(divergence.tick, 251);
+        assert!(divergence.to_string().contains("tick 251"));
+
+        // A different seed goes its own way from the start
+        let mut replay = recorded_run();
+        replay.seed += 1;
+        assert!(replay.verify().unwrap_err().tick < 250);
+    }
+}
@@ -0,0 +1,356 @@
+use crate::game::GameMode;
+use crate::setup_logging::get_data_dir;
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+use tracing::warn;
+
+/// Choices offered for [`Settings::animation_speed`].
+const ANIMATION_SPEEDS: [f32; 4] = [0.5, 1.0, 1.5, 2.0];
+/// Choices offered for [`Settings::auto_buy_threshold`].
+const AUTO_BUY_THRESHOLDS: [usize; 5] = [0, 20, 50, 100, 200];
+/// Choices offered for [`Settings::confirm_sell_level`], `0` being "never ask".
+const CONFIRM_SELL_LEVELS: [usize; 4] = [0, 2, 3, 5];
+/// Choices offered for [`Settings::key_repeat_delay_ms`], `0` being "off".
+const KEY_REPEAT_DELAYS_MS: [u64; 4] = [0, 150, 250, 400];
+/// Choices offered for [`Settings::key_repeat_rate`].
+const KEY_REPEAT_RATES: [u32; 4] = [10, 15, 20, 30];
+
+/// How enemies on the path are drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnemyRenderStyle {
+    /// How many enemies stand in each cell.
+    #[default]
+    Count,
+    /// A glyph per enemy, colored by kind, so the board shows what's coming as well.
+    Glyphs,
+}
+
+impl EnemyRenderStyle {
+    pub fn next(self) -> EnemyRenderStyle {
+        match self {
+            EnemyRenderStyle::Count => EnemyRenderStyle::Glyphs,
+            EnemyRenderStyle::Glyphs => EnemyRenderStyle::Count,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EnemyRenderStyle::Count => "Count",
+            EnemyRenderStyle::Glyphs => "Glyphs",
+        }
+    }
+}
+
+/// Player preferences, edited on the settings screen.
+///
+/// Stored as `settings.toml` in the data directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Skip animated effects, drawing static highlights instead.
+    pub reduce_motion: bool,
+    /// Mode highlighted on the menu at startup.
+    pub default_mode: GameMode,
+    /// Multiplier on how fast animated effects play, `1.0` being their designed pace.
+    pub animation_speed: f32,
+    /// Buy allies automatically during runs.
+    pub auto_buy: bool,
+    /// Coins auto-buy leaves untouched, saved for the player's own purchases.
+    pub auto_buy_threshold: usize,
+    /// Selling an ally of this level or higher asks for confirmation first. `0` never asks.
+    pub confirm_sell_level: usize,
+    /// How enemies on the path are drawn.
+    pub enemy_render_style: EnemyRenderStyle,
+    /// Tint path cells by how many enemies are in them instead of drawing the enemies.
+    pub enemy_density_heatmap: bool,
+    /// Describe key game events in the event log, for players who can't follow the board.
+    pub narrate_events: bool,
+    /// Merge bought allies into a matching one on the board instead of placing them.
+    pub auto_merge_on_buy: bool,
+    /// Keep the dragged ally selected when dropping it onto one it can't merge with.
+    pub keep_selection_on_failed_merge: bool,
+    /// How long an arrow key is held before it starts repeating, in milliseconds. `0` leaves
+    /// repeating to the terminal.
+    pub key_repeat_delay_ms: u64,
+    /// Moves per second of a held arrow key once it repeats.
+    pub key_repeat_rate: u32,
+    /// Color the path cells as a track and the empty ally cells as the ground to build on.
+    pub path_track: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            reduce_motion: false,
+            default_mode: GameMode::Campaign,
+            animation_speed: 1.0,
+            auto_buy: false,
+            auto_buy_threshold: 50,
+            confirm_sell_level: 3,
+            enemy_render_style: EnemyRenderStyle::default(),
+            enemy_density_heatmap: false,
+            narrate_events: false,
+            auto_merge_on_buy: false,
+            keep_selection_on_failed_merge: false,
+            key_repeat_delay_ms: 250,
+            key_repeat_rate: 15,
+            path_track: true,
+        }
+    }
+}
+
+impl Settings {
+    /// Number of rows on the settings screen.
+    pub const LEN: usize = 14;
+
+    /// [`Settings::animation_speed`], kept within a sane range in case the file was edited by
+    /// hand.
+    pub fn animation_speed(&self) -> f32 {
+        if self.animation_speed.is_finite() {
+            self.animation_speed.clamp(0.25, 4.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// `(delay, interval)` of a held arrow key repeating, `None` if it's turned off. The rate
+    /// is capped by the tick rate, which is when repeats happen.
+    pub fn key_repeat(&self) -> Option<(Duration, Duration)> {
+        if self.key_repeat_delay_ms == 0 {
+            return None;
+        }
+        let rate = self.key_repeat_rate.clamp(1, 60);
+        Some((
+            Duration::from_millis(self.key_repeat_delay_ms),
+            Duration::from_secs_f64(1.0 / rate as f64),
+        ))
+    }
+
+    pub fn path() -> PathBuf {
+        get_data_dir().join("settings.toml")
+    }
+
+    /// Loads the saved settings, falling back to the defaults if there are none yet or the file
+    /// can't be read.
+    pub fn load() -> Settings {
+        match std::fs::read_to_string(Self::path()) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|err| {
+                warn!(%err, "invalid settings file, using defaults");
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// `(label, value)` of every row, in display order.
+    pub fn entries(&self) -> [(&'static str, String); Self::LEN] {
+        let on_off = |b: bool| if b { "On" } else { "Off" }.to_string();
+        [
+            ("Reduce motion", on_off(self.reduce_motion)),
+            ("Default mode", self.default_mode.name().to_string()),
+            ("Animation speed", format!("{}x", self.animation_speed())),
+            ("Auto-buy", on_off(self.auto_buy)),
+            (
+                "Auto-buy reserve",
+                format!("{} coins", self.auto_buy_threshold),
+            ),
+            (
+                "Confirm selling from level",
+                match self.confirm_sell_level {
+                    0 => "Never".to_string(),
+                    level => level.to_string(),
+                },
+            ),
+            (
+                "Enemies shown as",
+                self.enemy_render_style.name().to_string(),
+            ),
+            ("Enemy heatmap", on_off(self.enemy_density_heatmap)),
+            ("Narrate events", on_off(self.narrate_events)),
+            ("Merge on buy", on_off(self.auto_merge_on_buy)),
+            (
+                "Keep selection on failed merge",
+                on_off(self.keep_selection_on_failed_merge),
+            ),
+            (
+                "Key repeat delay",
+                match self.key_repeat_delay_ms {
+                    0 => "Off".to_string(),
+                    ms => format!("{ms} ms"),
+                },
+            ),
+            ("Key repeat rate", format!("{}/s", self.key_repeat_rate)),
+            ("Path track", on_off(self.path_track)),
+        ]
+    }
+
+    /// Steps the value of row `index` to its next option.
+    pub fn cycle(&mut self, index: usize) {
+        match index {
+            0 => self.reduce_motion = !self.reduce_motion,
+            1 => {
+                let i = GameMode::ALL
+                    .iter()
+                    .position(|m| *m == self.default_mode)
+                    .unwrap_or_default();
+                self.default_mode = GameMode::ALL[(i + 1) % GameMode::ALL.len()];
+            }
+            2 => {
+                // Hand-edited values snap to the first choice above them
+                self.animation_speed = ANIMATION_SPEEDS
+                    .into_iter()
+                    .find(|&s| s > self.animation_speed)
+                    .unwrap_or(ANIMATION_SPEEDS[0]);
+            }
+            3 => self.auto_buy = !self.auto_buy,
+            4 => {
+                self.auto_buy_threshold = AUTO_BUY_THRESHOLDS
+                    .into_iter()
+                    .find(|&t| t > self.auto_buy_threshold)
+                    .unwrap_or(AUTO_BUY_THRESHOLDS[0]);
+            }
+            5 => {
+                self.confirm_sell_level = CONFIRM_SELL_LEVELS
+                    .into_iter()
+                    .find(|&l| l > self.confirm_sell_level)
+                    .unwrap_or(CONFIRM_SELL_LEVELS[0]);
+            }
+            6 => self.enemy_render_style = self.enemy_render_style.next(),
+            7 => self.enemy_density_heatmap = !self.enemy_density_heatmap,
+            8 => self.narrate_events = !self.narrate_events,
+            9 => self.auto_merge_on_buy = !self.auto_merge_on_buy,
+            10 => self.keep_selection_on_failed_merge = !self.keep_selection_on_failed_merge,
+            11 => {
+                self.key_repeat_delay_ms = KEY_REPEAT_DELAYS_MS
+                    .into_iter()
+                    .find(|&d| d > self.key_repeat_delay_ms)
+                    .unwrap_or(KEY_REPEAT_DELAYS_MS[0]);
+            }
+            12 => {
+                self.key_repeat_rate = KEY_REPEAT_RATES
+                    .into_iter()
+                    .find(|&r| r > self.key_repeat_rate)
+                    .unwrap_or(KEY_REPEAT_RATES[0]);
+            }
+            13 => self.path_track = !self.path_track,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycling_visits_every_option() {
+        let mut settings = Settings::default();
+        settings.cycle(0);
+        assert!(settings.reduce_motion);
+        settings.cycle(0);
+        assert!(!settings.reduce_motion);
+
+        for _ in 0..GameMode::ALL.len() {
+            settings.cycle(1);
+        }
+        assert_eq!(settings.default_mode, Settings::default().default_mode);
+        settings.cycle(1);
+        assert_eq!(settings.default_mode, GameMode::Endless);
+
+        settings.cycle(3);
+        assert!(settings.auto_buy);
+        settings.cycle(4);
+        assert_eq!(settings.auto_buy_threshold, 100);
+        settings.cycle(4);
+        settings.cycle(4);
+        assert_eq!(settings.auto_buy_threshold, 0);
+
+        settings.cycle(5);
+        assert_eq!(settings.confirm_sell_level, 5);
+        settings.cycle(5);
+        assert_eq!(settings.confirm_sell_level, 0);
+        assert_eq!(settings.entries()[5].1, "Never");
+
+        settings.cycle(6);
+        assert_eq!(settings.enemy_render_style, EnemyRenderStyle::Glyphs);
+        settings.cycle(6);
+        assert_eq!(settings.enemy_render_style, EnemyRenderStyle::Count);
+
+        settings.cycle(7);
+        assert!(settings.enemy_density_heatmap);
+        assert_eq!(settings.entries()[7].1, "On");
+
+        settings.cycle(8);
+        assert!(settings.narrate_events);
+
+        settings.cycle(9);
+        assert!(settings.auto_merge_on_buy);
+
+        settings.cycle(10);
+        assert!(settings.keep_selection_on_failed_merge);
+
+        settings.cycle(11);
+        assert_eq!(settings.key_repeat_delay_ms, 400);
+        settings.cycle(11);
+        assert_eq!(settings.entries()[11].1, "Off");
+        settings.cycle(12);
+        assert_eq!(settings.key_repeat_rate, 20);
+
+        settings.cycle(13);
+        assert!(!settings.path_track);
+    }
+
+    #[test]
+    fn key_repeat_turns_off_at_zero_delay() {
+        let mut settings = Settings::default();
+        let (delay, interval) = settings.key_repeat().unwrap();
+        assert_eq!(delay, Duration::from_millis(250));
+        assert!((interval.as_secs_f64() - 1.0 / 15.0).abs() < 1e-9);
+
+        settings.key_repeat_rate = 0;
+        assert_eq!(settings.key_repeat().unwrap().1, Duration::from_secs(1));
+        settings.key_repeat_delay_ms = 0;
+        assert_eq!(settings.key_repeat(), None);
+    }
+
+    #[test]
+    fn animation_speed_cycles_and_stays_in_range() {
+        let mut settings = Settings::default();
+        settings.cycle(2);
+        assert_eq!(settings.animation_speed, 1.5);
+        settings.cycle(2);
+        settings.cycle(2);
+        assert_eq!(settings.animation_speed, 0.5);
+
+        settings.animation_speed = 0.0;
+        assert_eq!(settings.animation_speed(), 0.25);
+        settings.animation_speed = f32::NAN;
+        assert_eq!(settings.animation_speed(), 1.0);
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut settings = Settings::default();
+        settings.cycle(0);
+        settings.cycle(1);
+        let content = toml::to_string(&settings).unwrap();
+        assert_eq!(toml::from_str::<Settings>(&content).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let settings: Settings = toml::from_str("reduce_motion = true").unwrap();
+        assert!(settings.reduce_motion);
+        assert_eq!(settings.default_mode, GameMode::Campaign);
+    }
+}
@@ -0,0 +1,93 @@
+//! Display text of the interface, looked up by key so it can be translated.
+
+use crate::assets;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// English text of every key, shown for keys the language file doesn't translate.
+const ENGLISH: &[(&str, &str)] = &[
+    // Status panel
+    ("status", "Status"),
+    ("mode", "Mode"),
+    ("auto_buy", "Auto-buy"),
+    ("coin", "Coin"),
+    ("currency_symbol", ""),
+    ("board_full", "Board Full"),
+    ("buy", "Buy"),
+    ("wave", "Wave"),
+    ("time", "Time"),
+    ("frenzy", "FRENZY"),
+    ("lives", "Lives"),
+    ("scrap", "Scrap"),
+    ("score", "Score"),
+    ("on_field", "On field"),
+    ("incoming", "Incoming"),
+    ("danger", "Danger"),
+    // Info panel
+    ("map", "Map"),
+    ("combat", "Combat"),
+    ("events", "Events"),
+    // Merge panel
+    ("merge_title", "Merge Italian Brainrot"),
+    ("cant_merge", "Can't merge"),
+];
+
+/// Display text by key, read from `lang.toml` in the asset directory as `key = "text"` pairs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Strings {
+    entries: HashMap<String, String>,
+}
+
+impl Strings {
+    pub fn path() -> PathBuf {
+        assets::asset_dir().join("lang.toml")
+    }
+
+    /// Loads the language file, falling back to English if there is none or it can't be read.
+    pub fn load() -> Strings {
+        match std::fs::read_to_string(Self::path()) {
+            Ok(content) => Strings::from_toml(&content).unwrap_or_else(|err| {
+                warn!(%err, "invalid language file, using English");
+                Strings::default()
+            }),
+            Err(_) => Strings::default(),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Strings, toml::de::Error> {
+        Ok(Strings {
+            entries: toml::from_str(content)?,
+        })
+    }
+
+    /// Display text of `key`: its translation, else its English text, else `key` itself.
+    pub fn t<'a>(&'a self, key: &'a str) -> &'a str {
+        self.entries
+            .get(key)
+            .map(String::as_str)
+            .or_else(|| {
+                ENGLISH
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, text)| *text)
+            })
+            .unwrap_or(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translations_fall_back_to_english_then_the_key() {
+        let strings = Strings::from_toml("coin = \"Monete\"\ncurrency_symbol = \"€\"").unwrap();
+        assert_eq!(strings.t("coin"), "Monete");
+        assert_eq!(strings.t("currency_symbol"), "€");
+        assert_eq!(strings.t("lives"), "Lives");
+        assert_eq!(strings.t("no_such_key"), "no_such_key");
+        assert_eq!(Strings::default().t("currency_symbol"), "");
+        assert!(Strings::from_toml("coin = 3").is_err());
+    }
+}
@@ -78,4 +78,25 @@ impl ExabindTheme for Theme {
     }
 }
 
+/// Colors of the game board.
+pub trait BoardTheme {
+    /// Background of a path cell `progress` of the way from the entrance (`0.0`) to the exit
+    /// (`1.0`): a dim track warming up towards where enemies leak.
+    fn path_track(&self, progress: f32) -> Color;
+    /// Background of an empty ally cell, the ground allies are placed on.
+    fn buildable_cell(&self) -> Style;
+}
+
+impl BoardTheme for Theme {
+    fn path_track(&self, progress: f32) -> Color {
+        COLORS
+            .surface0
+            .lerp(&COLORS.maroon, 0.35 * progress.clamp(0.0, 1.0))
+    }
+
+    fn buildable_cell(&self) -> Style {
+        Style::default().fg(COLORS.surface1).bg(COLORS.mantle)
+    }
+}
+
 const COLORS: Catppuccin = Catppuccin::new();
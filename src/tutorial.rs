@@ -0,0 +1,107 @@
+use crate::event::AppEvent;
+use crate::game::{AllySelectionMode, Game};
+
+/// Steps of the tutorial, in order. Each one waits for the player to do what it asks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TutorialStep {
+    Buy,
+    Select,
+    Merge,
+    Start,
+}
+
+impl TutorialStep {
+    pub const FIRST: TutorialStep = TutorialStep::Buy;
+    pub const COUNT: usize = 4;
+
+    /// Position of the step, counting from 1.
+    pub fn number(&self) -> usize {
+        *self as usize + 1
+    }
+
+    pub fn instruction(&self) -> &'static str {
+        match self {
+            TutorialStep::Buy => "Press Space to buy an ally",
+            TutorialStep::Select => "Move the cursor onto an ally and press Enter to select it",
+            TutorialStep::Merge => {
+                "Move onto a matching ally and press Enter to drop and merge them"
+            }
+            TutorialStep::Start => "Enemies are waiting. Press P to start the wave",
+        }
+    }
+
+    /// Which step follows once `event` has been handled, `None` when the tutorial is over.
+    pub fn advance(self, event: &AppEvent, game: &Game) -> Option<TutorialStep> {
+        let next = match (self, event) {
+            // Choosing opens the element picker first, the ally is only bought from it
+            (TutorialStep::Buy, AppEvent::BuyAlly)
+                if game.ally_selection_mode() == AllySelectionMode::Random =>
+            {
+                TutorialStep::Select
+            }
+            (TutorialStep::Buy, AppEvent::BuyAllyOf(_) | AppEvent::BuildAlly(_)) => {
+                TutorialStep::Select
+            }
+            (TutorialStep::Select, AppEvent::ToggleSelection) if game.selected.is_some() => {
+                TutorialStep::Merge
+            }
+            (TutorialStep::Merge, AppEvent::ToggleSelection)
+                if game.selected.is_none() && game.last_merge_undo.is_some() =>
+            {
+                TutorialStep::Start
+            }
+            (TutorialStep::Start, AppEvent::TogglePause) => return None,
+            _ => self,
+        };
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Ally, AllyElement};
+
+    #[test]
+    fn walks_through_buy_select_merge_and_start() {
+        let mut game = Game::new();
+        let ally = Some(Ally {
+            element: AllyElement::Basic,
+            level: 1,
+            levelup_ratio: 1.5,
+            ..Default::default()
+        });
+        game.board.ally_grid[0][0] = ally.clone();
+        game.board.ally_grid[0][1] = ally;
+
+        let step = TutorialStep::FIRST;
+        let step = step.advance(&AppEvent::ToggleSelection, &game).unwrap();
+        assert_eq!(step, TutorialStep::Buy);
+        let step = step.advance(&AppEvent::BuyAlly, &game).unwrap();
+        assert_eq!(step, TutorialStep::Select);
+
+        game.cursor_select();
+        let step = step.advance(&AppEvent::ToggleSelection, &game).unwrap();
+        assert_eq!(step, TutorialStep::Merge);
+
+        game.cursor = (0, 1);
+        game.cursor_select();
+        let step = step.advance(&AppEvent::ToggleSelection, &game).unwrap();
+        assert_eq!(step, TutorialStep::Start);
+        assert_eq!(step.advance(&AppEvent::TogglePause, &game), None);
+    }
+
+    #[test]
+    fn moving_an_ally_is_not_a_merge() {
+        let mut game = Game::new();
+        game.board.ally_grid[0][0] = Some(Ally {
+            level: 1,
+            ..Default::default()
+        });
+        game.cursor_select();
+        game.cursor = (2, 2);
+        game.cursor_select();
+        let step = TutorialStep::Merge.advance(&AppEvent::ToggleSelection, &game);
+        assert_eq!(step, Some(TutorialStep::Merge));
+    }
+}
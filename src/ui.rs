@@ -1,28 +1,83 @@
-use crate::app::UniqueEffectId;
-use crate::color_cycle::RepeatingColorCycle;
+use crate::app::{DamagePopup, ElementPicker, Focus, PauseMenuEntry, SellOrder, UniqueEffectId};
+use crate::color_cycle::{ClampedColorCycle, RepeatingColorCycle};
+use crate::daily;
 use crate::fx::effect;
 // use crate::fx;
-use crate::game::AllyElement;
-use crate::styling::Catppuccin;
-use crate::{app::App, game::Ally};
-use color_eyre::eyre::{OptionExt, Result};
+use crate::game::{
+    AllyElement, AllyStats, ArmorType, BOARD_HEIGHT, BOARD_WIDTH, Direction, EnemyKind, Game,
+    GameMode, GameState, MergeError, PathBranch, SANDBOX_COIN_GRANT, TargetingMode,
+};
+use crate::settings::EnemyRenderStyle;
+use crate::styling::{BoardTheme, Catppuccin, Theme};
+use crate::tutorial::TutorialStep;
+use crate::{
+    app::App,
+    game::{Ally, Enemy},
+};
+use color_eyre::eyre::Result;
 use ratatui::{
     buffer::Buffer,
-    layout::{Alignment, Constraint, Flex, Layout, Rect},
+    layout::{Alignment, Constraint, Flex, Layout, Position, Rect},
     prelude::StatefulWidget,
     style::{Color, Style, Stylize},
-    text::Line,
-    widgets::{Block, BorderType, Padding, Paragraph, Widget},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, LineGauge, Padding, Paragraph, Widget, Wrap},
 };
 use ratatui_image::{Resize, StatefulImage};
+use std::collections::BTreeMap;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+};
 use tachyonfx::{
-    ColorSpace, Duration, EffectTimer, HslConvertable, Interpolation, Motion, ToRgbComponents, fx,
+    ColorSpace, Duration, Effect, EffectTimer, HslConvertable, Interpolation, Motion,
+    ToRgbComponents, fx,
 };
 use tracing::info;
 use tui_big_text::BigText;
 use tui_logger::TuiLoggerWidget;
 
 const APP_NAME: &str = "Brainrot TD";
+/// Marks path cells holding a stunned enemy.
+const STUN_GLYPH: &str = "✦";
+/// Marks path cells holding an enemy in light armor, weak to single-target hits.
+const LIGHT_ARMOR_GLYPH: &str = "◇";
+/// Marks path cells holding an enemy in heavy armor, weak to splash damage.
+const HEAVY_ARMOR_GLYPH: &str = "◆";
+/// Marks path cells holding a damaged enemy that is regenerating.
+const REGEN_GLYPH: &str = "+";
+/// Draws a reflector, and marks the path cells holding one when enemies are shown as counts.
+const REFLECT_GLYPH: &str = "⊛";
+/// Drawn on the border between two allies whose elements synergize.
+const SYNERGY_GLYPH: &str = "•";
+/// Titles path cells an enemy is about to spawn in.
+const SPAWN_GLYPH: &str = "▼";
+/// Titles the corner enemies leave the board at when the path forks.
+const EXIT_GLYPH: &str = "⇲";
+/// Titles the path cell sandbox spawns land in.
+const SANDBOX_SPAWN_GLYPH: &str = "⊕";
+/// Marks the path cell holding the focus fire target.
+const PRIORITY_GLYPH: &str = "◎";
+/// Marks allies that target enemies they can finish off.
+const SMART_FINISH_GLYPH: &str = "⌖";
+/// Drawn on the bottom border of allies that have had nothing to attack for a while.
+const IDLE_GLYPH: &str = "zzz";
+/// Traces the path of chain attacks.
+const CHAIN_GLYPH: char = 'ϟ';
+/// Most enemy glyphs drawn in one path cell before the rest are counted as `+N`.
+const ENEMY_GLYPH_CAP: usize = 4;
+/// Enemies in one path cell at which the density heatmap is at its hottest.
+const HEATMAP_SATURATION: usize = 8;
+/// Most boss health bars stacked above the board, the rest are summed up on one more row.
+const BOSS_BAR_CAP: usize = 3;
+/// Rows of the stat table compared on the merge panel.
+const ALLY_STAT_ROWS: usize = 6;
+/// Marks allies at the level cap.
+const MAXED_GLYPH: &str = "★";
+/// Leads the badge on the bottom border of dual-element allies.
+const DUAL_GLYPH: &str = "◈";
+/// Smallest board cell drawn, room for its border and one character inside.
+const MIN_CELL_SIZE: u16 = 3;
 
 impl Widget for &mut App {
     /// Renders the user interface widgets.
@@ -33,34 +88,117 @@ impl Widget for &mut App {
     // - https://github.com/ratatui/ratatui/tree/master/examples
     fn render(self, area: Rect, buf: &mut Buffer) {
         match self.mode {
-            crate::app::AppMode::Menu => {
+            crate::app::AppMode::Menu | crate::app::AppMode::Settings => {
+                let [title_area, menu_area] =
+                    Layout::vertical([Constraint::Length(8), Constraint::Fill(1)]).areas(area);
                 let big_text = BigText::builder()
                     .style(Style::new().blue())
                     .lines(vec![APP_NAME.into()])
                     .centered()
                     .build();
-                big_text.render(area, buf);
+                big_text.render(title_area, buf);
+                if self.mode == crate::app::AppMode::Menu {
+                    self.render_menu(menu_area, buf);
+                } else {
+                    self.render_settings(menu_area, buf);
+                }
             }
             crate::app::AppMode::InGame => {
-                let block = Block::bordered()
-                    .title(APP_NAME)
+                let is_paused = matches!(
+                    self.game.as_ref().map(|g| &g.game_state),
+                    Some(GameState::Pause)
+                );
+                let title = if is_paused {
+                    format!("{APP_NAME} (PAUSED)")
+                } else {
+                    APP_NAME.to_string()
+                };
+                let mut block = Block::bordered()
+                    .title(title)
                     .title_alignment(Alignment::Center)
                     .border_type(BorderType::Rounded);
+                if self
+                    .game
+                    .as_ref()
+                    .is_some_and(|g| g.mode == GameMode::Sandbox)
+                {
+                    block = block.title_bottom(
+                        Line::from(vec![
+                            Span::raw(" SANDBOX ").black().on_magenta().bold(),
+                            Span::raw(format!(
+                                " 1-4 spawn grunt/tank/runner/reflector at {SANDBOX_SPAWN_GLYPH} (cell {}) \
+                                 · [ ] move it · C +{SANDBOX_COIN_GRANT} coins ",
+                                self.sandbox_spawn_cell
+                            )),
+                        ])
+                        .centered(),
+                    );
+                }
                 let inner_block = block.inner(area);
                 block.render(area, buf);
 
+                let bosses = self.game.as_ref().map_or(0, |game| {
+                    game.board
+                        .enemies
+                        .iter()
+                        .filter(|e| e.kind.is_boss() && e.hp > 0)
+                        .count()
+                });
+                let [boss_bar_area, game_area] = Layout::vertical([
+                    Constraint::Length(boss_bar_rows(bosses)),
+                    Constraint::Fill(1),
+                ])
+                .areas(inner_block);
+                self.render_boss_bars(boss_bar_area, buf);
+
                 let [left_area, info_panel_area] =
                     Layout::horizontal([Constraint::Ratio(3, 4), Constraint::Fill(1)])
-                        .areas(inner_block);
+                        .areas(game_area);
                 let [grid_area, merge_panel_area] =
                     Layout::vertical([Constraint::Ratio(3, 4), Constraint::Fill(1)])
                         .areas(left_area);
 
                 self.render_grid(grid_area, buf);
                 self.render_info_panel(info_panel_area, buf);
-                self.render_merge_panel(merge_panel_area, buf);
+                match self.inspect {
+                    Some(inspect) => self.render_inspect_panel(inspect.cell, merge_panel_area, buf),
+                    None => self.render_merge_panel(merge_panel_area, buf),
+                }
+                if self.focus == Focus::Grid {
+                    // The grid has no frame of its own, so outline the path around it
+                    self.panel_block(Focus::Grid).render(grid_area, buf);
+                }
+
+                if let Some(step) = self.tutorial {
+                    // Point at the part of the screen the step is about
+                    let highlighted = match step {
+                        TutorialStep::Start => info_panel_area,
+                        _ => grid_area,
+                    };
+                    Block::bordered()
+                        .border_type(BorderType::Thick)
+                        .border_style(Style::new().yellow())
+                        .render(highlighted, buf);
+                    render_tutorial(step, merge_panel_area, buf);
+                }
+
+                if matches!(
+                    self.game.as_ref().map(|g| &g.game_state),
+                    Some(GameState::End)
+                ) {
+                    self.render_game_over(inner_block, buf);
+                } else if let Some(order) = self.pending_sell {
+                    self.render_sell_prompt(order, inner_block, buf);
+                } else if let Some(picker) = self.element_picker {
+                    self.render_element_picker(picker, inner_block, buf);
+                } else if self.is_pause_menu_open() {
+                    self.render_pause_menu(inner_block, buf);
+                }
             }
         }
+        if self.show_perf {
+            self.render_perf_overlay(area, buf);
+        }
     }
 }
 
@@ -69,31 +207,604 @@ impl App {
     //     self.game.and_then(|g| g.selected).map(|sele| {})
     // }
 
+    fn render_menu(&mut self, area: Rect, buf: &mut Buffer) {
+        let today = daily::today();
+        let lines = GameMode::ALL
+            .iter()
+            .map(|mode| {
+                let best = Some(mode)
+                    .filter(|mode| mode.is_ranked())
+                    .and_then(|mode| self.high_scores.best(&mode.high_score_table(today)))
+                    .map(|e| {
+                        format!(
+                            "  (best: {} pts, {} waves)",
+                            format_count(e.score as usize),
+                            e.waves_cleared
+                        )
+                    })
+                    .unwrap_or_default();
+                format!("{}{best}", mode.name())
+            })
+            .chain(["Settings".to_string()])
+            .enumerate()
+            .map(|(i, entry)| {
+                let line = Line::raw(entry);
+                if i == self.menu_index {
+                    line.style(Style::new().black().on_blue())
+                } else {
+                    line
+                }
+            })
+            .chain([
+                Line::raw(""),
+                Line::raw("Up/Down to choose, Enter to start, Q to quit").dark_gray(),
+            ])
+            .collect::<Vec<_>>();
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .render(area, buf);
+    }
+
+    fn render_settings(&mut self, area: Rect, buf: &mut Buffer) {
+        let lines = self
+            .settings
+            .entries()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (label, value))| {
+                let line = Line::raw(format!("{label}: {value}"));
+                if i == self.settings_index {
+                    line.style(Style::new().black().on_blue())
+                } else {
+                    line
+                }
+            })
+            .chain([
+                Line::raw(""),
+                Line::raw("Up/Down to choose, Enter/Left/Right to change, Esc to go back")
+                    .dark_gray(),
+            ])
+            .collect::<Vec<_>>();
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .render(area, buf);
+    }
+
+    fn render_game_over(&mut self, area: Rect, buf: &mut Buffer) {
+        const MVP_HEIGHT: u16 = 12;
+
+        let game = self.game.as_ref().unwrap();
+        let mvp = game.mvp().cloned();
+        let mvp_height = if mvp.is_some() { MVP_HEIGHT } else { 0 };
+        let breakdown = element_breakdown(&game.element_stats);
+        let breakdown_height = breakdown.len() as u16;
+        let title = if game.is_victory() {
+            "Victory!"
+        } else {
+            "Game Over"
+        };
+        let inner_block = render_popup(area, 36, 9 + mvp_height + breakdown_height, title, buf);
+
+        let [summary_area, mvp_area, breakdown_area, hint_area] = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Length(mvp_height),
+            Constraint::Length(breakdown_height),
+            Constraint::Length(2),
+        ])
+        .areas(inner_block);
+        Paragraph::new(breakdown).render(breakdown_area, buf);
+        Paragraph::new(vec![
+            Line::raw(format!("Mode: {}", game.mode.name())),
+            Line::raw(format!("Waves cleared: {}", game.waves_cleared)),
+            Line::raw(format!(
+                "Time survived: {}",
+                format_duration(game.elapsed_seconds())
+            )),
+            Line::raw(format!("Score: {}", format_count(game.score() as usize))),
+            Line::raw(format!("Seed: {}", game.seed)),
+        ])
+        .alignment(Alignment::Center)
+        .render(summary_area, buf);
+        Paragraph::new(vec![
+            Line::raw(""),
+            Line::raw(if self.copied_seed == Some(game.seed) {
+                "Enter: menu · Seed copied"
+            } else {
+                "Enter: menu · C: copy seed"
+            }),
+        ])
+        .alignment(Alignment::Center)
+        .render(hint_area, buf);
+
+        if let Some(mvp) = mvp {
+            let [header_area, ally_area, stats_area] = Layout::vertical([
+                Constraint::Length(2),
+                Constraint::Fill(1),
+                Constraint::Length(1),
+            ])
+            .areas(mvp_area);
+            Paragraph::new(vec![Line::raw(""), Line::raw("MVP").yellow().bold()])
+                .alignment(Alignment::Center)
+                .render(header_area, buf);
+            if let Err(err) = self.render_ally(&mvp, ally_area, buf) {
+                info!(%err, "failed to render mvp avatar");
+            }
+            Paragraph::new(format!(
+                "{} damage, {} kills",
+                format_count(mvp.stats.damage_dealt),
+                format_count(mvp.stats.kills)
+            ))
+            .alignment(Alignment::Center)
+            .render(stats_area, buf);
+        }
+    }
+
+    /// A health bar for each living boss, the one closest to the exit first, up to
+    /// [`BOSS_BAR_CAP`] of them.
+    fn render_boss_bars(&self, area: Rect, buf: &mut Buffer) {
+        let Some(game) = self.game.as_ref() else {
+            return;
+        };
+        let mut bosses = game
+            .board
+            .enemies
+            .iter()
+            .filter(|e| e.kind.is_boss() && e.hp > 0)
+            .collect::<Vec<_>>();
+        bosses.sort_by(|a, b| b.position.total_cmp(&a.position));
+        let rows = Layout::vertical(vec![Constraint::Length(1); area.height as usize]).split(area);
+        for (boss, &row) in bosses.iter().take(BOSS_BAR_CAP).zip(rows.iter()) {
+            LineGauge::default()
+                .label(format!(
+                    "{} {}/{}",
+                    boss.kind.name(),
+                    format_count(boss.hp),
+                    format_count(boss.max_hp.max(boss.hp))
+                ))
+                .ratio(boss_health(boss))
+                .filled_style(Style::new().red())
+                .render(row, buf);
+        }
+        if bosses.len() > BOSS_BAR_CAP
+            && let Some(&row) = rows.get(BOSS_BAR_CAP)
+        {
+            Line::raw(format!("+{} more", bosses.len() - BOSS_BAR_CAP))
+                .dark_gray()
+                .render(row, buf);
+        }
+    }
+
+    /// Frame rate, frame time and what drives their cost, in the top right corner of `area`.
+    fn render_perf_overlay(&self, area: Rect, buf: &mut Buffer) {
+        let frame_secs = self.frame_time.as_secs_f64();
+        let fps = if frame_secs > 0.0 {
+            1.0 / frame_secs
+        } else {
+            0.0
+        };
+        // tachyonfx doesn't count the effects it runs, only the long-lived glows are known
+        let fx = if self.effects.0.is_running() {
+            "running"
+        } else {
+            "idle"
+        };
+        let (enemies, allies) = self.game.as_ref().map_or((0, 0), |game| {
+            let allies = game.board.ally_grid.iter().flatten().flatten().count();
+            (game.board.enemies.len(), allies)
+        });
+        let lines = vec![
+            Line::raw(format!("{fps:.0} fps  {:.1} ms", frame_secs * 1000.0)),
+            Line::raw(format!("fx {fx}, {} glows", self.cooldown_glows.len())),
+            Line::raw(format!("{enemies} enemies  {allies} allies")),
+        ];
+        let width = lines.iter().map(Line::width).max().unwrap_or_default() as u16 + 2;
+        let [_, overlay] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Length(width)]).areas(area);
+        let [overlay, _] = Layout::vertical([
+            Constraint::Length(lines.len() as u16 + 2),
+            Constraint::Fill(1),
+        ])
+        .areas(overlay);
+        Clear.render(overlay, buf);
+        Paragraph::new(lines)
+            .block(Block::bordered().border_style(Style::new().dark_gray()))
+            .render(overlay, buf);
+    }
+
+    fn render_pause_menu(&self, area: Rect, buf: &mut Buffer) {
+        let hint = "Up/Down to choose, Enter to confirm, P to resume";
+        let width = (hint.len() as u16 + 4).min(area.width);
+        let height = PauseMenuEntry::ALL.len() as u16 + 4;
+        let inner = render_popup(area, width, height, "Paused", buf);
+        let lines = PauseMenuEntry::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let line = Line::raw(entry.label());
+                if i == self.pause_menu_index {
+                    line.style(Style::new().black().on_blue())
+                } else {
+                    line
+                }
+            })
+            .chain([Line::raw(""), Line::raw(hint).dark_gray()])
+            .collect::<Vec<_>>();
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .render(inner, buf);
+    }
+
+    /// The elements to buy or build an ally of, each in its color and numbered for its key, with
+    /// the price and the highlighted one on a blue background.
+    fn render_element_picker(&self, picker: ElementPicker, area: Rect, buf: &mut Buffer) {
+        let game = self.game.as_ref().unwrap();
+        let index = picker.index;
+        let hint = "Up/Down and Enter or 1-8 to buy, Esc to cancel";
+        let (title, price) = match picker.build_at {
+            Some((row, col)) => (
+                format!("Build at row {} col {}", row + 1, col + 1),
+                format!("Build for {} coins", game.chosen_ally_cost()),
+            ),
+            None => (
+                "Choose an ally".to_string(),
+                format!(
+                    "{} x{} for {} coins each",
+                    self.strings.t("buy"),
+                    self.counter,
+                    game.chosen_ally_cost()
+                ),
+            ),
+        };
+        let width = (hint.len() as u16 + 4).min(area.width);
+        let height = AllyElement::ALL.len() as u16 + 6;
+        let inner = render_popup(area, width, height, &title, buf);
+        let lines = [Line::raw(price), Line::raw("")]
+            .into_iter()
+            .chain(AllyElement::ALL.iter().enumerate().map(|(i, &element)| {
+                let line = Line::raw(format!("{} {element:?}", i + 1));
+                if i == index {
+                    line.style(Style::new().black().on_blue())
+                } else {
+                    line.fg(ally_element_color(element))
+                }
+            }))
+            .chain([Line::raw(""), Line::raw(hint).dark_gray()])
+            .collect::<Vec<_>>();
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .render(inner, buf);
+    }
+
+    fn render_sell_prompt(&self, order: SellOrder, area: Rect, buf: &mut Buffer) {
+        let level = self.sell_order_level(order).unwrap_or_default();
+        let question = match order {
+            SellOrder::One(_) => format!("Sell level {level} ally? (y/n)"),
+            SellOrder::AllOf(element) => {
+                format!("Sell all {element:?} allies, up to level {level}? (y/n)")
+            }
+        };
+        let width = (question.chars().count() as u16 + 4).min(area.width);
+        let inner = render_popup(area, width, 3, "Sell", buf);
+        Paragraph::new(Line::raw(question).yellow())
+            .alignment(Alignment::Center)
+            .render(inner, buf);
+    }
+
+    /// Readout of board cell `cell` in inspect mode, in place of the merge panel: the stats of
+    /// the ally in an ally cell, or the enemies in a path cell with every debuff they carry and
+    /// the seconds it has left.
+    fn render_inspect_panel(&self, cell: (usize, usize), area: Rect, buf: &mut Buffer) {
+        let game = self.game.as_ref().unwrap();
+        let block = Block::bordered()
+            .border_type(BorderType::Thick)
+            .border_style(Style::new().yellow())
+            .title(format!("Inspect {cell:?}"))
+            .title_bottom(Line::raw("Arrows to move, I or Esc to resume").dark_gray())
+            .padding(Padding::horizontal(2));
+        let inner_block = block.inner(area);
+        block.render(area, buf);
+
+        let is_path =
+            cell.0 == 0 || cell.1 == 0 || cell.0 == BOARD_HEIGHT - 1 || cell.1 == BOARD_WIDTH - 1;
+        let mut lines = Vec::new();
+        if !is_path {
+            match game.ally_in_cell(cell) {
+                Some(ally) => {
+                    lines.push(Line::raw(ally.name()).bold());
+                    lines.extend(ally_stat_lines(ally, ally));
+                    let hp = Line::raw(format!("HP: {}/{}", ally.hp, ally.max_hp));
+                    lines.push(if ally.hp < ally.max_hp {
+                        hp.light_red()
+                    } else {
+                        hp
+                    });
+                    lines.push(Line::raw(format!("Targeting: {}", ally.targeting.name())));
+                    lines.push(
+                        Line::raw(format!(
+                            "Dealt {} damage, {} kills",
+                            format_count(ally.stats.damage_dealt),
+                            format_count(ally.stats.kills)
+                        ))
+                        .dark_gray(),
+                    );
+                }
+                None => lines.push(Line::raw("Empty ally cell").dark_gray()),
+            }
+            Paragraph::new(lines).render(inner_block, buf);
+            return;
+        }
+        for enemy in game.enemies_in_cell(cell) {
+            lines.push(Line::raw(format!(
+                "#{} {} {:?} hp {}/{} at {:.1}",
+                enemy.id,
+                enemy.kind.name(),
+                enemy.armor_type,
+                enemy.hp,
+                enemy.max_hp,
+                enemy.position
+            )));
+            let debuffs = [
+                ("slow", &enemy.slow_list),
+                ("dot", &enemy.dot_list),
+                ("stun", &enemy.stun_list),
+                ("shred", &enemy.armor_shred_list),
+            ];
+            for (name, list) in debuffs {
+                for debuff in list {
+                    lines.push(
+                        Line::raw(format!(
+                            "  {name} {} for {:.2}s",
+                            debuff.value, debuff.cooldown
+                        ))
+                        .dark_gray(),
+                    );
+                }
+            }
+        }
+        if lines.is_empty() {
+            lines.push(Line::raw("No enemies here").dark_gray());
+        }
+        Paragraph::new(lines).render(inner_block, buf);
+    }
+
     fn render_info_panel(&mut self, area: Rect, buf: &mut Buffer) {
-        let [status_panel_area, events_panel_area] =
-            Layout::vertical([Constraint::Max(3 + 2), Constraint::Fill(1)]).areas(area);
+        let [
+            status_panel_area,
+            minimap_area,
+            combat_log_area,
+            events_panel_area,
+        ] = Layout::vertical([
+            Constraint::Max(9 + 2),
+            Constraint::Length(BOARD_HEIGHT as u16 + 2),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ])
+        .areas(area);
         self.render_status_panel(status_panel_area, buf);
+        self.render_minimap(minimap_area, buf);
+        self.render_combat_log(combat_log_area, buf);
         self.render_events_panel(events_panel_area, buf);
     }
 
+    /// The latest lines of the combat log, newest at the bottom.
+    fn render_combat_log(&mut self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered().title(self.strings.t("combat").to_string());
+        let inner_block = block.inner(area);
+        block.render(area, buf);
+        let shown = self.combat_log.len().min(inner_block.height as usize);
+        let lines = self
+            .combat_log
+            .iter()
+            .skip(self.combat_log.len() - shown)
+            .map(|line| Line::raw(line.as_str()))
+            .collect::<Vec<_>>();
+        Paragraph::new(lines).render(inner_block, buf);
+    }
+
+    /// The whole board at a glance: the path, allies in their element colors and live enemies,
+    /// scaled down to fit `area`.
+    fn render_minimap(&mut self, area: Rect, buf: &mut Buffer) {
+        let game = self.game.as_ref().unwrap();
+        let block = Block::bordered().title(self.strings.t("map").to_string());
+        let inner_block = block.inner(area);
+        block.render(area, buf);
+
+        let scale = minimap_scale((BOARD_WIDTH, BOARD_HEIGHT), inner_block);
+        let width = (BOARD_WIDTH as f32 * 2.0 * scale).ceil() as u16;
+        let height = (BOARD_HEIGHT as f32 * scale).ceil() as u16;
+        let [map] = Layout::horizontal([Constraint::Length(width)])
+            .flex(Flex::Center)
+            .areas(inner_block);
+        let [map] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(map);
+        if map.is_empty() {
+            return;
+        }
+
+        // A forked path is drawn as its two branches in their own colors
+        let branches = if game.is_path_forked() {
+            vec![
+                (Some(PathBranch::Upper), Style::new().blue()),
+                (Some(PathBranch::Lower), Style::new().magenta()),
+            ]
+        } else {
+            vec![(None, Style::new().dark_gray())]
+        };
+        for (branch, style) in branches {
+            let enemy = Enemy {
+                branch,
+                ..Default::default()
+            };
+            for position in 0..enemy.path_length() as usize {
+                let on_board = Game::enemy_grid_position(&Enemy {
+                    position: position as f32,
+                    ..enemy.clone()
+                });
+                let (x, y) = minimap_point(on_board, scale, map);
+                buf.set_string(x, y, "·", style);
+            }
+        }
+        let ally_width = ((2.0 * scale) as usize).max(1);
+        for (row, allies) in game.board.ally_grid.iter().enumerate() {
+            for (col, ally) in allies.iter().enumerate() {
+                let Some(ally) = ally else {
+                    continue;
+                };
+                let on_board = (col as f32 + 1.0, row as f32 + 1.0);
+                let (x, y) = minimap_point(on_board, scale, map);
+                let style = Style::new().bg(ally_element_color(ally.element));
+                buf.set_stringn(
+                    x,
+                    y,
+                    " ".repeat(ally_width),
+                    (map.right() - x) as usize,
+                    style,
+                );
+            }
+        }
+        for enemy in &game.board.enemies {
+            let (x, y) = minimap_point(Game::enemy_grid_position(enemy), scale, map);
+            let glyph = enemy_kind_glyph(enemy.kind);
+            buf.set_span(x, y, &glyph, 1);
+        }
+    }
+
     fn render_status_panel(&mut self, area: Rect, buf: &mut Buffer) {
         let game = self.game.as_ref().unwrap();
-        let block = Block::bordered().title("Status");
+        let counts = game.enemy_counts();
+        let s = &self.strings;
+        let block = Block::bordered().title(s.t("status").to_string());
         let inner_block = block.inner(area);
         block.render(area, buf);
+        let [text_area, danger_area] =
+            Layout::vertical([Constraint::Max(8), Constraint::Length(1)]).areas(inner_block);
         Paragraph::new(vec![
-            Line::raw(format!("Coin: {}", game.coin)),
-            Line::raw(format!("Level: {}", game.level)),
+            if game.auto_buy {
+                Line::from(vec![
+                    Span::raw(format!("{}: {}  ", s.t("mode"), game.mode.name())),
+                    Span::raw(s.t("auto_buy").to_string()).green(),
+                ])
+            } else {
+                Line::raw(format!("{}: {}", s.t("mode"), game.mode.name()))
+            },
+            Line::from(vec![
+                Span::raw(format!(
+                    "{}: {}{}  ",
+                    s.t("coin"),
+                    s.t("currency_symbol"),
+                    format_count(game.coin)
+                )),
+                reward_multiplier_span(game.reward_multiplier()),
+                if game.board_is_full() {
+                    Span::raw(s.t("board_full").to_string()).red().bold()
+                } else {
+                    Span::raw(format!("{} x{}", s.t("buy"), self.counter)).dark_gray()
+                },
+            ]),
+            Line::from(vec![
+                Span::raw(format!(
+                    "{}: {}  {}: {}  ",
+                    s.t("wave"),
+                    game.level,
+                    s.t("time"),
+                    format_duration(game.elapsed_seconds())
+                )),
+                if game.frenzy_multiplier() > 1.0 {
+                    Span::raw(s.t("frenzy").to_string()).red().bold()
+                } else {
+                    Span::raw("")
+                },
+            ]),
             Line::raw(format!(
-                "Remain Enemy: {}",
-                game.board.enemy_ready2spawn.len()
+                "{}: {}  {}: {}",
+                s.t("lives"),
+                game.lives,
+                s.t("scrap"),
+                format_count(game.scrap)
             )),
+            Line::raw(format!(
+                "{}: {}",
+                s.t("score"),
+                format_count(game.score() as usize)
+            )),
+            Line::raw(format!(
+                "{}: {}",
+                s.t("on_field"),
+                format_count(counts.on_field)
+            )),
+            Line::raw(format!(
+                "{}: {}",
+                s.t("incoming"),
+                format_count(counts.incoming)
+            )),
+            Line::raw(
+                EnemyKind::ALL
+                    .iter()
+                    .zip(counts.by_kind)
+                    // Only `[[wave]]` groups spawn reflectors, so they're left out until some do
+                    .filter(|&(&kind, count)| kind != EnemyKind::Reflector || count > 0)
+                    .map(|(kind, count)| format!("{} {}", kind.name(), format_count(count)))
+                    .collect::<Vec<_>>()
+                    .join(" · "),
+            )
+            .dark_gray(),
         ])
-        .render(inner_block, buf);
+        .render(text_area, buf);
+
+        // How close the leading enemy is to leaking
+        let danger = game.path_progress_max();
+        let danger_color = match danger {
+            d if d >= 0.8 => Color::Red,
+            d if d >= 0.5 => Color::Yellow,
+            _ => Color::Green,
+        };
+        LineGauge::default()
+            .label(s.t("danger").to_string())
+            .ratio(danger as f64)
+            .filled_style(Style::new().fg(danger_color))
+            .render(danger_area, buf);
+
+        // Flash the coin line on bounties and failed buys, and the lives line on lifesteal
+        let flashes = [
+            (std::mem::take(&mut self.is_coin_gained), 1, Color::Yellow),
+            (std::mem::take(&mut self.is_buy_rejected), 1, Color::Red),
+            (std::mem::take(&mut self.is_life_restored), 3, Color::Green),
+        ];
+        if self.settings.reduce_motion {
+            return;
+        }
+        let duration = (800.0 / self.settings.animation_speed()) as u32;
+        for (is_due, line, color) in flashes {
+            if !is_due {
+                continue;
+            }
+            let line_area = Rect {
+                y: inner_block.y + line,
+                height: 1,
+                ..inner_block
+            }
+            .intersection(inner_block);
+            let fx =
+                fx::fade_from_fg(color, (duration, Interpolation::QuadOut)).with_area(line_area);
+            self.effects.0.add_effect(fx);
+        }
+    }
+
+    /// Border of a panel, highlighted while it has the keyboard focus.
+    fn panel_block(&self, panel: Focus) -> Block<'static> {
+        let block = Block::bordered();
+        if self.focus == panel {
+            block.border_style(Style::new().cyan())
+        } else {
+            block
+        }
     }
 
     fn render_events_panel(&mut self, area: Rect, buf: &mut Buffer) {
-        let block = Block::bordered().title("Events");
+        let block = self
+            .panel_block(Focus::Events)
+            .title(self.strings.t("events").to_string());
         let inner_block = block.inner(area);
         block.render(area, buf);
         TuiLoggerWidget::default()
@@ -102,8 +813,9 @@ impl App {
     }
 
     fn render_merge_panel(&mut self, area: Rect, buf: &mut Buffer) {
-        let block = Block::bordered()
-            .title("Merge Italian Brainrot")
+        let block = self
+            .panel_block(Focus::Shop)
+            .title(self.strings.t("merge_title").to_string())
             .padding(Padding::horizontal(2));
         let inner_block = block.inner(area);
         block.render(area, buf);
@@ -137,18 +849,44 @@ impl App {
         };
         match (selected_ally, hovered_ally) {
             (Some(lhs), Some(rhs)) => {
-                self.render_ally(&lhs, ally_lhs, buf)
+                // Stats of both inputs under their avatars, to weigh merging against moving
+                let stat_rows = Constraint::Length(ALLY_STAT_ROWS as u16);
+                let [lhs_avatar, lhs_stats] =
+                    Layout::vertical([Constraint::Fill(1), stat_rows]).areas(ally_lhs);
+                let [rhs_avatar, rhs_stats] =
+                    Layout::vertical([Constraint::Fill(1), stat_rows]).areas(ally_rhs);
+                self.render_ally(&lhs, lhs_avatar, buf)
                     .expect("failed to render lhs ally");
-                self.render_ally(&rhs, ally_rhs, buf)
+                self.render_ally(&rhs, rhs_avatar, buf)
                     .expect("failed to render lhs ally");
-                if let Some(output) = self
-                    .game
-                    .as_mut()
-                    .unwrap()
-                    .ally_merge(lhs.clone(), rhs.clone())
-                {
-                    self.render_ally(&output, ally_output, buf)
-                        .expect("failed to render output ally");
+                Paragraph::new(ally_stat_lines(&lhs, &rhs)).render(lhs_stats, buf);
+                Paragraph::new(ally_stat_lines(&rhs, &lhs)).render(rhs_stats, buf);
+
+                let game = self.game.as_mut().unwrap();
+                match game.ally_merge(lhs.clone(), rhs.clone()) {
+                    Ok(output) => self
+                        .render_ally(&output, ally_output, buf)
+                        .expect("failed to render output ally"),
+                    Err(err) => {
+                        let [note] = Layout::vertical([Constraint::Length(2)])
+                            .flex(Flex::Center)
+                            .areas(ally_output);
+                        let note_line = match err {
+                            MergeError::MaxLevel => {
+                                Line::raw(format!("{MAXED_GLYPH} {}", err.reason())).yellow()
+                            }
+                            _ => Line::raw(format!(
+                                "{}: {}",
+                                self.strings.t("cant_merge"),
+                                err.reason()
+                            ))
+                            .dark_gray(),
+                        };
+                        Paragraph::new(note_line)
+                            .alignment(Alignment::Center)
+                            .wrap(Wrap { trim: true })
+                            .render(note, buf);
+                    }
                 }
             }
             (Some(lhs), None) | (None, Some(lhs)) => {
@@ -160,18 +898,26 @@ impl App {
     }
 
     fn render_ally(&mut self, ally: &Ally, area: Rect, buf: &mut Buffer) -> Result<()> {
+        let mut caption = vec![Line::raw(ally.name())];
+        if ally.has_element(AllyElement::Aoe) {
+            caption.push(Line::raw(format!("Splash: {}", ally.aoe_center.name())).dark_gray());
+        }
         let [avatar_rect, name_rect] =
-            Layout::vertical([Constraint::Fill(1), Constraint::Max(1)]).areas(area);
-        let ally_image = self
-            .image_repository
-            .get_mut(ally.avatar_path())
-            .ok_or_eyre("failed to get ally image")?;
-        let [avatar_rect_mid] = Layout::horizontal([Constraint::Length(16)])
-            .flex(Flex::Center)
-            .areas(avatar_rect);
-        let image = StatefulImage::new().resize(Resize::Fit(None));
-        image.render(avatar_rect_mid, buf, &mut ally_image.0);
-        Paragraph::new(ally.name())
+            Layout::vertical([Constraint::Fill(1), Constraint::Max(caption.len() as u16)])
+                .areas(area);
+        // Nothing fits, and images and effects don't take well to an empty area
+        if area.is_empty() {
+            return Ok(());
+        }
+        // Allies without an avatar image yet are shown by name only
+        if let Some(ally_image) = self.image_repository.get_mut(ally.avatar_path()) {
+            let [avatar_rect_mid] = Layout::horizontal([Constraint::Length(16)])
+                .flex(Flex::Center)
+                .areas(avatar_rect);
+            let image = StatefulImage::new().resize(Resize::Fit(None));
+            image.render(avatar_rect_mid, buf, &mut ally_image.0);
+        }
+        Paragraph::new(caption)
             .bg(Color::Black)
             .alignment(Alignment::Center)
             .render(name_rect, buf);
@@ -181,36 +927,33 @@ impl App {
     fn render_grid(&mut self, grid_area: Rect, buf: &mut Buffer) {
         let game = self.game.as_ref().unwrap();
 
-        const GRID_WIDTH: usize = 9;
-        const GRID_HEIGHT: usize = 5;
+        const GRID_WIDTH: usize = BOARD_WIDTH;
+        const GRID_HEIGHT: usize = BOARD_HEIGHT;
 
-        let row_constraints = vec![Constraint::Max(10); GRID_HEIGHT];
-        let grid = Layout::vertical(row_constraints)
-            .split(grid_area)
-            .iter()
-            .map(|&a| {
-                let col_constrains = vec![Constraint::Max(20); GRID_WIDTH];
-                Layout::horizontal(col_constrains).split(a).to_vec()
-            })
-            .collect::<Vec<_>>();
-        assert_eq!(GRID_HEIGHT, grid.len());
-        assert_eq!(GRID_WIDTH, grid[0].len());
+        let Some(grid) = grid_cells(grid_area) else {
+            render_too_small(grid_area, buf);
+            return;
+        };
+
+        // Board effects that never end only play while the game runs, so the frame rate can idle
+        // while it stands still
+        let is_animated =
+            !self.settings.reduce_motion && matches!(game.game_state, GameState::Running);
 
-        if self.is_selection_updated {
+        let is_selection_animated = is_animated && game.selected.is_some();
+        if self.is_selection_updated || is_selection_animated != self.is_selection_animated {
             self.is_selection_updated = false;
+            self.is_selection_animated = is_selection_animated;
 
-            if let Some((sele_y, sele_x)) = game.selected {
-                let sele_cell = grid[sele_y + 1][sele_x + 1].clone();
-                self.effects.0.add_unique_effect(
-                    UniqueEffectId::Selected,
-                    effect::selected_category(Color::Cyan, sele_cell.clone()),
-                );
-            } else {
-                self.effects.0.unique(
-                    UniqueEffectId::Selected,
-                    effect::selected_category(Color::Cyan, Rect::ZERO),
-                );
-            }
+            let fx = match game.selected.filter(|_| is_selection_animated) {
+                Some((sele_y, sele_x)) => {
+                    effect::selected_category(Color::Cyan, grid[sele_y + 1][sele_x + 1])
+                }
+                None => fx::sleep(0),
+            };
+            self.effects
+                .0
+                .add_unique_effect(UniqueEffectId::Selected, fx);
         }
 
         // render all cells first
@@ -227,44 +970,198 @@ impl App {
         for row_i in 1..GRID_HEIGHT - 1 {
             for col_i in 1..GRID_WIDTH - 1 {
                 let ally = &game.board.ally_grid[row_i - 1][col_i - 1];
-                let text = match ally {
+                let is_maxed = ally.as_ref().is_some_and(|a| game.is_maxed(a));
+                let mut text = match ally {
+                    Some(a) if is_maxed => format!("{MAXED_GLYPH}{}", a.level),
                     Some(a) => a.level.to_string(),
                     None => "".to_string(),
                 };
+                if ally
+                    .as_ref()
+                    .is_some_and(|a| a.targeting == TargetingMode::SmartFinish)
+                {
+                    text.push_str(SMART_FINISH_GLYPH);
+                }
+                if let Some(facing) = ally.as_ref().and_then(|a| a.facing) {
+                    text.push(facing_arrow(facing));
+                }
 
-                let style = calculate_ally_style(ally);
-                let block = Block::bordered().style(style);
+                let style = match ally {
+                    None if self.settings.path_track => Theme.buildable_cell(),
+                    _ => calculate_ally_style(ally),
+                };
+                let block = if is_maxed {
+                    Block::bordered()
+                        .border_type(BorderType::Double)
+                        .border_style(Style::new().yellow())
+                        .style(style)
+                } else {
+                    Block::bordered().style(style)
+                };
                 let p = Paragraph::new(text)
                     .block(block)
                     .alignment(Alignment::Center);
 
                 let rect = grid[row_i][col_i].clone();
                 p.render(rect, buf);
+
+                if ally.as_ref().is_some_and(|a| game.is_idle(a)) {
+                    buf.set_string(
+                        rect.x + 1,
+                        rect.bottom().saturating_sub(1),
+                        IDLE_GLYPH,
+                        Style::new().dark_gray(),
+                    );
+                }
+
+                // Dual allies are told apart by a static badge too, as their color cycle is
+                // skipped with reduce motion on
+                if let Some(badge) = ally.as_ref().and_then(dual_badge) {
+                    let width = badge.width() as u16;
+                    buf.set_line(
+                        rect.right().saturating_sub(width + 1).max(rect.x),
+                        rect.bottom().saturating_sub(1),
+                        &badge,
+                        width,
+                    );
+                }
+
+                if self.show_coordinates {
+                    // ally_grid index, on the top border
+                    buf.set_string(
+                        rect.x + 1,
+                        rect.y,
+                        format!("{},{}", row_i - 1, col_i - 1),
+                        Style::new().dark_gray(),
+                    );
+                }
+            }
+        }
+
+        // mark synergizing neighbors on their shared border
+        for link in game.synergy_links() {
+            let ((i, j), (ni, _)) = (link.from, link.to);
+            let cell = grid[i + 1][j + 1];
+            let position = if ni == i {
+                (cell.right() - 1, cell.y + cell.height / 2)
+            } else {
+                (cell.x + cell.width / 2, cell.bottom() - 1)
+            };
+            if let Some(c) = buf.cell_mut(position) {
+                c.set_symbol(SYNERGY_GLYPH).set_fg(Color::Yellow);
             }
         }
 
-        // update fx
-        if self.is_ally_updated {
-            self.is_ally_updated = false;
-            for row_i in 1..GRID_HEIGHT - 1 {
-                for col_i in 1..GRID_WIDTH - 1 {
-                    let ally = &game.board.ally_grid[row_i - 1][col_i - 1];
-                    if let Some((e0, e1)) = ally
+        // cycle the colors of allies of two elements, issued again whenever the allies change
+        let is_ally_updated = std::mem::take(&mut self.is_ally_updated);
+        if is_ally_updated || !is_animated {
+            self.effects
+                .stop_element_cycles(self.element_cycles.drain());
+        }
+        if is_animated {
+            let speed = self.settings.animation_speed();
+            for (row_i, row) in game.board.ally_grid.iter().enumerate() {
+                for (col_i, ally) in row.iter().enumerate() {
+                    let Some((e0, e1)) = ally
                         .as_ref()
                         .and_then(|a| a.second_element.map(|e1| (a.element, e1)))
-                    {
-                        let c0 = ally_element_color(e0);
-                        let c1 = ally_element_color(e1);
-                        let rect = grid[row_i][col_i].clone();
-                        let fx =
-                            effect::color_cycle_bg(mixed_element_color(c0, c1, 3), 66, |_| true)
-                                .with_area(rect);
-                        self.effects.0.add_effect(fx);
+                    else {
+                        if self.element_cycles.remove(&(row_i, col_i)) {
+                            self.effects.stop_element_cycles([(row_i, col_i)]);
+                        }
+                        continue;
+                    };
+                    if !self.element_cycles.insert((row_i, col_i)) {
+                        continue;
                     }
+                    let c0 = ally_element_color(e0);
+                    let c1 = ally_element_color(e1);
+                    let step_duration = (66.0 / speed) as u32;
+                    let fx = effect::color_cycle_bg(
+                        mixed_element_color(c0, c1, 3, speed),
+                        step_duration,
+                        |_| true,
+                    )
+                    .with_area(grid[row_i + 1][col_i + 1]);
+                    self.effects
+                        .0
+                        .add_unique_effect(UniqueEffectId::ElementCycle(row_i, col_i), fx);
+                }
+            }
+        }
+
+        // glow allies brighter as their attack comes off cooldown. Issued again after the element
+        // cycles above so they blend over them
+        if is_ally_updated || !is_animated {
+            self.effects
+                .stop_cooldown_glows(self.cooldown_glows.drain().map(|(cell, _)| cell));
+        }
+        if is_animated {
+            let flash_duration = (150.0 / self.settings.animation_speed()) as u32;
+            for (row_i, row) in game.board.ally_grid.iter().enumerate() {
+                for (col_i, ally) in row.iter().enumerate() {
+                    let Some(ally) = ally else {
+                        if self.cooldown_glows.remove(&(row_i, col_i)).is_some() {
+                            self.effects.stop_cooldown_glows([(row_i, col_i)]);
+                        }
+                        continue;
+                    };
+                    let readiness =
+                        self.cooldown_glows
+                            .entry((row_i, col_i))
+                            .or_insert_with(|| {
+                                let readiness =
+                                    Arc::new(AtomicU32::new(ally.readiness().to_bits()));
+                                let colors = cooldown_glow_colors(ally_element_color(ally.element));
+                                let fx = effect::cooldown_glow(
+                                    colors,
+                                    readiness.clone(),
+                                    flash_duration,
+                                )
+                                .with_area(grid[row_i + 1][col_i + 1]);
+                                self.effects.0.add_unique_effect(
+                                    UniqueEffectId::CooldownGlow(row_i, col_i),
+                                    fx,
+                                );
+                                readiness
+                            });
+                    readiness.store(ally.readiness().to_bits(), Ordering::Relaxed);
                 }
             }
         }
 
+        // pulse the allies that attacked, so idle ones stand out, and animate their attacks
+        if !self.attacks.is_empty() {
+            let duration = (150.0 / self.settings.animation_speed()) as u32;
+            for ((row_i, col_i), (target_y, target_x)) in self.attacks.drain(..) {
+                let Some(element) = game.board.ally_grid[row_i][col_i]
+                    .as_ref()
+                    .map(|a| a.element)
+                else {
+                    continue;
+                };
+                let ally_cell = grid[row_i + 1][col_i + 1];
+                let target_cell = grid[target_y][target_x];
+                let fx = fx::fade_from_fg(Color::White, (duration, Interpolation::QuadOut))
+                    .with_area(ally_cell);
+                self.effects.0.add_effect(fx);
+                for fx in attack_effects(element, ally_cell, target_cell, duration) {
+                    self.effects.0.add_effect(fx);
+                }
+            }
+        }
+        // connect the enemies a chain attack bounced between
+        if !self.bounces.is_empty() {
+            let duration = (75.0 / self.settings.animation_speed()) as u32;
+            let color = ally_element_color(AllyElement::Chain);
+            for ((from_y, from_x), (to_y, to_x)) in self.bounces.drain(..) {
+                let from = cell_center(grid[from_y][from_x]);
+                let to = cell_center(grid[to_y][to_x]);
+                let fx = effect::projectile(CHAIN_GLYPH, color, from, to, true, duration);
+                self.effects.0.add_effect(fx);
+            }
+        }
+
         // render enemies
         let grid_indices = (0..GRID_WIDTH)
             .map(|x| (0, x))
@@ -272,30 +1169,309 @@ impl App {
             .chain((0..GRID_WIDTH - 1).rev().map(|x| (GRID_HEIGHT - 1, x)))
             .chain((1..GRID_HEIGHT - 1).rev().map(|y| (y, 0)))
             .collect::<Vec<_>>();
-        let mut counts = [[0; GRID_WIDTH]; GRID_HEIGHT];
+        let path_cells = game.path_cells();
+        let mut kinds = vec![vec![Vec::new(); GRID_WIDTH]; GRID_HEIGHT];
+        let mut stunned = [[false; GRID_WIDTH]; GRID_HEIGHT];
+        let mut regenerating = [[false; GRID_WIDTH]; GRID_HEIGHT];
+        let mut armor_types = vec![vec![Vec::new(); GRID_WIDTH]; GRID_HEIGHT];
+        let mut prioritized = None;
         for e in &game.board.enemies {
-            let pos_i = e.position.floor() as usize % grid_indices.len();
-            let (grid_y, grid_x) = grid_indices[pos_i];
-            counts[grid_y][grid_x] += 1;
+            let (grid_y, grid_x) = Game::enemy_grid_cell(e);
+            kinds[grid_y][grid_x].push(e.kind);
+            stunned[grid_y][grid_x] |= e.is_stunned();
+            regenerating[grid_y][grid_x] |= e.is_regenerating();
+            if !armor_types[grid_y][grid_x].contains(&e.armor_type) {
+                armor_types[grid_y][grid_x].push(e.armor_type);
+            }
+            if game.priority_target == Some(e.id) {
+                prioritized = Some((grid_y, grid_x));
+            }
         }
+        let telegraphed = game
+            .pending_spawns()
+            .into_iter()
+            .map(|(position, _)| {
+                Game::enemy_grid_cell(&Enemy {
+                    position,
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let exit = game
+            .is_path_forked()
+            .then_some((GRID_HEIGHT - 1, GRID_WIDTH - 1));
+        let sandbox_spawn = (game.mode == GameMode::Sandbox).then(|| {
+            Game::enemy_grid_cell(&Enemy {
+                position: self.sandbox_spawn_cell as f32,
+                ..Default::default()
+            })
+        });
         for &(grid_y, grid_x) in &grid_indices {
             let cell = grid[grid_y][grid_x];
-            let text = match counts[grid_y][grid_x] {
-                0 => "".to_string(),
-                c @ _ => format!("{c}"),
+            let kinds = &kinds[grid_y][grid_x];
+            let heat = (self.settings.enemy_density_heatmap && !kinds.is_empty())
+                .then(|| heatmap_color(kinds.len()));
+            let mut line = match (kinds.len(), self.settings.enemy_render_style) {
+                (0, _) => Line::default(),
+                _ if heat.is_some() => Line::default(),
+                (c, EnemyRenderStyle::Count) => Line::raw(c.to_string()),
+                (_, EnemyRenderStyle::Glyphs) => {
+                    enemy_glyph_line(kinds, cell.width.saturating_sub(2) as usize)
+                }
+            };
+            if !kinds.is_empty() && stunned[grid_y][grid_x] {
+                line.push_span(format!(" {STUN_GLYPH}"));
+            }
+            for &armor_type in &armor_types[grid_y][grid_x] {
+                if let Some(glyph) = armor_glyph(armor_type) {
+                    line.push_span(Span::raw(glyph).dark_gray());
+                }
+            }
+            if !kinds.is_empty() && regenerating[grid_y][grid_x] {
+                line.push_span(Span::raw(format!(" {REGEN_GLYPH}")).green());
+            }
+            // Glyphs draw reflectors already, counts need telling that hitting them hurts
+            if self.settings.enemy_render_style == EnemyRenderStyle::Count
+                && kinds.contains(&EnemyKind::Reflector)
+            {
+                line.push_span(Span::raw(format!(" {REFLECT_GLYPH}")).light_cyan());
+            }
+            let mut lines = vec![line];
+            if let Some(popup) = self.damage_popups.get(&(grid_y, grid_x)) {
+                lines.push(damage_popup_line(popup));
+            }
+            let block = if prioritized == Some((grid_y, grid_x)) {
+                Block::bordered()
+                    .title(PRIORITY_GLYPH)
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::new().light_red())
+            } else if telegraphed.contains(&(grid_y, grid_x)) {
+                Block::bordered()
+                    .title(SPAWN_GLYPH)
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::new().yellow())
+            } else if exit == Some((grid_y, grid_x)) {
+                Block::bordered()
+                    .title(EXIT_GLYPH)
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::new().light_red())
+            } else if sandbox_spawn == Some((grid_y, grid_x)) {
+                Block::bordered()
+                    .title(SANDBOX_SPAWN_GLYPH)
+                    .title_alignment(Alignment::Center)
+                    .border_style(Style::new().magenta())
+            } else {
+                Block::bordered()
             };
-            let p = Paragraph::new(text)
-                .block(Block::bordered())
+            let track = path_cells
+                .get(&(grid_y, grid_x))
+                .filter(|_| self.settings.path_track);
+            let style = match (heat, track) {
+                (Some(color), _) => Style::new().bg(color).fg(Catppuccin::new().crust),
+                (None, Some(&progress)) => Style::new().gray().bg(Theme.path_track(progress)),
+                (None, None) => Style::new().gray(),
+            };
+            let p = Paragraph::new(lines)
+                .block(block)
                 .alignment(Alignment::Center)
-                .style(Style::new().gray());
+                .style(style);
             p.render(cell.clone(), buf);
         }
+        if self.is_spawn_pulse_due {
+            self.is_spawn_pulse_due = false;
+            if !self.settings.reduce_motion {
+                let duration = (300.0 / self.settings.animation_speed()) as u32;
+                for &(grid_y, grid_x) in &telegraphed {
+                    let fx = fx::fade_from_fg(Color::Yellow, (duration, Interpolation::SineOut))
+                        .with_area(grid[grid_y][grid_x]);
+                    self.effects.0.add_effect(fx);
+                }
+            }
+        }
 
         // render cursor and selected
+        if let Some((sele_y, sele_x)) = game.selected.filter(|_| !self.is_selection_animated) {
+            // The animated selection border is skipped, draw a static one instead
+            let sele_cell = grid[sele_y + 1][sele_x + 1].clone();
+            Block::bordered()
+                .border_style(Style::new().cyan())
+                .render(sele_cell, buf);
+        }
         let (cursor_y, cursor_x) = game.cursor;
         let cursor_cell = grid[cursor_y + 1][cursor_x + 1].clone();
         let block = Block::bordered().border_style(Style::new().magenta());
         block.render(cursor_cell, buf);
+        if let Some(inspect) = self.inspect {
+            Block::bordered()
+                .border_type(BorderType::Thick)
+                .border_style(Style::new().yellow())
+                .render(grid[inspect.cell.0][inspect.cell.1], buf);
+        }
+    }
+}
+
+/// Splits `grid_area` into the board's cells, indexed `[row][column]`. `None` if the area is too
+/// small for every cell to be at least [`MIN_CELL_SIZE`] across.
+fn grid_cells(grid_area: Rect) -> Option<Vec<Vec<Rect>>> {
+    let grid = Layout::vertical(vec![Constraint::Max(10); BOARD_HEIGHT])
+        .split(grid_area)
+        .iter()
+        .map(|&row| {
+            Layout::horizontal(vec![Constraint::Max(20); BOARD_WIDTH])
+                .split(row)
+                .to_vec()
+        })
+        .collect::<Vec<_>>();
+    let fits = grid.len() == BOARD_HEIGHT
+        && grid.iter().flatten().count() == BOARD_WIDTH * BOARD_HEIGHT
+        && grid
+            .iter()
+            .flatten()
+            .all(|cell| cell.width >= MIN_CELL_SIZE && cell.height >= MIN_CELL_SIZE);
+    fits.then_some(grid)
+}
+
+/// Asks for a bigger terminal, in place of a board that doesn't fit `area`.
+fn render_too_small(area: Rect, buf: &mut Buffer) {
+    Paragraph::new("Terminal too small, enlarge it to see the board")
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .red()
+        .render(area, buf);
+}
+
+/// Clears a `width` x `height` box centered in `area` and draws a titled border around it.
+/// Returns the area inside the border.
+fn render_popup(area: Rect, width: u16, height: u16, title: &str, buf: &mut Buffer) -> Rect {
+    let [popup] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [popup] = Layout::vertical([Constraint::Length(height)])
+        .flex(Flex::Center)
+        .areas(popup);
+    let block = Block::bordered()
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .border_type(BorderType::Rounded);
+    let inner = block.inner(popup);
+    Clear.render(popup, buf);
+    block.render(popup, buf);
+    inner
+}
+
+/// Width of the damage bars of [`element_breakdown`].
+const BREAKDOWN_BAR_WIDTH: usize = 10;
+
+/// A header and a row per element in `stats`: its name, a bar of its damage relative to the
+/// element that dealt the most, then its damage and kills. Empty if no element dealt any.
+fn element_breakdown(stats: &BTreeMap<AllyElement, AllyStats>) -> Vec<Line<'static>> {
+    let most = stats.values().map(|s| s.damage_dealt).max().unwrap_or(0);
+    if most == 0 {
+        return Vec::new();
+    }
+    let rows = stats.iter().map(|(&element, s)| {
+        let filled = (s.damage_dealt * BREAKDOWN_BAR_WIDTH).div_ceil(most);
+        Line::from(vec![
+            Span::raw(format!("{:<9}", format!("{element:?}"))),
+            Span::raw("█".repeat(filled)).fg(ally_element_color(element)),
+            Span::raw(" ".repeat(BREAKDOWN_BAR_WIDTH - filled)),
+            Span::raw(format!(
+                " {} dmg {} k",
+                format_count(s.damage_dealt),
+                format_count(s.kills)
+            )),
+        ])
+    });
+    [
+        Line::raw(""),
+        Line::raw("By element").yellow().bold().centered(),
+    ]
+    .into_iter()
+    .chain(rows)
+    .collect()
+}
+
+/// The instruction box of the tutorial `step`.
+fn render_tutorial(step: TutorialStep, area: Rect, buf: &mut Buffer) {
+    let title = format!("Tutorial {}/{}", step.number(), TutorialStep::COUNT);
+    let width = (step.instruction().len() as u16 + 4).min(area.width);
+    let inner = render_popup(area, width, 4, &title, buf);
+    Paragraph::new(vec![
+        Line::raw(step.instruction()).yellow().bold(),
+        Line::raw("Press Tab to skip the tutorial").dark_gray(),
+    ])
+    .alignment(Alignment::Center)
+    .render(inner, buf);
+}
+
+/// `(label, value, whether lower is better)` of the stats compared on the merge panel.
+fn ally_stats(ally: &Ally) -> [(&'static str, f32, bool); ALLY_STAT_ROWS] {
+    [
+        ("Lv", ally.level as f32, false),
+        ("ATK", ally.atk as f32, false),
+        ("Range", ally.range as f32, false),
+        ("AOE", ally.aoe_range as f32, false),
+        ("Attacks/s", ally.attacks_per_second, false),
+        ("Special", ally.special_value, false),
+    ]
+}
+
+/// The stat table of `ally` on the merge panel. Stats that differ from `other` are green where
+/// `ally` is better and red where it's worse.
+fn ally_stat_lines(ally: &Ally, other: &Ally) -> Vec<Line<'static>> {
+    ally_stats(ally)
+        .into_iter()
+        .zip(ally_stats(other))
+        .map(|((label, value, lower_is_better), (_, other_value, _))| {
+            let text = format!("{label}: {}", (value * 100.0).round() / 100.0);
+            if value == other_value {
+                Line::raw(text)
+            } else if (value < other_value) == lower_is_better {
+                Line::raw(text).green()
+            } else {
+                Line::raw(text).red()
+            }
+        })
+        .collect()
+}
+
+/// Two-letter abbreviation of `element`, for labels too small for its name.
+fn element_abbreviation(element: AllyElement) -> &'static str {
+    match element {
+        AllyElement::Basic => "Ba",
+        AllyElement::Slow => "Sl",
+        AllyElement::Aoe => "Ao",
+        AllyElement::Dot => "Do",
+        AllyElement::Critical => "Cr",
+        AllyElement::Chain => "Ch",
+        AllyElement::Coin => "Co",
+        AllyElement::Aura => "Au",
+    }
+}
+
+/// Badge of a dual-element ally: [`DUAL_GLYPH`] and both elements abbreviated, each on its
+/// element's color. `None` for single-element allies.
+fn dual_badge(ally: &Ally) -> Option<Line<'static>> {
+    let second = ally.second_element?;
+    let element_span = |element| {
+        Span::raw(element_abbreviation(element))
+            .bg(ally_element_color(element))
+            .fg(Color::Black)
+    };
+    Some(Line::from(vec![
+        Span::raw(DUAL_GLYPH).white(),
+        element_span(ally.element),
+        element_span(second),
+    ]))
+}
+
+/// Arrow pointing where a facing Aoe ally splashes.
+fn facing_arrow(facing: Direction) -> char {
+    match facing {
+        Direction::Up => '↑',
+        Direction::Down => '↓',
+        Direction::Left => '←',
+        Direction::Right => '→',
     }
 }
 
@@ -313,10 +1489,63 @@ fn ally_element_color(elem: AllyElement) -> Color {
         AllyElement::Dot => Color::LightGreen,
         AllyElement::Aoe => Color::LightRed,
         AllyElement::Critical => Color::Gray,
+        AllyElement::Chain => Color::LightMagenta,
+        AllyElement::Coin => Color::LightYellow,
+        AllyElement::Aura => Color::Cyan,
+    }
+}
+
+fn cell_center(cell: Rect) -> Position {
+    Position::new(cell.x + cell.width / 2, cell.y + cell.height / 2)
+}
+
+/// The animation of an attack by an ally of `element`, flying from `from` to `to` in about
+/// `duration` milliseconds.
+fn attack_effects(element: AllyElement, from: Rect, to: Rect, duration: u32) -> Vec<Effect> {
+    let color = ally_element_color(element);
+    let (from, to_center) = (cell_center(from), cell_center(to));
+    let (glyph, trail) = match element {
+        AllyElement::Basic => ('•', false),
+        AllyElement::Slow => ('❄', false),
+        AllyElement::Dot => ('∘', false),
+        AllyElement::Aoe => ('●', false),
+        AllyElement::Critical => ('·', true),
+        AllyElement::Chain => (CHAIN_GLYPH, true),
+        AllyElement::Coin => ('$', false),
+        AllyElement::Aura => ('○', false),
+    };
+    // A critical streak is drawn in one quick stroke
+    let flight = if trail { duration / 2 } else { duration };
+    let mut effects = vec![effect::projectile(
+        glyph, color, from, to_center, trail, flight,
+    )];
+    // Dot and Aoe hits leave a cloud or a blast on the target's cell once they land
+    if matches!(element, AllyElement::Dot | AllyElement::Aoe) {
+        let splash = fx::fade_from(color, color, (duration, Interpolation::QuadOut)).with_area(to);
+        effects.push(fx::delay(flight, splash));
     }
+    effects
 }
 
-fn mixed_element_color(c0: Color, c1: Color, step: usize) -> RepeatingColorCycle {
+/// Gradient of the cooldown glow of an ally in `color`, from dim right after it attacks to a
+/// bright highlight once it is ready.
+fn cooldown_glow_colors(color: Color) -> ClampedColorCycle {
+    let (h, s, _) = color.to_hsl_f32();
+    ClampedColorCycle::new(
+        Color::from_hsl_f32(h, s, 20.0),
+        &[(16, color), (4, Color::from_hsl_f32(h, s, 80.0))],
+    )
+}
+
+/// Color cycle blending two element colors. `step` sets how many frames each color is held,
+/// shortened or stretched by `animation_speed`.
+fn mixed_element_color(
+    c0: Color,
+    c1: Color,
+    step: usize,
+    animation_speed: f32,
+) -> RepeatingColorCycle {
+    let step = ((step as f32 / animation_speed).round() as usize).max(1);
     let color_step: usize = 7 * step;
 
     let (h0, s0, l0) = c0.to_hsl_f32();
@@ -370,6 +1599,280 @@ fn mixed_element_color(c0: Color, c1: Color, step: usize) -> RepeatingColorCycle
     )
 }
 
+/// `-damage`, with an arrow when the hit was super or not very effective.
+fn damage_popup_line(popup: &DamagePopup) -> Line<'static> {
+    let text = format!("-{}", format_count(popup.damage));
+    if popup.effectiveness > 1.0 {
+        Line::raw(format!("{text}↑")).light_green()
+    } else if popup.effectiveness < 1.0 {
+        Line::raw(format!("{text}↓")).dark_gray()
+    } else {
+        Line::raw(text).white()
+    }
+}
+
+/// Formats a count to fit narrow panels: thousands separators below 100,000, then abbreviated
+/// with one decimal (`123.4k`, `1.5M`, `2.0B`).
+fn format_count(n: usize) -> String {
+    if n < 100_000 {
+        let digits = n.to_string();
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push(',');
+            }
+            out.push(c);
+        }
+        return out;
+    }
+    // Thresholds sit just below the next unit so rounding never prints "1000.0k"
+    let (unit, suffix) = if n < 999_950 {
+        (1e3, "k")
+    } else if n < 999_950_000 {
+        (1e6, "M")
+    } else {
+        (1e9, "B")
+    };
+    format!("{:.1}{suffix}", n as f64 / unit)
+}
+
+fn enemy_kind_glyph(kind: EnemyKind) -> Span<'static> {
+    match kind {
+        EnemyKind::Grunt => Span::raw("●").light_red(),
+        EnemyKind::Tank => Span::raw("■").magenta(),
+        EnemyKind::Runner => Span::raw("▸").light_yellow(),
+        EnemyKind::Reflector => Span::raw(REFLECT_GLYPH).light_cyan(),
+    }
+}
+
+fn armor_glyph(armor_type: ArmorType) -> Option<&'static str> {
+    match armor_type {
+        ArmorType::Unarmored => None,
+        ArmorType::Light => Some(LIGHT_ARMOR_GLYPH),
+        ArmorType::Heavy => Some(HEAVY_ARMOR_GLYPH),
+    }
+}
+
+/// Minimap rows per board row that fit a board of `(columns, rows)` in `area` without stretching
+/// it, never above 1. Minimap columns count double, as terminal cells are about twice as tall as
+/// they are wide.
+fn minimap_scale((columns, rows): (usize, usize), area: Rect) -> f32 {
+    let by_width = area.width as f32 / (columns as f32 * 2.0);
+    let by_height = area.height as f32 / rows as f32;
+    by_width.min(by_height).min(1.0)
+}
+
+/// Terminal cell in `map` that board position `(x, y)` falls in, at `scale` from
+/// [`minimap_scale`].
+fn minimap_point((x, y): (f32, f32), scale: f32, map: Rect) -> (u16, u16) {
+    let col = ((x * 2.0 * scale) as u16).min(map.width.saturating_sub(1));
+    let row = ((y * scale) as u16).min(map.height.saturating_sub(1));
+    (map.x + col, map.y + row)
+}
+
+/// Tint of a path cell holding `count` enemies on the density heatmap, from cool to hot
+/// through the theme's palette.
+fn heatmap_color(count: usize) -> Color {
+    let palette = Catppuccin::new();
+    let colors = ClampedColorCycle::new(
+        palette.teal,
+        &[(8, palette.yellow), (8, palette.peach), (8, palette.red)],
+    );
+    let progress = (count as f32 - 1.0) / (HEATMAP_SATURATION - 1) as f32;
+    *colors.color_at_progress(progress)
+}
+
+/// A glyph per enemy of `kinds`, grouped by kind, fitting in `width` columns. Enemies past
+/// [`ENEMY_GLYPH_CAP`] or what fits are summed up as `+N`.
+fn enemy_glyph_line(kinds: &[EnemyKind], width: usize) -> Line<'static> {
+    let mut kinds = kinds.to_vec();
+    kinds.sort_by_key(|&kind| EnemyKind::ALL.iter().position(|&k| k == kind));
+    let shown = if kinds.len() <= ENEMY_GLYPH_CAP.min(width) {
+        kinds.len()
+    } else {
+        let overflow_width = format!("+{}", kinds.len()).len();
+        ENEMY_GLYPH_CAP
+            .min(width.saturating_sub(overflow_width))
+            .max(1)
+    };
+    let mut spans = kinds[..shown]
+        .iter()
+        .map(|&kind| enemy_kind_glyph(kind))
+        .collect::<Vec<_>>();
+    if shown < kinds.len() {
+        spans.push(Span::raw(format!("+{}", kinds.len() - shown)));
+    }
+    Line::from(spans)
+}
+
+/// Rows the boss health bars take with `bosses` living bosses: one each up to
+/// [`BOSS_BAR_CAP`], and one for the rest.
+fn boss_bar_rows(bosses: usize) -> u16 {
+    (bosses.min(BOSS_BAR_CAP) + usize::from(bosses > BOSS_BAR_CAP)) as u16
+}
+
+/// Share of its HP `boss` has left. Enemies spawned without a `max_hp` count as unhurt.
+fn boss_health(boss: &Enemy) -> f64 {
+    match boss.max_hp.max(boss.hp) {
+        0 => 0.0,
+        max_hp => boss.hp as f64 / max_hp as f64,
+    }
+}
+
+/// The kill reward multiplier next to the coins, green while it helps and red while it hurts.
+/// Nothing at `1.0`.
+fn reward_multiplier_span(multiplier: f32) -> Span<'static> {
+    let text = format!("Reward x{multiplier}  ");
+    if multiplier > 1.0 {
+        Span::raw(text).green()
+    } else if multiplier < 1.0 {
+        Span::raw(text).red()
+    } else {
+        Span::default()
+    }
+}
+
+/// `seconds` as `MM:SS`, minutes growing past two digits if need be.
+fn format_duration(seconds: f32) -> String {
+    let seconds = seconds as u64;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Linear interpolation between two color channels, in either direction.
 fn lerp(a: u8, b: u8, t: f32) -> u8 {
-    a + ((b - a) as f32 * t).floor() as u8
+    (a as f32 + (b as f32 - a as f32) * t)
+        .round()
+        .clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui_image::picker::Picker;
+
+    #[test]
+    fn only_dual_allies_get_a_badge() {
+        let mut ally = Ally {
+            element: AllyElement::Critical,
+            ..Default::default()
+        };
+        assert_eq!(dual_badge(&ally), None);
+        ally.second_element = Some(AllyElement::Chain);
+        let badge = dual_badge(&ally).unwrap();
+        assert_eq!(badge.to_string(), format!("{DUAL_GLYPH}CrCh"));
+        assert_eq!(
+            badge.spans[1].style.bg,
+            Some(ally_element_color(AllyElement::Critical))
+        );
+        assert_eq!(
+            badge.spans[2].style.bg,
+            Some(ally_element_color(AllyElement::Chain))
+        );
+    }
+
+    #[test]
+    fn tiny_boards_ask_for_room_instead_of_panicking() {
+        let tiny = Rect::new(0, 0, 1, 1);
+        assert_eq!(grid_cells(tiny), None);
+        let mut app = App::with_picker(Picker::from_fontsize((8, 16)));
+        app.game = Some(Game::new());
+        let mut buf = Buffer::empty(tiny);
+        app.render_grid(tiny, &mut buf);
+        assert_eq!(grid_cells(Rect::new(0, 0, 30, 10)), None);
+
+        let grid = grid_cells(Rect::new(0, 0, 90, 25)).unwrap();
+        assert_eq!(grid.len(), BOARD_HEIGHT);
+        assert!(grid.iter().all(|row| row.len() == BOARD_WIDTH));
+    }
+
+    #[test]
+    fn heatmap_gets_hotter_up_to_saturation() {
+        let palette = Catppuccin::new();
+        assert_eq!(heatmap_color(1), palette.teal);
+        assert_ne!(heatmap_color(4), heatmap_color(1));
+        assert_eq!(heatmap_color(HEATMAP_SATURATION), palette.red);
+        assert_eq!(heatmap_color(HEATMAP_SATURATION * 2), palette.red);
+    }
+
+    #[test]
+    fn boss_bars_stack_up_to_a_cap() {
+        assert_eq!(boss_bar_rows(0), 0);
+        assert_eq!(boss_bar_rows(2), 2);
+        assert_eq!(boss_bar_rows(BOSS_BAR_CAP), BOSS_BAR_CAP as u16);
+        assert_eq!(boss_bar_rows(BOSS_BAR_CAP + 5), BOSS_BAR_CAP as u16 + 1);
+
+        let boss = |hp, max_hp| Enemy {
+            kind: EnemyKind::Tank,
+            hp,
+            max_hp,
+            ..Default::default()
+        };
+        assert_eq!(boss_health(&boss(50, 200)), 0.25);
+        assert_eq!(boss_health(&boss(80, 0)), 1.0);
+    }
+
+    #[test]
+    fn minimap_downscales_big_boards_without_stretching() {
+        let area = Rect::new(0, 0, 18, 5);
+        assert_eq!(minimap_scale((9, 5), area), 1.0);
+        assert_eq!(minimap_scale((9, 5), Rect::new(0, 0, 80, 40)), 1.0);
+
+        // Twice as wide as fits: both axes shrink by half
+        let scale = minimap_scale((18, 5), area);
+        assert_eq!(scale, 0.5);
+        assert_eq!(minimap_point((17.0, 4.0), scale, area), (17, 2));
+        assert_eq!(
+            minimap_point((0.0, 0.0), scale, Rect::new(3, 4, 18, 5)),
+            (3, 4)
+        );
+        // The far edge of the board stays inside
+        assert_eq!(minimap_point((18.0, 5.0), scale, area), (17, 2));
+    }
+
+    #[test]
+    fn format_count_at_boundaries() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1,000");
+        assert_eq!(format_count(99_999), "99,999");
+        assert_eq!(format_count(100_000), "100.0k");
+        assert_eq!(format_count(999_949), "999.9k");
+        assert_eq!(format_count(999_999), "1.0M");
+        assert_eq!(format_count(1_500_000), "1.5M");
+        assert_eq!(format_count(2_000_000_000), "2.0B");
+    }
+
+    #[test]
+    fn format_duration_as_minutes_and_seconds() {
+        assert_eq!(format_duration(0.0), "00:00");
+        assert_eq!(format_duration(59.9), "00:59");
+        assert_eq!(format_duration(754.0), "12:34");
+        assert_eq!(format_duration(6000.0), "100:00");
+    }
+
+    #[test]
+    fn enemy_glyphs_overflow_into_a_count() {
+        let text = |kinds: &[EnemyKind], width| enemy_glyph_line(kinds, width).to_string();
+        use EnemyKind::*;
+        assert_eq!(text(&[Runner, Grunt, Tank], 10), "●■▸");
+        assert_eq!(text(&[Grunt; 4], 10), "●●●●");
+        assert_eq!(text(&[Grunt; 12], 10), "●●●●+8");
+        // A narrow cell keeps room for the count
+        assert_eq!(text(&[Tank; 5], 4), "■■+3");
+    }
+
+    #[test]
+    fn lerp_increasing() {
+        assert_eq!(lerp(10, 200, 0.0), 10);
+        assert_eq!(lerp(10, 200, 0.5), 105);
+        assert_eq!(lerp(10, 200, 1.0), 200);
+    }
+
+    #[test]
+    fn lerp_decreasing() {
+        assert_eq!(lerp(200, 10, 0.0), 200);
+        assert_eq!(lerp(200, 10, 0.5), 105);
+        assert_eq!(lerp(200, 10, 1.0), 10);
+        assert_eq!(lerp(255, 0, 1.0), 0);
+    }
 }